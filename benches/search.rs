@@ -0,0 +1,123 @@
+// Node counts of the parallel search strategies and move ordering heuristics
+// on positions that are still open enough for alpha-beta to matter. Run with
+// `cargo bench`.
+use matryoshka_tic_tac_toe::game::Game;
+use matryoshka_tic_tac_toe::solver::{Algorithm, MoveOrdering, Solver};
+use std::time::{Duration, Instant};
+
+type Position = (usize, Vec<(usize, usize, usize)>);
+
+fn run(solver: &Solver, game: &Game) -> (i8, u64, Duration) {
+    let now = Instant::now();
+    let (_, score) = solver.search_move(game);
+    (score, solver.nodes(), now.elapsed())
+}
+
+fn main() {
+    let positions: Vec<Position> = vec![
+        (5, vec![(1, 1, 2), (0, 0, 1), (2, 2, 0)]),
+        (5, vec![(0, 1, 1), (1, 1, 0), (2, 1, 2)]),
+        (5, vec![(1, 1, 4), (0, 0, 4), (2, 2, 3)]),
+        (
+            6,
+            vec![(1, 1, 5), (0, 0, 5), (2, 2, 4), (0, 2, 4), (2, 0, 3)],
+        ),
+        (
+            6,
+            vec![(1, 1, 2), (0, 0, 1), (2, 2, 0), (0, 2, 0), (2, 0, 1)],
+        ),
+        (
+            6,
+            vec![(0, 1, 5), (1, 1, 5), (2, 1, 4), (1, 0, 4), (1, 2, 3)],
+        ),
+    ];
+
+    let games = positions
+        .into_iter()
+        .map(|(number_of_pieces, moves)| {
+            let mut game = Game::new_with_size(number_of_pieces);
+            for (i, j, k) in moves {
+                game = game.make_move(i, j, k).unwrap();
+            }
+            game
+        })
+        .collect::<Vec<Game>>();
+
+    println!("Parallel search, no move ordering");
+    println!(
+        "{:>6} {:>6} {:>20} {:>14} {:>20} {:>14} {:>8}",
+        "pieces", "score", "parallel nodes", "time", "ybwc nodes", "time", "ratio"
+    );
+    for game in games.iter() {
+        let parallel = Solver::new()
+            .with_algorithm(Algorithm::Parallel)
+            .with_ordering(MoveOrdering::none());
+        let ybwc = Solver::new()
+            .with_algorithm(Algorithm::YoungBrothersWait)
+            .with_ordering(MoveOrdering::none());
+        let (parallel_score, parallel_nodes, parallel_time) = run(&parallel, game);
+        let (ybwc_score, ybwc_nodes, ybwc_time) = run(&ybwc, game);
+
+        assert_eq!(parallel_score, ybwc_score);
+        assert!(ybwc_nodes < parallel_nodes);
+
+        println!(
+            "{:>6} {:>6} {:>20} {:>14?} {:>20} {:>14?} {:>8.1}",
+            game.get_number_of_pieces(),
+            ybwc_score,
+            parallel_nodes,
+            parallel_time,
+            ybwc_nodes,
+            ybwc_time,
+            parallel_nodes as f64 / ybwc_nodes as f64
+        );
+    }
+
+    let orderings = [
+        "none", "wins", "blocks", "captures", "killers", "history", "largest", "all",
+    ];
+
+    println!();
+    println!("Young Brothers Wait, nodes per move ordering");
+    print!("{:>6}", "pieces");
+    for ordering in orderings.iter() {
+        print!(" {:>10}", ordering);
+    }
+    println!();
+    for game in games.iter() {
+        print!("{:>6}", game.get_number_of_pieces());
+        let mut expected_score = None;
+        for ordering in orderings.iter() {
+            let solver = Solver::new().with_ordering(ordering.parse().unwrap());
+            let (score, nodes, _) = run(&solver, game);
+            assert_eq!(*expected_score.get_or_insert(score), score);
+            print!(" {:>10}", nodes);
+        }
+        println!();
+    }
+
+    println!();
+    println!("Proof-number search against Young Brothers Wait");
+    println!(
+        "{:>6} {:>6} {:>20} {:>14} {:>20} {:>14}",
+        "pieces", "score", "pns nodes", "time", "ybwc nodes", "time"
+    );
+    for game in games.iter() {
+        let pns = Solver::new().with_algorithm(Algorithm::ProofNumber);
+        let ybwc = Solver::new();
+        let (pns_score, pns_nodes, pns_time) = run(&pns, game);
+        let (ybwc_score, ybwc_nodes, ybwc_time) = run(&ybwc, game);
+
+        assert_eq!(pns_score, ybwc_score);
+
+        println!(
+            "{:>6} {:>6} {:>20} {:>14?} {:>20} {:>14?}",
+            game.get_number_of_pieces(),
+            pns_score,
+            pns_nodes,
+            pns_time,
+            ybwc_nodes,
+            ybwc_time
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use crate::game::{Game, Move, PlayerKind};
+use crate::hint::Hint;
+use crate::io::{format_move, prompt_input, prompt_move, Input};
+use crate::mcts::Mcts;
+use crate::solver::Solver;
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Anything that can play a game: a person at the console, one of the
+/// engines or a player somewhere else
+pub trait Agent {
+    fn choose_move(&mut self, game: &Game) -> Move;
+
+    /// Whether an illegal move is asked for again, as it is from a person
+    /// who mistyped. An engine would only play the same move again.
+    fn asks_again(&self) -> bool {
+        false
+    }
+}
+
+/// Whoever is at the console, typing in moves
+pub struct Human;
+
+impl Agent for Human {
+    fn choose_move(&mut self, _game: &Game) -> Move {
+        prompt_move()
+    }
+
+    fn asks_again(&self) -> bool {
+        true
+    }
+}
+
+/// Whoever is at the console, who can type `hint` instead of a move to see
+/// the lines either player can complete next and the solver's suggestion.
+/// A clone shares the solver, so both players at one console are coached
+/// without reading the lookup twice.
+#[derive(Clone)]
+pub struct Coached {
+    solver: Rc<RefCell<Solver>>,
+    search: bool,
+}
+
+impl Default for Coached {
+    fn default() -> Self {
+        Self::new(Solver::new())
+    }
+}
+
+impl Coached {
+    pub fn new(solver: Solver) -> Self {
+        Self {
+            solver: Rc::new(RefCell::new(solver)),
+            search: true,
+        }
+    }
+
+    /// Whether a hint searches positions the tablebase and the lookup don't
+    /// know. A search from early in a game of 6 or more pieces can take a
+    /// long while, so without it the hint only suggests what they know. On
+    /// by default.
+    pub fn with_search(mut self, search: bool) -> Self {
+        self.search = search;
+        self
+    }
+}
+
+impl Agent for Coached {
+    fn choose_move(&mut self, game: &Game) -> Move {
+        loop {
+            match prompt_input() {
+                Input::Move(m) => break m,
+                Input::Hint if self.search => {
+                    print!("{}", Hint::new(game, &mut self.solver.borrow_mut()))
+                }
+                Input::Hint => print!("{}", Hint::recalled(game, &self.solver.borrow())),
+            }
+        }
+    }
+
+    fn asks_again(&self) -> bool {
+        true
+    }
+}
+
+impl Agent for Solver {
+    /// Searches for whoever's turn it is, so one solver can play either side
+    fn choose_move(&mut self, game: &Game) -> Move {
+        self.kind = game.current_player_kind();
+        self.find_move(game)
+    }
+}
+
+impl Agent for Mcts {
+    fn choose_move(&mut self, game: &Game) -> Move {
+        self.find_move(game)
+    }
+}
+
+/// Plays any legal move
+pub struct Random {
+    rng: SmallRng,
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Random {
+    pub fn new() -> Self {
+        Self {
+            rng: SmallRng::from_entropy(),
+        }
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Agent for Random {
+    fn choose_move(&mut self, game: &Game) -> Move {
+        game.legal_moves()
+            .choose(&mut self.rng)
+            .map(|(b_move, _)| *b_move)
+            .unwrap_or((0, 0, 0))
+    }
+}
+
+/// Plays `game` out between `x` and `o`, calling `on_position` before every
+/// move. An illegal move from a person is asked for again, one from an
+/// engine is an error. The game stops early if the player to move is stuck
+/// without a legal move, leaving it unfinished.
+pub fn play_match<F>(
+    game: Game,
+    x: &mut dyn Agent,
+    o: &mut dyn Agent,
+    on_position: F,
+) -> Result<Game, String>
+where
+    F: FnMut(&Game),
+{
+    play_match_moves(game, x, o, on_position).map(|(game, _)| game)
+}
+
+/// `play_match`, also returning every move that was played in order
+pub fn play_match_moves<F>(
+    mut game: Game,
+    x: &mut dyn Agent,
+    o: &mut dyn Agent,
+    mut on_position: F,
+) -> Result<(Game, Vec<Move>), String>
+where
+    F: FnMut(&Game),
+{
+    let mut moves = Vec::new();
+    while !game.is_over() {
+        on_position(&game);
+
+        let kind = game.current_player_kind();
+        let agent: &mut dyn Agent = match kind {
+            PlayerKind::X => &mut *x,
+            PlayerKind::O => &mut *o,
+        };
+        let (row, col, size) = agent.choose_move(&game);
+
+        match game.clone().make_move(row, col, size) {
+            Ok(next) => {
+                game = next;
+                moves.push((row, col, size));
+            }
+            Err(_) if agent.asks_again() => (),
+            Err(e) => return Err(illegal(kind, (row, col, size), e)),
+        }
+    }
+    Ok((game, moves))
+}
+
+/// Why an engine's move was turned down
+pub(crate) fn illegal(kind: PlayerKind, (row, col, size): Move, e: &str) -> String {
+    format!(
+        "{} played {}, which isn't legal: {}",
+        match kind {
+            PlayerKind::X => "x",
+            PlayerKind::O => "o",
+        },
+        format_move((row, col, size)),
+        e
+    )
+}
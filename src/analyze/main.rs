@@ -0,0 +1,90 @@
+extern crate getopts;
+use getopts::Options;
+use matryoshka_tic_tac_toe::record::parse_records;
+use matryoshka_tic_tac_toe::report::Report;
+use matryoshka_tic_tac_toe::solver::{Solver, Tablebase};
+use std::env;
+use std::fs;
+
+fn print_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} FILE [options]", program);
+    println!("{}", opts.usage(&brief));
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+    let mut solver = Solver::new();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "g",
+        "game",
+        "Which game of the records file to analyse, from 1, defaults to all of them",
+        "NUMBER",
+    );
+    opts.optopt(
+        "t",
+        "tablebase",
+        "Look positions up in a tablebase made by lookup-generator --retrograde",
+        "FILE",
+    );
+    opts.optflag("h", "help", "print this help menu");
+    let m = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    if m.opt_present("h") || m.free.is_empty() {
+        print_usage(&program, opts);
+        return;
+    }
+    if let Some(path) = m.opt_str("t") {
+        match Tablebase::read(&path) {
+            Ok(t) => solver = solver.with_tablebase(t),
+            Err(e) => {
+                eprintln!("Couldn't read tablebase {}: {}", path, e);
+                return;
+            }
+        }
+    }
+
+    let path = &m.free[0];
+    let records = match fs::read_to_string(path).map_err(|e| e.to_string()) {
+        Ok(text) => parse_records(&text),
+        Err(e) => Err(e),
+    };
+    let mut records = match records {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("Couldn't read records from {}: {}", path, e);
+            return;
+        }
+    };
+    match m.opt_get::<usize>("g") {
+        Ok(Some(number)) => match number.checked_sub(1).filter(|i| *i < records.len()) {
+            Some(i) => records = vec![records.remove(i)],
+            None => {
+                eprintln!("There are {} games in {}", records.len(), path);
+                return;
+            }
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+        _ => (),
+    }
+
+    for (i, record) in records.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        match Report::new(record, &mut solver) {
+            Ok(report) => print!("{}", report),
+            Err(e) => eprintln!("Couldn't analyse game {}: {}", i + 1, e),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::game::Game;
+use crate::raster::{Raster, PALETTE};
+use crate::record::GameRecord;
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+/// Replays a recorded game as an animated GIF, a frame for the empty board
+/// and one for every move. The last frame shows the winning line, if there is
+/// one, and stays up for longer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Animation {
+    square: u16,
+    delay_ms: u16,
+    final_delay_ms: u16,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Animation {
+    /// 100 pixel squares, 800ms a move and 3s on the final position
+    pub fn new() -> Self {
+        Self {
+            square: 100,
+            delay_ms: 800,
+            final_delay_ms: 3000,
+        }
+    }
+
+    pub fn with_square(mut self, square: u16) -> Self {
+        self.square = square;
+        self
+    }
+
+    /// How long every move stays up
+    pub fn with_delay(mut self, delay_ms: u16) -> Self {
+        self.delay_ms = delay_ms;
+        self
+    }
+
+    /// How long the final position stays up before the replay starts again
+    pub fn with_final_delay(mut self, final_delay_ms: u16) -> Self {
+        self.final_delay_ms = final_delay_ms;
+        self
+    }
+
+    /// Every frame, with how long it stays up in milliseconds
+    pub fn frames(&self, record: &GameRecord) -> Result<Vec<(Raster, u16)>, String> {
+        let mut positions = vec![Game::new_with_size(record.number_of_pieces)];
+        for (row, col, size) in &record.moves {
+            let next = positions
+                .last()
+                .unwrap()
+                .clone()
+                .make_move(*row, *col, *size)
+                .map_err(|e| format!("Move {}: {}", positions.len(), e))?;
+            positions.push(next);
+        }
+
+        let last = positions.len() - 1;
+        Ok(positions
+            .iter()
+            .enumerate()
+            .map(|(i, game)| match i == last {
+                true => (
+                    Raster::draw(game, self.square, &game.winning_line()),
+                    self.final_delay_ms,
+                ),
+                false => (Raster::draw(game, self.square, &[]), self.delay_ms),
+            })
+            .collect())
+    }
+
+    /// Writes the replay as a GIF that loops forever
+    pub fn write_gif<W: Write>(&self, record: &GameRecord, out: W) -> io::Result<()> {
+        let frames = self
+            .frames(record)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let (width, height) = (frames[0].0.width, frames[0].0.height);
+        let mut encoder =
+            gif::Encoder::new(out, width, height, &PALETTE).map_err(io::Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+        for (raster, delay_ms) in &frames {
+            let frame = gif::Frame {
+                width,
+                height,
+                // GIF delays are in hundredths of a second
+                delay: delay_ms / 10,
+                buffer: Cow::Borrowed(&raster.pixels),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame).map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+use crate::game::Game;
+use crate::io::{format_move, parse_move, parse_position};
+use crate::mcts::{Budget, Mcts};
+use crate::solver::Solver;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Playouts for the move played when a search is stopped before it finishes
+const FALLBACK_PLAYOUTS: usize = 2000;
+
+/// A line-based protocol for driving the solver from another program, in the
+/// spirit of UCI. Commands:
+///
+/// - `uci`, answered with `id name ...` and `uciok`
+/// - `isready`, answered with `readyok`
+/// - `newgame [pieces]`, an empty board
+/// - `position startpos|<notation> [moves 2B3 ...]`, where the notation is
+///   the one from `io::format_position`
+/// - `go [milliseconds]`, searches the position and answers with an `info`
+///   line and `bestmove 2B3`, or `bestmove none` if there is no move
+/// - `stop`, ends the search early. The move is then the lookup's if it
+///   knows the position, otherwise a quick Monte Carlo
+///   guess that may not be perfect, with an `info string` saying which.
+/// - `quit`
+///
+/// Only `stop`, `quit` and `isready` are dealt with during a search, any other
+/// command waits for it to finish.
+///
+/// The `info` line has the score for the player to move (10 for a win, 0 for
+/// a tie and -10 for a loss), the positions searched, the time taken in
+/// milliseconds and the principal variation, perfect play to the end of the
+/// game. Errors are reported as `info string ...`.
+pub struct Engine<W: Write + Send + 'static> {
+    game: Game,
+    solver: Arc<Mutex<Solver>>,
+    stop: Arc<AtomicBool>,
+    searches: Arc<AtomicU64>,
+    search: Option<JoinHandle<()>>,
+    out: Arc<Mutex<W>>,
+}
+
+impl<W: Write + Send + 'static> Engine<W> {
+    pub fn new(solver: Solver, out: W) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        Self {
+            game: Game::new(),
+            solver: Arc::new(Mutex::new(solver.with_stop(Arc::clone(&stop)))),
+            stop,
+            searches: Arc::new(AtomicU64::new(0)),
+            search: None,
+            out: Arc::new(Mutex::new(out)),
+        }
+    }
+
+    /// Handles one command, returning false once told to quit
+    pub fn handle(&mut self, line: &str) -> bool {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        match words.split_first() {
+            Some((&"uci", _)) => {
+                send(
+                    &self.out,
+                    &format!(
+                        "id name matryoshka-tic-tac-toe {}",
+                        env!("CARGO_PKG_VERSION")
+                    ),
+                );
+                send(&self.out, "uciok");
+            }
+            Some((&"isready", _)) => send(&self.out, "readyok"),
+            Some((&"newgame", args)) => {
+                self.finish();
+                match args.first().map(|pieces| pieces.parse::<usize>()) {
+                    Some(Ok(pieces)) => self.game = Game::new_with_size(pieces),
+                    Some(Err(_)) => send(&self.out, "info string expected a number of pieces"),
+                    None => self.game = Game::new(),
+                }
+            }
+            Some((&"position", args)) => {
+                self.finish();
+                match self.position(args) {
+                    Ok(game) => self.game = game,
+                    Err(e) => send(&self.out, &format!("info string {}", e)),
+                }
+            }
+            Some((&"go", args)) => {
+                self.finish();
+                let time = match args.first().map(|time| time.parse::<u64>()) {
+                    Some(Ok(time)) => Some(Duration::from_millis(time)),
+                    Some(Err(_)) => {
+                        send(&self.out, "info string expected a time in milliseconds");
+                        return true;
+                    }
+                    None => None,
+                };
+                self.go(time);
+            }
+            Some((&"stop", _)) => self.stop_search(),
+            Some((&"quit", _)) => {
+                self.stop_search();
+                return false;
+            }
+            Some((command, _)) => send(
+                &self.out,
+                &format!("info string unknown command '{}'", command),
+            ),
+            None => (),
+        }
+        true
+    }
+
+    /// Waits for a search in progress to finish by itself
+    pub fn finish(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.join().unwrap();
+        }
+    }
+
+    fn stop_search(&mut self) {
+        if self.search.is_some() {
+            self.stop.store(true, Ordering::Relaxed);
+            self.finish();
+        }
+    }
+
+    fn position(&self, args: &[&str]) -> Result<Game, String> {
+        let (position, moves) = match args.iter().position(|arg| *arg == "moves") {
+            Some(i) => (&args[..i], &args[i + 1..]),
+            None => (args, &[][..]),
+        };
+        let mut game = match position {
+            ["startpos"] => Game::new_with_size(self.game.get_number_of_pieces()),
+            notation => parse_position(&notation.join(" "))?,
+        };
+        for m in moves {
+            let (row, col, size) = parse_move(m).map_err(|_| format!("invalid move '{}'", m))?;
+            game = game
+                .make_move(row, col, size)
+                .map_err(|e| format!("illegal move {}: {}", m, e))?;
+        }
+        Ok(game)
+    }
+
+    fn go(&mut self, time: Option<Duration>) {
+        let id = self.searches.fetch_add(1, Ordering::Relaxed) + 1;
+        self.stop.store(false, Ordering::Relaxed);
+
+        if let Some(time) = time {
+            let (stop, searches) = (Arc::clone(&self.stop), Arc::clone(&self.searches));
+            thread::spawn(move || {
+                thread::sleep(time);
+                // Only the search this timer was started for
+                if searches.load(Ordering::Relaxed) == id {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+
+        let (game, solver, out) = (
+            self.game.clone(),
+            Arc::clone(&self.solver),
+            Arc::clone(&self.out),
+        );
+        self.search = Some(thread::spawn(move || {
+            let mut solver = solver.lock().unwrap();
+            search(&game, &mut solver, &out);
+        }));
+    }
+}
+
+fn search<W: Write>(game: &Game, solver: &mut Solver, out: &Mutex<W>) {
+    let started = Instant::now();
+    if game.legal_moves().is_empty() {
+        send(out, "bestmove none");
+        return;
+    }
+
+    let analysis = solver.analyse(game);
+    // The tablebase would have answered without a search that could be
+    // stopped, so only the lookup is left to ask
+    let best = match analysis.best_move {
+        Some(best) => best,
+        None => match solver.check_lookup(game) {
+            Some(known) => {
+                send(
+                    out,
+                    "info string stopped before the search finished, playing the lookup's move",
+                );
+                known
+            }
+            None => {
+                send(
+                    out,
+                    &format!(
+                        "info string stopped before the search finished, guessing from {} playouts",
+                        FALLBACK_PLAYOUTS
+                    ),
+                );
+                Mcts::new()
+                    .with_budget(Budget::Playouts(FALLBACK_PLAYOUTS))
+                    .find_move(game)
+            }
+        },
+    };
+
+    if !analysis.pv.is_empty() {
+        let mut info = String::from("info");
+        if let Some(score) = analysis.score {
+            info.push_str(&format!(" score {}", score));
+        }
+        info.push_str(&format!(
+            " nodes {} time {} pv {}",
+            analysis.nodes,
+            started.elapsed().as_millis(),
+            analysis
+                .pv
+                .iter()
+                .map(|m| format_move(*m))
+                .collect::<Vec<String>>()
+                .join(" ")
+        ));
+        send(out, &info);
+    }
+    send(out, &format!("bestmove {}", format_move(best)));
+}
+
+fn send<W: Write>(out: &Mutex<W>, line: &str) {
+    let mut out = out.lock().unwrap();
+    // Nobody is listening any more if this fails, so there is no one to tell
+    let _ = writeln!(out, "{}", line).and_then(|_| out.flush());
+}
@@ -0,0 +1,91 @@
+extern crate getopts;
+use getopts::Options;
+use matryoshka_tic_tac_toe::engine::Engine;
+use matryoshka_tic_tac_toe::solver::{Algorithm, MoveOrdering, Solver, Tablebase};
+use std::env;
+use std::io::{self, BufRead};
+
+fn print_usage(program: &str, opts: Options) {
+    let brief = format!(
+        "Usage: {} [options]\n\nReads commands from stdin, see engine::Engine for the protocol",
+        program
+    );
+    println!("{}", opts.usage(&brief));
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+    let mut solver = Solver::new();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "a",
+        "algorithm",
+        "Search algorithm: ybwc (default), parallel or pns",
+        "ALGORITHM",
+    );
+    opts.optopt(
+        "o",
+        "ordering",
+        "Move ordering heuristics: all, none or a list of wins,blocks,captures,killers,history,largest",
+        "HEURISTICS",
+    );
+    opts.optopt(
+        "t",
+        "tablebase",
+        "Answer from a tablebase made by lookup-generator --retrograde",
+        "FILE",
+    );
+
+    opts.optflag("h", "help", "print this help menu");
+    let m = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    if m.opt_present("h") {
+        print_usage(&program, opts);
+        return;
+    }
+    match m.opt_get::<Algorithm>("a") {
+        Ok(Some(a)) => solver = solver.with_algorithm(a),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+        _ => (),
+    }
+    match m.opt_get::<MoveOrdering>("o") {
+        Ok(Some(o)) => solver = solver.with_ordering(o),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+        _ => (),
+    }
+    if let Some(path) = m.opt_str("t") {
+        match Tablebase::read(&path) {
+            Ok(t) => solver = solver.with_tablebase(t),
+            Err(e) => {
+                eprintln!("Couldn't read tablebase {}: {}", path, e);
+                return;
+            }
+        }
+    }
+
+    let mut engine = Engine::new(solver, io::stdout());
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !engine.handle(&line) {
+            return;
+        }
+    }
+    // The other end hung up, but still gets the answer to its last `go`
+    engine.finish();
+}
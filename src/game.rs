@@ -1,5 +1,23 @@
 const NUMBER_OF_PIECES: usize = 6;
 
+/// The most pieces per player that `Game::key` can represent
+pub const MAX_PIECES: usize = 31;
+const KEY_X_PIECES: usize = 54;
+const KEY_O_PIECES: usize = KEY_X_PIECES + MAX_PIECES;
+const KEY_CURRENT_PLAYER: usize = KEY_O_PIECES + MAX_PIECES;
+
+/// Every row, column and diagonal as indices into `Tiles::data`
+pub const LINES: [[usize; 3]; 8] = [
+    [0, 1, 2],
+    [3, 4, 5],
+    [6, 7, 8],
+    [0, 3, 6],
+    [1, 4, 7],
+    [2, 5, 8],
+    [0, 4, 8],
+    [2, 4, 6],
+];
+
 use core::cmp::max;
 use serde::{Deserialize, Serialize};
 use std::ops::{Index, IndexMut};
@@ -12,16 +30,13 @@ pub struct Player {
 
 impl Player {
     pub fn new(kind: PlayerKind, pieces: Vec<usize>) -> Self {
-        Self {
-            kind: kind,
-            pieces: pieces,
-        }
+        Self { kind, pieces }
     }
 
-    fn remove_playable_piece(&mut self, size: usize) -> usize {
+    fn remove_playable_piece(&mut self, size: usize) -> Option<usize> {
         match self.pieces.binary_search(&size) {
-            Ok(i) => self.pieces.remove(i),
-            Err(_) => 0,
+            Ok(i) => Some(self.pieces.remove(i)),
+            Err(_) => None,
         }
     }
 
@@ -38,6 +53,9 @@ pub enum PlayerKind {
 
 pub type Tile = Option<(PlayerKind, usize)>;
 
+/// Row, column and piece size
+pub type Move = (usize, usize, usize);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Tiles {
     pub data: [Tile; 9],
@@ -77,6 +95,12 @@ pub struct Game {
     current_player_kind: PlayerKind,
 }
 
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Game {
     pub fn new() -> Self {
         Self {
@@ -103,6 +127,10 @@ impl Game {
     }
 
     pub fn make_move(mut self, row: usize, col: usize, size: usize) -> Result<Game, &'static str> {
+        if row > 2 || col > 2 {
+            return Err("There is no such tile on the board!");
+        }
+
         if let Some(other_tile) = self.tiles[row][col] {
             let (_, other_size) = other_tile;
             if other_size >= size {
@@ -117,7 +145,7 @@ impl Game {
             _ => o.remove_playable_piece(size),
         };
 
-        if playable_piece != size {
+        if playable_piece != Some(size) {
             return Err("You don't have a tile with that size");
         }
 
@@ -186,7 +214,7 @@ impl Game {
         }
         self.winner = self
             .winner
-            .or_else(|| check_winner(&tiles_row))
+            .or_else(|| check_winner(tiles_row))
             .or_else(|| check_winner(&tiles_col))
             .or_else(|| check_winner(&tiles_diagonal_1))
             .or_else(|| check_winner(&tiles_diagonal_2));
@@ -212,13 +240,13 @@ impl Game {
     fn check_cappable(&self) -> Option<Winner> {
         let (x, o) = self.players.clone();
 
-        for (_, piece) in x.pieces.iter().enumerate() {
+        for piece in x.pieces.iter() {
             if self.piece_can_be_placed(piece) {
                 return None;
             }
         }
 
-        for (_, piece) in o.pieces.iter().enumerate() {
+        for piece in o.pieces.iter() {
             if self.piece_can_be_placed(piece) {
                 return None;
             }
@@ -228,24 +256,79 @@ impl Game {
     }
 
     pub fn piece_can_be_placed(&self, piece: &usize) -> bool {
-        for tile in self.tiles.data.iter() {
-            match tile {
-                Some(other_piece) => {
-                    let (_, other_size) = other_piece;
-                    if other_size < piece {
-                        return true;
+        for (_, other_size) in self.tiles.data.iter().flatten() {
+            if other_size < piece {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Every completed row, column and diagonal as its three (row, col)
+    /// squares, in `LINES` order. A move can complete two lines at once,
+    /// e.g. a row and a diagonal. Placing a piece, even over another, only
+    /// changes who owns its own square, so a capture can never complete a
+    /// line for the opponent and every line here belongs to the winner.
+    pub fn winning_line(&self) -> Vec<[(usize, usize); 3]> {
+        LINES
+            .iter()
+            .filter(|line| self.line_owner(line).is_some())
+            .map(|line| line.map(|index| (index / 3, index % 3)))
+            .collect()
+    }
+
+    // Whoever has a piece on top in all three squares of `line`
+    fn line_owner(&self, line: &[usize; 3]) -> Option<PlayerKind> {
+        let owners = line.map(|index| self.tiles.data[index].map(|(kind, _)| kind));
+        if owners[0] == owners[1] && owners[1] == owners[2] {
+            owners[0]
+        } else {
+            None
+        }
+    }
+
+    /// The move that leads from this position to `after`, if there is one
+    pub fn move_between(&self, after: &Game) -> Option<Move> {
+        self.legal_moves()
+            .into_iter()
+            .find(|(_, child)| child == after)
+            .map(|(m, _)| m)
+    }
+
+    /// Every legal move and the position it leads to, in row/col/piece order
+    pub fn legal_moves(&self) -> Vec<((usize, usize, usize), Game)> {
+        if self.is_finished() {
+            return Vec::new();
+        }
+
+        let (x, o) = &self.players;
+        let pieces = match self.current_player_kind {
+            PlayerKind::X => &x.pieces,
+            PlayerKind::O => &o.pieces,
+        };
+
+        let mut moves = Vec::new();
+        for i in 0..3 {
+            for j in 0..3 {
+                for piece in pieces {
+                    if let Ok(child) = self.clone().make_move(i, j, *piece) {
+                        moves.push(((i, j, *piece), child));
                     }
                 }
-                _ => (),
             }
         }
-        false
+        moves
     }
 
     pub fn is_finished(&self) -> bool {
         self.winner.is_some()
     }
 
+    /// Finished, or the player to move has no legal move, which ties the game
+    pub fn is_over(&self) -> bool {
+        self.is_finished() || self.legal_moves().is_empty()
+    }
+
     pub fn winner(&self) -> Option<Winner> {
         self.winner
     }
@@ -266,19 +349,15 @@ impl Game {
         let (x, o) = self.players.clone();
         let mut biggest_placed_piece = 0;
         let tiles = self.tiles.data.iter();
-        for tile in tiles {
-            match tile {
-                Some((_, size)) => {
-                    if *size > biggest_placed_piece {
-                        biggest_placed_piece = *size;
-                    }
-                }
-                _ => (),
+        for (_, size) in tiles.flatten() {
+            if *size > biggest_placed_piece {
+                biggest_placed_piece = *size;
             }
         }
         max(
             biggest_placed_piece,
-            *max(o.pieces.iter().max(), x.pieces.iter().max()).unwrap(),
+            // Both hands are empty once every piece has been played
+            *max(o.pieces.iter().max(), x.pieces.iter().max()).unwrap_or(&0),
         )
     }
 
@@ -289,6 +368,127 @@ impl Game {
         let remaining_turns = x.pieces.len() + o.pieces.len();
         (max_turns - remaining_turns) + 1
     }
+
+    /// Packs the position into a single integer: six bits per tile, a bitmask
+    /// of the pieces left in each hand and whose turn it is. The winner isn't
+    /// stored since it can be recovered from the position. Pieces of size
+    /// `MAX_PIECES` or more don't fit in the packing and are an error.
+    pub fn key(&self) -> Result<u128, String> {
+        let (x, o) = &self.players;
+        let largest = self
+            .tiles
+            .data
+            .iter()
+            .filter_map(|tile| tile.map(|(_, size)| size))
+            .chain(x.pieces.iter().copied())
+            .chain(o.pieces.iter().copied())
+            .max();
+        if let Some(size) = largest {
+            if size >= MAX_PIECES {
+                return Err(format!(
+                    "A piece of size {} doesn't fit in a key, the most is {} pieces",
+                    size, MAX_PIECES
+                ));
+            }
+        }
+
+        let mut key: u128 = 0;
+        for (index, tile) in self.tiles.data.iter().enumerate() {
+            let code = match tile {
+                Some((PlayerKind::X, size)) => 1 + *size as u128,
+                Some((PlayerKind::O, size)) => (1 + *size as u128) | (1 << 5),
+                None => 0,
+            };
+            key |= code << (6 * index);
+        }
+
+        for piece in x.pieces.iter() {
+            key |= 1 << (KEY_X_PIECES + piece);
+        }
+        for piece in o.pieces.iter() {
+            key |= 1 << (KEY_O_PIECES + piece);
+        }
+
+        if self.current_player_kind == PlayerKind::O {
+            key |= 1 << KEY_CURRENT_PLAYER;
+        }
+        Ok(key)
+    }
+
+    pub fn from_key(key: u128) -> Self {
+        let mut tiles = Tiles { data: [None; 9] };
+        for (index, tile) in tiles.data.iter_mut().enumerate() {
+            let code = (key >> (6 * index)) & 0b11_1111;
+            if code != 0 {
+                let kind = if code & 1 << 5 == 0 {
+                    PlayerKind::X
+                } else {
+                    PlayerKind::O
+                };
+                *tile = Some((kind, (code & 0b1_1111) as usize - 1));
+            }
+        }
+
+        let pieces = |offset: usize| {
+            (0..MAX_PIECES)
+                .filter(|piece| key & 1 << (offset + piece) != 0)
+                .collect::<Vec<usize>>()
+        };
+
+        Self::from_parts(
+            tiles,
+            (
+                Player::new(PlayerKind::X, pieces(KEY_X_PIECES)),
+                Player::new(PlayerKind::O, pieces(KEY_O_PIECES)),
+            ),
+            if key & 1 << KEY_CURRENT_PLAYER == 0 {
+                PlayerKind::X
+            } else {
+                PlayerKind::O
+            },
+        )
+    }
+
+    /// A position from its board, both hands and whose turn it is. Nothing
+    /// checks that the position could have come up in a real game.
+    pub fn from_parts(
+        tiles: Tiles,
+        players: (Player, Player),
+        current_player_kind: PlayerKind,
+    ) -> Self {
+        let mut game = Self {
+            tiles,
+            winner: None,
+            players,
+            current_player_kind,
+        };
+
+        // A move can only ever complete a line for the player making it, so
+        // any completed line must have been the one that ended the game
+        game.winner = LINES
+            .iter()
+            .find_map(|line| match game.line_owner(line) {
+                Some(PlayerKind::X) => Some(Winner::X),
+                Some(PlayerKind::O) => Some(Winner::O),
+                None => None,
+            })
+            .or_else(|| {
+                let (x, o) = &game.players;
+                if x.pieces.is_empty() && o.pieces.is_empty() {
+                    Some(Winner::Tie)
+                } else {
+                    None
+                }
+            })
+            .or_else(|| {
+                if game.tiles.data.iter().all(|tile| tile.is_some()) {
+                    game.check_cappable()
+                } else {
+                    None
+                }
+            });
+        game
+    }
 }
 
 #[derive(Debug, Clone)]
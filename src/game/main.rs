@@ -1,38 +1,136 @@
-use matryoshka_tic_tac_toe::{game, io};
+extern crate getopts;
+use getopts::Options;
+use matryoshka_tic_tac_toe::{agent, game, io, json, net, tui};
 
-use game::{Game, PlayerKind, Winner};
+use agent::{play_match, Coached};
+use game::Game;
 use io::*;
+use std::env;
+use std::net::TcpListener;
 
-fn main() {
-    let mut game = Game::new();
+fn print_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} [options]", program);
+    println!("{}", opts.usage(&brief));
+}
 
-    while !game.is_finished() {
-        game.tiles().print();
-        let (x, o) = game.players.clone();
-        x.print_pieces();
-        o.print_pieces();
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+    let mut number = Game::new().get_number_of_pieces();
+    let mut renderer = Renderer::new();
 
-        println!(
-            "Next piece: {}",
-            match game.current_player_kind() {
-                PlayerKind::X => "x",
-                PlayerKind::O => "o",
+    let mut opts = Options::new();
+    opts.optflag(
+        "",
+        "json",
+        "Read JSON commands and write JSON events, one per line, see docs/json-api.md",
+    );
+    opts.optflag(
+        "",
+        "tui",
+        "Play full screen, picking squares with the arrow keys",
+    );
+    opts.optopt(
+        "",
+        "host",
+        "Play x against someone who connects to this port",
+        "PORT",
+    );
+    opts.optopt(
+        "",
+        "connect",
+        "Play o against a game hosted at this address, e.g. 192.168.1.2:4000",
+        "ADDRESS",
+    );
+    opts.optopt(
+        "n",
+        "number",
+        "How many pieces to use, unless connecting to someone else's game",
+        "PIECES",
+    );
+    opts.optopt(
+        "",
+        "board",
+        "How to print the board: combined (default) or split into owners and sizes",
+        "LAYOUT",
+    );
+    opts.optflag(
+        "",
+        "glyphs",
+        "Draw pieces as circles growing with their size",
+    );
+    opts.optflag("h", "help", "print this help menu");
+    if let Ok(m) = opts.parse(&args[1..]) {
+        if m.opt_present("h") {
+            print_usage(&program, opts);
+            return;
+        }
+        if let Ok(Some(n)) = m.opt_get::<usize>("n") {
+            number = n;
+        }
+        match m.opt_get::<Layout>("board") {
+            Ok(Some(layout)) => renderer = renderer.with_layout(layout),
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
             }
-        );
-
-        let (row, col, size) = prompt_move();
-
-        match game.clone().make_move(row, col, size) {
-            Ok(x) => game = x,
-            _ => continue,
+            _ => (),
+        }
+        renderer = renderer.with_glyphs(m.opt_present("glyphs"));
+        if m.opt_present("json") {
+            let stdin = std::io::stdin();
+            json::run(stdin.lock(), std::io::stdout(), number, None).unwrap();
+            return;
+        }
+        if m.opt_present("tui") {
+            match tui::run(number, None) {
+                Ok(Some(game)) => renderer.print_result(&game),
+                Ok(None) => (),
+                Err(e) => eprintln!("{}", e),
+            }
+            return;
+        }
+        if let Ok(Some(port)) = m.opt_get::<u16>("host") {
+            let listener = match TcpListener::bind(("0.0.0.0", port)) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("Couldn't listen on port {}: {}", port, e);
+                    return;
+                }
+            };
+            println!("Waiting for o to connect on port {}", port);
+            let host = net::Host::new(listener, number);
+            match host.play(&mut coach(number), |game| renderer.print_position(game)) {
+                Ok(game) => renderer.print_result(&game),
+                Err(e) => eprintln!("{}", e),
+            }
+            return;
+        }
+        if let Some(address) = m.opt_str("connect") {
+            let client = net::Client::new(address);
+            match client.play(&mut coach(number), |game| renderer.print_position(game)) {
+                Ok(game) => renderer.print_result(&game),
+                Err(e) => eprintln!("{}", e),
+            }
+            return;
         }
     }
 
-    game.tiles().print();
-
-    match game.winner().expect("A finished game should have winner") {
-        Winner::X => println!("x wins!"),
-        Winner::O => println!("o wins!"),
-        Winner::Tie => println!("Tie!"),
+    // Both players share the one solver
+    let coached = coach(number);
+    match play_match(
+        Game::new_with_size(number),
+        &mut coached.clone(),
+        &mut coached.clone(),
+        |game| renderer.print_position(game),
+    ) {
+        Ok(game) => renderer.print_result(&game),
+        Err(e) => eprintln!("{}", e),
     }
 }
+
+// Hints search from the start of games smaller than the usual 6 pieces, on
+// bigger ones they only suggest what the lookup knows
+fn coach(number_of_pieces: usize) -> Coached {
+    Coached::default().with_search(number_of_pieces < Game::new().get_number_of_pieces())
+}
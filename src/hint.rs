@@ -0,0 +1,236 @@
+use crate::game::{Game, Move, PlayerKind, LINES};
+use crate::io::format_move;
+use crate::solver::{Analysis, Solver};
+use std::fmt;
+
+/// A line one player could complete with their next move: they own two of
+/// its squares and have a piece in hand that can go on the third
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Threat {
+    pub player: PlayerKind,
+    pub line: [(usize, usize); 3],
+    /// The square that completes the line
+    pub square: (usize, usize),
+    /// The pieces in hand big enough to go on `square`
+    pub sizes: Vec<usize>,
+    /// For the opponent's threats, the moves the player to move has that
+    /// leave the line out of the opponent's reach, by covering one of its
+    /// squares with a piece the opponent can't cap in turn. Empty for the
+    /// player to move's own threats.
+    pub caps: Vec<Move>,
+}
+
+/// Everything a player might want to know before moving, without moving
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hint {
+    /// Lines the player to move can complete now
+    pub wins: Vec<Threat>,
+    /// Lines the opponent could complete on their next move
+    pub threats: Vec<Threat>,
+    /// The solver's move for the player to move and what it makes of it
+    pub analysis: Analysis,
+}
+
+impl Hint {
+    /// With the solver's analysis of the position, which is a full search
+    /// unless the tablebase covers it
+    pub fn new(game: &Game, solver: &mut Solver) -> Self {
+        Self::with_analysis(game, solver.analyse(game))
+    }
+
+    /// With only what the solver's tablebase or lookup already know, so it
+    /// is quick on positions a search would take a long while over
+    pub fn recalled(game: &Game, solver: &Solver) -> Self {
+        Self::with_analysis(game, solver.recall(game))
+    }
+
+    fn with_analysis(game: &Game, analysis: Analysis) -> Self {
+        let player = game.current_player_kind();
+        let opponent = other(player);
+        let against = threats(game, opponent)
+            .into_iter()
+            .map(|threat| Threat {
+                caps: caps(game, &threat),
+                ..threat
+            })
+            .collect();
+        Self {
+            wins: threats(game, player),
+            threats: against,
+            analysis,
+        }
+    }
+
+    /// The moves that stop every one of the opponent's threats at once,
+    /// those in the `caps` of all of them
+    pub fn defences(&self) -> Vec<Move> {
+        match self.threats.split_first() {
+            Some((first, rest)) => first
+                .caps
+                .iter()
+                .filter(|m| rest.iter().all(|threat| threat.caps.contains(m)))
+                .copied()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Whether one move stops all of the opponent's threats. Two threats
+    /// that each need a different move can't both be stopped.
+    pub fn can_defend(&self) -> bool {
+        self.threats.is_empty() || !self.defences().is_empty()
+    }
+}
+
+/// Every line `player` could complete if it were their move, in `LINES`
+/// order
+pub fn threats(game: &Game, player: PlayerKind) -> Vec<Threat> {
+    if game.is_finished() {
+        return Vec::new();
+    }
+    let (x, o) = &game.players;
+    let hand = match player {
+        PlayerKind::X => &x.pieces,
+        PlayerKind::O => &o.pieces,
+    };
+    LINES
+        .iter()
+        .filter_map(|line| {
+            let owners = line.map(|index| game.tiles.data[index].map(|(kind, _)| kind));
+            let missing = (0..3)
+                .filter(|&i| owners[i] != Some(player))
+                .collect::<Vec<_>>();
+            let index = match missing[..] {
+                [i] => line[i],
+                _ => return None,
+            };
+            let covered = game.tiles.data[index].map(|(_, size)| size);
+            let sizes = hand
+                .iter()
+                .filter(|size| match covered {
+                    Some(covered) => **size > covered,
+                    None => true,
+                })
+                .copied()
+                .collect::<Vec<usize>>();
+            if sizes.is_empty() {
+                return None;
+            }
+            Some(Threat {
+                player,
+                line: line.map(|index| (index / 3, index % 3)),
+                square: (index / 3, index % 3),
+                sizes,
+                caps: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+// The moves on the line of an opponent's threat after which the opponent
+// can no longer complete it
+fn caps(game: &Game, threat: &Threat) -> Vec<Move> {
+    game.legal_moves()
+        .into_iter()
+        .filter(|((row, col, _), _)| threat.line.contains(&(*row, *col)))
+        .filter(|(_, after)| {
+            !threats(after, threat.player)
+                .iter()
+                .any(|other| other.line == threat.line)
+        })
+        .map(|(m, _)| m)
+        .collect()
+}
+
+fn other(kind: PlayerKind) -> PlayerKind {
+    match kind {
+        PlayerKind::X => PlayerKind::O,
+        PlayerKind::O => PlayerKind::X,
+    }
+}
+
+fn name(kind: PlayerKind) -> &'static str {
+    match kind {
+        PlayerKind::X => "x",
+        PlayerKind::O => "o",
+    }
+}
+
+fn square((row, col): (usize, usize)) -> String {
+    format!("{}{}", row + 1, (b'A' + col as u8) as char)
+}
+
+fn sizes(sizes: &[usize]) -> String {
+    sizes
+        .iter()
+        .map(|size| size.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn moves(moves: &[Move]) -> String {
+    moves
+        .iter()
+        .map(|m| format_move(*m))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// A few lines for the console, e.g.
+///
+/// ```text
+/// You can win on 1C with 2, 3
+/// o threatens 3A with 4, 5; stop it with 3A5 1A5
+/// Suggested move 1C2, wins
+/// ```
+///
+/// With more than one threat, each is listed and then the moves that stop
+/// them all, if there are any.
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for win in &self.wins {
+            writeln!(
+                f,
+                "You can win on {} with {}",
+                square(win.square),
+                sizes(&win.sizes)
+            )?;
+        }
+        for threat in &self.threats {
+            write!(
+                f,
+                "{} threatens {} with {}",
+                name(threat.player),
+                square(threat.square),
+                sizes(&threat.sizes)
+            )?;
+            match (self.threats.len(), threat.caps.is_empty()) {
+                (1, true) => writeln!(f, "; it can't be stopped")?,
+                (1, false) => writeln!(f, "; stop it with {}", moves(&threat.caps))?,
+                _ => writeln!(f)?,
+            }
+        }
+        if self.threats.len() > 1 {
+            match self.defences().is_empty() {
+                true => writeln!(f, "No one move stops them all")?,
+                false => writeln!(f, "Stop them all with {}", moves(&self.defences()))?,
+            }
+        }
+        if self.wins.is_empty() && self.threats.is_empty() {
+            writeln!(f, "Neither player can complete a line next move")?;
+        }
+        match self.analysis.best_move {
+            Some(best) => {
+                write!(f, "Suggested move {}", format_move(best))?;
+                match self.analysis.score {
+                    Some(10) => writeln!(f, ", wins")?,
+                    Some(0) => writeln!(f, ", ties")?,
+                    Some(_) => writeln!(f, ", loses against perfect play")?,
+                    None => writeln!(f)?,
+                }
+            }
+            None => writeln!(f, "There is no move to suggest")?,
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
-use crate::game::{InvalidMove, PlayerKind, Tiles};
-use std::io::{self, Write};
+use crate::game::{Game, InvalidMove, Move, Player, PlayerKind, Tiles, Winner, MAX_PIECES};
+use std::env;
+use std::io::{self, IsTerminal, Write};
 use std::process;
+use std::str::FromStr;
 
 pub fn prompt_move() -> (usize, usize, usize) {
     loop {
@@ -19,8 +21,46 @@ pub fn prompt_move() -> (usize, usize, usize) {
     }
 }
 
+/// What a player typed at the move prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Move(Move),
+    Hint,
+}
+
+/// Like `prompt_move`, but `hint` asks for help instead of moving
+pub fn prompt_input() -> Input {
+    loop {
+        print!("Enter move using the syntax 'row column size' (e.g. 1A1), or hint: ");
+
+        io::stdout().flush().expect("Failed to flush stdout");
+
+        let line = read_line();
+        if line.trim().eq_ignore_ascii_case("hint") {
+            break Input::Hint;
+        }
+
+        match parse_move(line.trim()) {
+            Ok(m) => break Input::Move(m),
+            Err(InvalidMove(invalid_str)) => {
+                eprintln!("Invalid move: '{}'. Please try again.", invalid_str,)
+            }
+        }
+    }
+}
+
+/// The board, both hands and whose turn it is, see `Renderer`
+pub fn print_position(game: &Game) {
+    Renderer::new().print_position(game);
+}
+
+/// The final board and who won, see `Renderer`
+pub fn print_result(game: &Game) {
+    Renderer::new().print_result(game);
+}
+
 pub fn parse_move(input: &str) -> Result<(usize, usize, usize), InvalidMove> {
-    if input.len() != 3 {
+    if input.len() < 3 || !input.is_ascii() {
         return Err(InvalidMove(input.to_string()));
     }
 
@@ -39,7 +79,7 @@ pub fn parse_move(input: &str) -> Result<(usize, usize, usize), InvalidMove> {
         invalid => return Err(InvalidMove(invalid.to_string())),
     };
 
-    let size = match input[2..3].parse::<usize>() {
+    let size = match input[2..].parse::<usize>() {
         Ok(x) => x,
         _ => return Err(InvalidMove(input.to_string())),
     };
@@ -47,6 +87,122 @@ pub fn parse_move(input: &str) -> Result<(usize, usize, usize), InvalidMove> {
     Ok((row, col, size))
 }
 
+/// The inverse of `parse_move`, e.g. `1A1`
+pub fn format_move((row, col, size): Move) -> String {
+    format!("{}{}{}", row + 1, (b'A' + col as u8) as char, size)
+}
+
+/// A position on one line: the board row by row, whose turn it is and the
+/// pieces left in each hand, e.g. `X2,-,-/-,O1,-/-,-,- x 0,1 0,2`. A tile is
+/// `-` or its owner and size, and an empty hand is `-`.
+pub fn format_position(game: &Game) -> String {
+    let board = (0..3)
+        .map(|row| {
+            game.tiles()[row]
+                .iter()
+                .map(|tile| match tile {
+                    Some((PlayerKind::X, size)) => format!("X{}", size),
+                    Some((PlayerKind::O, size)) => format!("O{}", size),
+                    None => "-".to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(",")
+        })
+        .collect::<Vec<String>>()
+        .join("/");
+    let hand = |player: &Player| {
+        if player.pieces.is_empty() {
+            "-".to_string()
+        } else {
+            player
+                .pieces
+                .iter()
+                .map(|piece| piece.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        }
+    };
+    let (x, o) = &game.players;
+    format!(
+        "{} {} {} {}",
+        board,
+        match game.current_player_kind() {
+            PlayerKind::X => "x",
+            PlayerKind::O => "o",
+        },
+        hand(x),
+        hand(o)
+    )
+}
+
+/// The inverse of `format_position`
+pub fn parse_position(input: &str) -> Result<Game, String> {
+    let fields = input.split_whitespace().collect::<Vec<&str>>();
+    let (board, turn, x, o) = match fields[..] {
+        [board, turn, x, o] => (board, turn, x, o),
+        _ => {
+            return Err(format!(
+                "Expected a board, whose turn it is and both hands, not '{}'",
+                input
+            ))
+        }
+    };
+
+    let mut tiles = Tiles { data: [None; 9] };
+    let rows = board.split('/').collect::<Vec<&str>>();
+    if rows.len() != 3 {
+        return Err(format!("Expected 3 rows, not '{}'", board));
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let row = row.split(',').collect::<Vec<&str>>();
+        if row.len() != 3 {
+            return Err(format!("Expected 3 tiles in row {}", i + 1));
+        }
+        for (j, tile) in row.iter().enumerate() {
+            tiles[i][j] = match (tile.get(..1), tile.get(1..)) {
+                (Some("-"), Some("")) => None,
+                (Some("X" | "x"), Some(size)) => Some((PlayerKind::X, parse_size(size)?)),
+                (Some("O" | "o"), Some(size)) => Some((PlayerKind::O, parse_size(size)?)),
+                _ => return Err(format!("Invalid tile '{}'", tile)),
+            };
+        }
+    }
+
+    let current_player_kind = match turn {
+        "x" | "X" => PlayerKind::X,
+        "o" | "O" => PlayerKind::O,
+        _ => return Err(format!("Expected x or o to move, not '{}'", turn)),
+    };
+
+    let hand = |hand: &str| -> Result<Vec<usize>, String> {
+        if hand == "-" {
+            return Ok(Vec::new());
+        }
+        let mut pieces = hand
+            .split(',')
+            .map(parse_size)
+            .collect::<Result<Vec<usize>, String>>()?;
+        pieces.sort_unstable();
+        Ok(pieces)
+    };
+
+    Ok(Game::from_parts(
+        tiles,
+        (
+            Player::new(PlayerKind::X, hand(x)?),
+            Player::new(PlayerKind::O, hand(o)?),
+        ),
+        current_player_kind,
+    ))
+}
+
+fn parse_size(size: &str) -> Result<usize, String> {
+    match size.parse::<usize>() {
+        Ok(size) if size < MAX_PIECES => Ok(size),
+        _ => Err(format!("Invalid piece size '{}'", size)),
+    }
+}
+
 pub fn read_line() -> String {
     let mut input = String::new();
     io::stdin()
@@ -65,43 +221,242 @@ pub fn read_line() -> String {
     input
 }
 
-impl Tiles {
-    pub fn print(self: &Self) {
-        print!("  ");
-        for j in 0..self[0].len() as u8 {
-            print!(" {}", (b'A' + j) as char);
+/// From the smallest piece to the biggest
+const GLYPHS: [char; 5] = ['·', '∘', '○', '◯', '●'];
+
+/// How the board is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// One grid with the owner and size of every piece, e.g. `X2`
+    #[default]
+    Combined,
+    /// Owners in one grid and sizes in another beside it
+    Split,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "combined" => Ok(Layout::Combined),
+            "split" => Ok(Layout::Split),
+            invalid => Err(format!(
+                "Unknown layout '{}', expected combined or split",
+                invalid
+            )),
         }
-        println!();
+    }
+}
+
+/// Prints positions for the console. It remembers the last position it
+/// printed, so that the move played since stands out.
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    layout: Layout,
+    colour: bool,
+    glyphs: bool,
+    previous: Option<Game>,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    /// The combined layout, in colour unless `NO_COLOR` is set or stdout
+    /// isn't a terminal
+    pub fn new() -> Self {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        Self {
+            layout: Layout::default(),
+            colour: !no_color && io::stdout().is_terminal(),
+            glyphs: false,
+            previous: None,
+        }
+    }
+
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn with_colour(mut self, colour: bool) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    /// Draws pieces in the combined layout as circles growing with their
+    /// size, `◯X2`
+    pub fn with_glyphs(mut self, glyphs: bool) -> Self {
+        self.glyphs = glyphs;
+        self
+    }
+
+    /// The board with column and row labels, the tile of `last_move` in bold
+    pub fn render(&self, tiles: &Tiles, last_move: Option<Move>) -> String {
+        self.render_with_lines(tiles, last_move, &[])
+    }
+
+    /// The board as `render` draws it, with the squares of `lines`, such as
+    /// `Game::winning_line`, underlined
+    pub fn render_with_lines(
+        &self,
+        tiles: &Tiles,
+        last_move: Option<Move>,
+        lines: &[[(usize, usize); 3]],
+    ) -> String {
+        match self.layout {
+            Layout::Combined => self.render_combined(tiles, last_move, lines),
+            Layout::Split => render_split(tiles),
+        }
+    }
+
+    /// The board, both hands and whose turn it is
+    pub fn print_position(&mut self, game: &Game) {
+        print!("{}", self.render(game.tiles(), self.last_move(game)));
+        let (x, o) = game.players.clone();
+        x.print_pieces();
+        o.print_pieces();
+
+        println!(
+            "Next piece: {}",
+            match game.current_player_kind() {
+                PlayerKind::X => "x",
+                PlayerKind::O => "o",
+            }
+        );
+        self.previous = Some(game.clone());
+    }
 
+    /// The final board, with the winning line underlined, and who won. A
+    /// game where a player is stuck without a move is a tie.
+    pub fn print_result(&mut self, game: &Game) {
+        print!(
+            "{}",
+            self.render_with_lines(game.tiles(), self.last_move(game), &game.winning_line())
+        );
+        self.previous = Some(game.clone());
+
+        match game.winner() {
+            Some(Winner::X) => println!("x wins!"),
+            Some(Winner::O) => println!("o wins!"),
+            _ => println!("Tie!"),
+        }
+    }
+
+    fn last_move(&self, game: &Game) -> Option<Move> {
+        self.previous
+            .as_ref()
+            .and_then(|previous| previous.move_between(game))
+    }
+
+    fn render_combined(
+        &self,
+        tiles: &Tiles,
+        last_move: Option<Move>,
+        lines: &[[(usize, usize); 3]],
+    ) -> String {
+        let biggest = tiles
+            .data
+            .iter()
+            .flatten()
+            .map(|(_, size)| *size)
+            .max()
+            .unwrap_or(0);
+        let width = 1 + biggest.to_string().len() + self.glyphs as usize;
+
+        let mut board = String::from("  ");
+        for j in 0..3u8 {
+            board += &format!(" {:<width$}", (b'A' + j) as char, width = width);
+        }
+        board.push('\n');
         for i in 0..3 {
-            print!(" {}", i + 1);
+            board += &format!(" {}", i + 1);
             for j in 0..3 {
-                let tile = self[i][j];
-                print!(
-                    " {}",
-                    match tile {
-                        Some((PlayerKind::X, _)) => "x",
-                        Some((PlayerKind::O, _)) => "o",
-                        _ => "_",
+                let (text, style) = match tiles[i][j] {
+                    Some((kind, size)) => {
+                        let (owner, colour) = match kind {
+                            PlayerKind::X => ('X', "31"),
+                            PlayerKind::O => ('O', "36"),
+                        };
+                        let glyph = match self.glyphs {
+                            true => glyph(size, biggest).to_string(),
+                            false => String::new(),
+                        };
+                        (format!("{}{}{}", glyph, owner, size), colour)
                     }
-                );
+                    None => ("_".to_string(), ""),
+                };
+                let bold = last_move.is_some_and(|(row, col, _)| (row, col) == (i, j));
+                let underlined = lines.iter().flatten().any(|square| *square == (i, j));
+                let style = match (bold, underlined) {
+                    (true, true) => format!("1;4;{}", style),
+                    (true, false) => format!("1;{}", style),
+                    (false, true) => format!("4;{}", style),
+                    (false, false) => style.to_string(),
+                };
+                let padding = " ".repeat(width - text.chars().count());
+                board += " ";
+                match self.colour && !style.is_empty() {
+                    true => board += &format!("\x1b[{}m{}\x1b[0m", style, text),
+                    false => board += &text,
+                }
+                board += &padding;
             }
+            board.push('\n');
+        }
+        // Without the padding after the last column
+        let mut board = board
+            .lines()
+            .map(|line| format!("{}\n", line.trim_end()))
+            .collect::<String>();
+        board.push('\n');
+        board
+    }
+}
+
+/// Picks a circle for a piece, in proportion to the biggest piece
+pub fn glyph(size: usize, biggest: usize) -> char {
+    GLYPHS[(size * (GLYPHS.len() - 1) / biggest.max(1)).min(GLYPHS.len() - 1)]
+}
 
-            print!("    ");
+fn render_split(tiles: &Tiles) -> String {
+    let mut board = String::from("  ");
+    for j in 0..3u8 {
+        board += &format!(" {}", (b'A' + j) as char);
+    }
+    board.push('\n');
 
-            for j in 0..3 {
-                let tile = self[i][j];
-                print!(
-                    " {}",
-                    match tile {
-                        Some((_, x)) => x.to_string(),
-                        _ => "_".to_string(),
-                    }
-                );
+    for i in 0..3 {
+        board += &format!(" {}", i + 1);
+        for j in 0..3 {
+            board += match tiles[i][j] {
+                Some((PlayerKind::X, _)) => " x",
+                Some((PlayerKind::O, _)) => " o",
+                _ => " _",
+            };
+        }
+
+        board += "    ";
+
+        for j in 0..3 {
+            match tiles[i][j] {
+                Some((_, x)) => board += &format!(" {}", x),
+                _ => board += " _",
             }
-            println!();
         }
+        board.push('\n');
+    }
 
-        println!();
+    board.push('\n');
+    board
+}
+
+impl Tiles {
+    pub fn print(&self) {
+        print!("{}", Renderer::new().render(self, None));
     }
 }
@@ -0,0 +1,172 @@
+use crate::agent::Agent;
+use crate::game::{Game, Move, PlayerKind, Winner, MAX_PIECES};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, Write};
+
+/// A command read from one line of JSON, see `docs/json-api.md`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    /// Starts again from an empty board, with the same number of pieces as
+    /// before unless `pieces` is given, from 1 to `game::MAX_PIECES`
+    NewGame {
+        pieces: Option<usize>,
+    },
+    /// Plays a move for whoever's turn it is
+    Move {
+        row: usize,
+        col: usize,
+        size: usize,
+    },
+    /// Asks for the position again
+    State,
+    Quit,
+}
+
+/// An event written as one line of JSON, see `docs/json-api.md`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The position after every change, with the moves the player to move
+    /// can make
+    State {
+        game: Box<Game>,
+        legal_moves: Vec<Move>,
+    },
+    Move {
+        player: PlayerKind,
+        r#move: Move,
+    },
+    /// A player won, or the game was tied. A player left without a legal move
+    /// ties the game.
+    GameOver {
+        winner: Winner,
+    },
+    /// A command that couldn't be read or carried out. Nothing changes.
+    Error {
+        message: String,
+    },
+}
+
+impl Event {
+    /// The position with the moves the player to move can make
+    pub fn state(game: &Game) -> Self {
+        Event::State {
+            game: Box::new(game.clone()),
+            legal_moves: game.legal_moves().into_iter().map(|(m, _)| m).collect(),
+        }
+    }
+
+    pub fn error(message: &str) -> Self {
+        Event::Error {
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Plays games driven by JSON commands, one per line of `input`, writing
+/// events to `output`. The `cpu`, if any, answers every move as o.
+pub fn run<R, W>(
+    input: R,
+    mut output: W,
+    number_of_pieces: usize,
+    mut cpu: Option<&mut dyn Agent>,
+) -> std::io::Result<()>
+where
+    R: BufRead,
+    W: Write,
+{
+    let mut number_of_pieces = number_of_pieces;
+    let mut game = Game::new_with_size(number_of_pieces);
+    emit(&mut output, &Event::state(&game))?;
+
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let command = match serde_json::from_str::<Command>(&line) {
+            Ok(command) => command,
+            Err(e) => {
+                emit(&mut output, &Event::error(&e.to_string()))?;
+                continue;
+            }
+        };
+
+        match command {
+            Command::NewGame { pieces } => match pieces.unwrap_or(number_of_pieces) {
+                pieces @ 1..=MAX_PIECES => {
+                    number_of_pieces = pieces;
+                    game = Game::new_with_size(number_of_pieces);
+                }
+                _ => {
+                    let message = format!("Games have from 1 to {} pieces", MAX_PIECES);
+                    emit(&mut output, &Event::error(&message))?;
+                    continue;
+                }
+            },
+            Command::Move { row, col, .. } if row > 2 || col > 2 => {
+                emit(
+                    &mut output,
+                    &Event::error("Rows and columns go from 0 to 2"),
+                )?;
+                continue;
+            }
+            Command::Move { row, col, size } => {
+                if let Err(message) = play(&mut output, &mut game, (row, col, size))? {
+                    emit(&mut output, &Event::error(&message))?;
+                    continue;
+                }
+                if let Some(cpu) = cpu.as_mut() {
+                    if !game.is_over() && game.current_player_kind() == PlayerKind::O {
+                        let cpu_move = cpu.choose_move(&game);
+                        if let Err(message) = play(&mut output, &mut game, cpu_move)? {
+                            emit(&mut output, &Event::error(&message))?;
+                            // Asking an engine again would only get the same move
+                            if !cpu.asks_again() {
+                                return Err(io::Error::other(message));
+                            }
+                        }
+                    }
+                }
+            }
+            Command::State => (),
+            Command::Quit => return Ok(()),
+        }
+
+        emit(&mut output, &Event::state(&game))?;
+        if game.is_over() {
+            emit(
+                &mut output,
+                &Event::GameOver {
+                    winner: game.winner().unwrap_or(Winner::Tie),
+                },
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// Plays `m` and reports it, the outer error being a failure to write
+fn play<W: Write>(output: &mut W, game: &mut Game, m: Move) -> std::io::Result<Result<(), String>> {
+    if game.is_over() {
+        return Ok(Err("The game is over".to_string()));
+    }
+    let (row, col, size) = m;
+    let player = game.current_player_kind();
+    match game.clone().make_move(row, col, size) {
+        Ok(next) => {
+            *game = next;
+            emit(output, &Event::Move { player, r#move: m })?;
+            Ok(Ok(()))
+        }
+        Err(e) => Ok(Err(e.to_string())),
+    }
+}
+
+fn emit<W: Write>(output: &mut W, event: &Event) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *output, event)?;
+    writeln!(output)?;
+    output.flush()
+}
@@ -1,14 +1,59 @@
+pub mod agent;
+pub mod animation;
+pub mod engine;
 pub mod game;
+pub mod hint;
 pub mod io;
+pub mod json;
+pub mod lobby;
+pub mod mcts;
+pub mod net;
+pub mod perft;
+pub mod raster;
+pub mod record;
+pub mod report;
+pub mod server;
 pub mod solver;
+pub mod state_space;
+pub mod svg;
 pub mod symmetry;
+pub mod tournament;
+pub mod tui;
+pub mod websocket;
 
 #[cfg(test)]
 mod tests {
-    use crate::game::{Game, PlayerKind, Winner};
-    use crate::solver::Solver;
-    use crate::symmetry::Symmetry;
+    use crate::agent::{play_match, play_match_moves, Agent, Random};
+    use crate::animation::Animation;
+    use crate::game::{Game, Move, PlayerKind, Winner, MAX_PIECES};
+    use crate::hint::{self, Hint, Threat};
+    use crate::io::{format_position, parse_move, parse_position, Layout, Renderer};
+    use crate::json::{self, Command, Event};
+    use crate::lobby::{self, Lobby, Opponent, Role, LOBBY_VERSION};
+    use crate::mcts::{Budget, Mcts, Rollout};
+    use crate::net::{Client, Connection, Host, Message, PROTOCOL_VERSION};
+    use crate::perft::Perft;
+    use crate::raster::Raster;
+    use crate::record::{parse_records, GameRecord};
+    use crate::report::{Report, Verdict};
+    use crate::server::Server;
+    use crate::solver::{
+        Algorithm, Difficulty, Heuristics, MoveOrdering, Proof, Solver, Tablebase,
+    };
+    use crate::state_space;
+    use crate::svg::Diagram;
+    use crate::symmetry::{index_to_coordinates, unfliptate_move, Symmetry};
+    use crate::tournament::{AgentSpec, Format, Score, Tournament};
+    use crate::tui::{self, Action, Picker};
+    use crate::websocket;
+    use std::collections::HashSet;
     use std::fs;
+    use std::io::{self, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+    use strum::IntoEnumIterator;
 
     #[test]
     fn col_3_o_wins() {
@@ -104,6 +149,39 @@ mod tests {
         assert_eq!(game.winner().unwrap(), Winner::Tie);
     }
 
+    #[test]
+    fn only_pieces_left_can_be_played() {
+        let mut game = Game::new_with_size(2);
+        game = game.make_move(0, 0, 0).unwrap();
+        game = game.make_move(1, 1, 1).unwrap();
+        assert!(game.clone().make_move(2, 2, 0).is_err());
+        assert!(game.make_move(2, 2, 1).is_ok());
+    }
+
+    #[test]
+    fn moves_off_the_board_are_illegal() {
+        let game = Game::new_with_size(3);
+        for (row, col) in [(0, 3), (3, 0), (usize::MAX, 1)] {
+            assert_eq!(
+                game.clone().make_move(row, col, 1),
+                Err("There is no such tile on the board!")
+            );
+        }
+    }
+
+    #[test]
+    fn used_pieces_cannot_be_played_again() {
+        // Size 0 is a piece like any other, once played it's gone
+        let mut game = Game::new_with_size(3);
+        game = game.make_move(0, 0, 0).unwrap();
+        game = game.make_move(1, 1, 2).unwrap();
+        assert_eq!(
+            game.clone().make_move(2, 2, 0),
+            Err("You don't have a tile with that size")
+        );
+        assert!(game.make_move(2, 2, 1).is_ok());
+    }
+
     #[test]
     fn the_one_from_the_video() {
         let mut game = Game::new();
@@ -140,9 +218,7 @@ mod tests {
 
         game = game.make_move(0, 0, 4).unwrap();
 
-        if game.winner() == Some(Winner::X) {
-            assert!(false);
-        }
+        assert_ne!(game.winner(), Some(Winner::X));
 
         // 2nd rotation
         let mut game = Game::new_with_size(6);
@@ -158,9 +234,7 @@ mod tests {
 
         game = game.make_move(0, 2, 4).unwrap();
 
-        if game.winner() == Some(Winner::X) {
-            assert!(false);
-        }
+        assert_ne!(game.winner(), Some(Winner::X));
 
         // 3rd rotation
         let mut game = Game::new_with_size(6);
@@ -176,9 +250,7 @@ mod tests {
 
         game = game.make_move(2, 2, 4).unwrap();
 
-        if game.winner() == Some(Winner::X) {
-            assert!(false);
-        }
+        assert_ne!(game.winner(), Some(Winner::X));
 
         // 4th rotation
         let mut game = Game::new_with_size(6);
@@ -194,9 +266,7 @@ mod tests {
 
         game = game.make_move(2, 0, 4).unwrap();
 
-        if game.winner() == Some(Winner::X) {
-            assert!(false);
-        }
+        assert_ne!(game.winner(), Some(Winner::X));
 
         let solver = Solver::new();
 
@@ -215,10 +285,8 @@ mod tests {
 
         let game = game.make_move(2, 1, 0);
 
-        if game.is_ok() {
-            if game.unwrap().winner() == Some(Winner::X) {
-                assert!(false);
-            }
+        if let Ok(game) = game {
+            assert_ne!(game.winner(), Some(Winner::X));
         }
 
         // 2nd rotation
@@ -236,10 +304,8 @@ mod tests {
 
         let game = game.make_move(0, 1, 0);
 
-        if game.is_ok() {
-            if game.unwrap().winner() == Some(Winner::X) {
-                assert!(false);
-            }
+        if let Ok(game) = game {
+            assert_ne!(game.winner(), Some(Winner::X));
         }
     }
 
@@ -267,8 +333,9 @@ mod tests {
     }
 
     #[test]
-    fn ai_plays_itself_o_wins() {
-        // A small game
+    fn ai_plays_itself_ties() {
+        // A small game, which is a draw with perfect play. Both solvers play
+        // perfectly for their own side, so neither can win it.
         let mut game = Game::new_with_size(3);
         let mut solver_x = Solver::new();
         let solver_o = Solver::new();
@@ -282,7 +349,1914 @@ mod tests {
             game = game.make_move(i, j, k).unwrap();
         }
 
-        assert_eq!(game.winner().unwrap(), Winner::O);
+        assert_eq!(game.winner().unwrap(), Winner::Tie);
+    }
+
+    #[test]
+    fn solver_plays_for_its_own_side() {
+        // x to move can complete the top row
+        let mut game = Game::new_with_size(3);
+        for (i, j, k) in [(0, 0, 1), (2, 2, 0), (0, 1, 0), (1, 1, 1)] {
+            game = game.make_move(i, j, k).unwrap();
+        }
+        let mut solver_x = Solver::new();
+        solver_x.kind = PlayerKind::X;
+        let (_, score) = solver_x.search_move(&game);
+        assert_eq!(score, 10);
+
+        // o to move can complete the top row
+        let mut game = Game::new_with_size(3);
+        for (i, j, k) in [(2, 2, 0), (0, 0, 1), (1, 2, 1), (0, 1, 0), (2, 0, 2)] {
+            game = game.make_move(i, j, k).unwrap();
+        }
+        let (_, score) = Solver::new().search_move(&game);
+        assert_eq!(score, 10);
+    }
+
+    #[test]
+    fn solver_agent_plays_either_side() {
+        // One solver, left searching as o, wins for whoever is to move
+        let mut solver: Box<dyn Agent> = Box::new(Solver::new());
+
+        let mut game = Game::new_with_size(3);
+        for (i, j, k) in [(0, 0, 1), (2, 2, 0), (0, 1, 0), (1, 1, 1)] {
+            game = game.make_move(i, j, k).unwrap();
+        }
+        let (i, j, k) = solver.choose_move(&game);
+        assert_eq!(game.make_move(i, j, k).unwrap().winner(), Some(Winner::X));
+
+        let mut game = Game::new_with_size(3);
+        for (i, j, k) in [(2, 2, 0), (0, 0, 1), (1, 2, 1), (0, 1, 0), (2, 0, 2)] {
+            game = game.make_move(i, j, k).unwrap();
+        }
+        let (i, j, k) = solver.choose_move(&game);
+        assert_eq!(game.make_move(i, j, k).unwrap().winner(), Some(Winner::O));
+    }
+
+    #[test]
+    fn analysis_scores_from_the_root_search() {
+        // An easy solver still analyses perfectly, with one search, and
+        // keeps playing its own side afterwards
+        let tablebase = Tablebase::generate(3);
+        let mut solver = Solver::new().with_difficulty(Difficulty::Easy);
+        let mut random = Random::with_seed(0);
+        for _ in 0..10 {
+            let mut game = Game::new_with_size(3);
+            while !game.is_finished() && !game.legal_moves().is_empty() {
+                let analysis = solver.analyse(&game);
+                assert_eq!(solver.kind, PlayerKind::O);
+                let entry = tablebase.get(&game).unwrap();
+                assert_eq!(
+                    analysis.score,
+                    Some(entry.score_for(game.current_player_kind()))
+                );
+
+                // The line the search found reaches that score
+                let end = analysis.pv.iter().fold(game.clone(), |game, (i, j, k)| {
+                    game.make_move(*i, *j, *k).unwrap()
+                });
+                let won = |winner| match winner {
+                    Winner::X => game.current_player_kind() == PlayerKind::X,
+                    Winner::O => game.current_player_kind() == PlayerKind::O,
+                    Winner::Tie => false,
+                };
+                let score = match end.winner() {
+                    Some(Winner::Tie) | None => 0,
+                    Some(winner) if won(winner) => 10,
+                    Some(_) => -10,
+                };
+                assert_eq!(analysis.score, Some(score));
+                assert_eq!(analysis.best_move, analysis.pv.first().copied());
+
+                let (i, j, k) = random.choose_move(&game);
+                game = game.make_move(i, j, k).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn young_brothers_wait_searches_fewer_nodes() {
+        let positions = [
+            (5, vec![(1, 1, 4), (0, 0, 4), (2, 2, 3)]),
+            (
+                6,
+                vec![(1, 1, 5), (0, 0, 5), (2, 2, 4), (0, 2, 4), (2, 0, 3)],
+            ),
+            (
+                6,
+                vec![(0, 1, 5), (1, 1, 5), (2, 1, 4), (1, 0, 4), (1, 2, 3)],
+            ),
+        ];
+
+        for (number_of_pieces, moves) in positions {
+            let mut game = Game::new_with_size(number_of_pieces);
+            for (i, j, k) in moves {
+                game = game.make_move(i, j, k).unwrap();
+            }
+
+            let parallel = Solver::new().with_algorithm(Algorithm::Parallel);
+            let ybwc = Solver::new().with_algorithm(Algorithm::YoungBrothersWait);
+            let (_, parallel_score) = parallel.search_move(&game);
+            let (_, ybwc_score) = ybwc.search_move(&game);
+
+            assert_eq!(parallel_score, ybwc_score);
+            assert!(ybwc.nodes() < parallel.nodes());
+        }
+    }
+
+    #[test]
+    fn move_ordering_keeps_the_score() {
+        let mut game = Game::new_with_size(5);
+        game = game.make_move(1, 1, 4).unwrap();
+        game = game.make_move(0, 0, 4).unwrap();
+        game = game.make_move(2, 2, 3).unwrap();
+
+        let unordered = Solver::new().with_ordering(MoveOrdering::none());
+        let ordered = Solver::new().with_ordering(MoveOrdering::all());
+        let (unordered_move, unordered_score) = unordered.search_move(&game);
+        let (ordered_move, ordered_score) = ordered.search_move(&game);
+
+        assert_eq!(unordered_move, ordered_move);
+        assert_eq!(unordered_score, ordered_score);
+        assert!(ordered.nodes() < unordered.nodes());
+
+        let wins_and_blocks = "wins, blocks".parse::<MoveOrdering>().unwrap();
+        assert!(wins_and_blocks.wins && wins_and_blocks.blocks);
+        assert!(!wins_and_blocks.history);
+        assert!("bogus".parse::<MoveOrdering>().is_err());
+    }
+
+    #[test]
+    fn move_ordering_history_saturates() {
+        let game = Game::new_with_size(16).make_move(0, 0, 0).unwrap();
+        let heuristics = Heuristics::new();
+        // Enough deep cutoffs to wrap a 32 bit counter
+        for _ in 0..4_500_000 {
+            heuristics.record_cutoff(&game, (2, 2, 15));
+        }
+        assert_eq!(heuristics.history(PlayerKind::O, (2, 2, 15)), (1 << 32) - 1);
+
+        // However much history a quiet move has, captures still come first
+        let mut children = game.legal_moves();
+        let ordering = "captures,history".parse::<MoveOrdering>().unwrap();
+        heuristics.order(&ordering, &game, &mut children);
+        assert_eq!(children[0].0 .0, 0);
+        assert_eq!(children[0].0 .1, 0);
+        assert_eq!(children[15].0, (2, 2, 15));
+
+        heuristics.age();
+        assert_eq!(heuristics.history(PlayerKind::O, (2, 2, 15)), (1 << 31) - 1);
+    }
+
+    #[test]
+    fn proof_number_search_agrees_with_alpha_beta() {
+        let tablebase = Tablebase::generate(3);
+        let pns = Solver::new().with_algorithm(Algorithm::ProofNumber);
+        let ybwc = Solver::new().with_algorithm(Algorithm::YoungBrothersWait);
+
+        let openings = Game::new_with_size(3).legal_moves();
+        for (_, game) in openings.iter() {
+            let (pns_move, pns_score) = pns.search_move(game);
+            let (_, ybwc_score) = ybwc.search_move(game);
+            assert_eq!(pns_score, ybwc_score);
+
+            let (i, j, k) = pns_move;
+            let child = game.clone().make_move(i, j, k).unwrap();
+            assert_eq!(
+                tablebase.get(&child).unwrap().score,
+                tablebase.get(game).unwrap().score
+            );
+        }
+
+        let mut game = Game::new_with_size(5);
+        game = game.make_move(1, 1, 4).unwrap();
+        game = game.make_move(0, 0, 4).unwrap();
+        game = game.make_move(2, 2, 3).unwrap();
+        assert_eq!(pns.search_move(&game).1, ybwc.search_move(&game).1);
+
+        // A table far too small for the whole search still gives the same
+        // answers, it only searches again what it forgot
+        let small = Solver::new()
+            .with_algorithm(Algorithm::ProofNumber)
+            .with_proof_table(256);
+        // and moves that keep the value of the position, though the table
+        // has long forgotten most of the root's children by the end
+        for (_, game) in openings.iter() {
+            let ((i, j, k), score) = small.search_move(game);
+            assert_eq!(score, ybwc.search_move(game).1);
+            let child = game.clone().make_move(i, j, k).unwrap();
+            assert_eq!(
+                tablebase.get(&child).unwrap().score,
+                tablebase.get(game).unwrap().score
+            );
+        }
+
+        // Playing x as well, after x takes the centre and o replies, so that
+        // the defence is searched for both sides
+        let mut pns = pns;
+        let centre = Game::new_with_size(3).make_move(1, 1, 2).unwrap();
+        for (_, game) in centre.legal_moves() {
+            pns.kind = game.current_player_kind();
+            let expected = tablebase.get(&game).unwrap().score_for(pns.kind);
+            assert_eq!(pns.search_move(&game).1, expected);
+        }
+
+        assert_eq!("pns".parse::<Algorithm>(), Ok(Algorithm::ProofNumber));
+        assert!("bogus".parse::<Algorithm>().is_err());
+    }
+
+    #[test]
+    fn difficulty_levels() {
+        // o to move with a win on the board
+        let mut win = Game::new();
+        win = win.make_move(0, 0, 2).unwrap();
+        win = win.make_move(2, 2, 2).unwrap();
+        win = win.make_move(2, 1, 1).unwrap();
+        win = win.make_move(1, 2, 1).unwrap();
+        win = win.make_move(0, 1, 0).unwrap();
+
+        let mut x = Solver::new();
+        x.kind = PlayerKind::X;
+        for difficulty in ["easy", "medium", "hard", "perfect"] {
+            let o = Solver::new().with_difficulty(difficulty.parse().unwrap());
+
+            let mut game = Game::new_with_size(4);
+            while !game.is_finished() && !game.legal_moves().is_empty() {
+                let (i, j, k) = match game.current_player_kind() {
+                    PlayerKind::X => x.search_move(&game).0,
+                    PlayerKind::O => o.find_move(&game),
+                };
+                game = game.make_move(i, j, k).unwrap();
+            }
+            if difficulty == "hard" || difficulty == "perfect" {
+                assert_eq!(game.winner(), Some(Winner::Tie));
+            }
+        }
+
+        let hard = Solver::new().with_difficulty(Difficulty::Hard);
+        let (i, j, k) = hard.find_move(&win);
+        assert_eq!(
+            win.clone().make_move(i, j, k).unwrap().winner(),
+            Some(Winner::O)
+        );
+        assert!("impossible".parse::<Difficulty>().is_err());
+
+        // Easy and medium always take a win on the board, even on the moves
+        // they would otherwise play at random
+        for difficulty in [Difficulty::Easy, Difficulty::Medium] {
+            for seed in 0..20 {
+                let solver = Solver::new().with_difficulty(difficulty).with_seed(seed);
+                let (i, j, k) = solver.find_move(&win);
+                assert_eq!(
+                    win.clone().make_move(i, j, k).unwrap().winner(),
+                    Some(Winner::O)
+                );
+            }
+        }
+
+        // o to move can set up two wins at once, which medium looks far
+        // enough ahead to see and easy doesn't
+        let mut fork = Game::new_with_size(4);
+        for (i, j, k) in [(2, 2, 3), (1, 0, 1), (2, 1, 2)] {
+            fork = fork.make_move(i, j, k).unwrap();
+        }
+        let forks = |difficulty: Difficulty| {
+            (0..20)
+                .filter(|seed| {
+                    let solver = Solver::new().with_difficulty(difficulty).with_seed(*seed);
+                    let (i, j, k) = solver.find_move(&fork);
+                    let after = fork.clone().make_move(i, j, k).unwrap();
+                    after.legal_moves().iter().all(|(_, reply)| {
+                        reply
+                            .legal_moves()
+                            .iter()
+                            .any(|(_, child)| child.winner() == Some(Winner::O))
+                    })
+                })
+                .count()
+        };
+        let (easy, medium) = (forks(Difficulty::Easy), forks(Difficulty::Medium));
+        assert!(medium >= 18);
+        assert!(easy < medium);
+    }
+
+    #[test]
+    fn difficulty_levels_cap_own_pieces() {
+        // o's only piece left, a 1, can only go on its own 0 at 1B. Every
+        // difficulty has to find that move rather than give up.
+        let mut game = Game::new_with_size(6);
+        for (i, j, k) in [
+            (2, 1, 5),
+            (0, 0, 4),
+            (2, 0, 0),
+            (2, 0, 5),
+            (2, 2, 1),
+            (0, 1, 0),
+            (0, 2, 2),
+            (1, 2, 2),
+            (1, 0, 3),
+            (1, 2, 3),
+            (1, 1, 4),
+        ] {
+            game = game.make_move(i, j, k).unwrap();
+        }
+        for difficulty in ["easy", "medium", "hard", "perfect"] {
+            let solver = Solver::new()
+                .with_difficulty(difficulty.parse().unwrap())
+                .with_seed(1);
+            assert_eq!(solver.find_move(&game), (0, 1, 1));
+        }
+    }
+
+    #[test]
+    fn agents_play_matches() {
+        // The solver never loses a small game, whatever x tries
+        let mut solver = Solver::new();
+        for seed in 0..8 {
+            let game = play_match(
+                Game::new_with_size(4),
+                &mut Random::with_seed(seed),
+                &mut solver,
+                |_| (),
+            )
+            .unwrap();
+            assert_ne!(game.winner(), Some(Winner::X));
+        }
+
+        // The same seeds play the same game
+        let mut positions = Vec::new();
+        let first = play_match(
+            Game::new_with_size(5),
+            &mut Random::with_seed(1),
+            &mut Random::with_seed(2),
+            |game| positions.push(game.clone()),
+        )
+        .unwrap();
+        let second = play_match(
+            Game::new_with_size(5),
+            &mut Random::with_seed(1),
+            &mut Random::with_seed(2),
+            |_| (),
+        )
+        .unwrap();
+        assert_eq!(first, second);
+        assert!(!positions.is_empty());
+
+        // The moves played replay to the same game
+        let (third, moves) = play_match_moves(
+            Game::new_with_size(5),
+            &mut Random::with_seed(1),
+            &mut Random::with_seed(2),
+            |_| (),
+        )
+        .unwrap();
+        assert_eq!(third, first);
+        assert_eq!(moves.len(), positions.len());
+        let replayed = moves
+            .iter()
+            .fold(Game::new_with_size(5), |game, (i, j, k)| {
+                game.make_move(*i, *j, *k).unwrap()
+            });
+        assert_eq!(replayed, first);
+
+        // An engine that only plays a piece it doesn't have ends the match
+        // instead of being asked forever
+        struct Stubborn;
+        impl Agent for Stubborn {
+            fn choose_move(&mut self, _game: &Game) -> Move {
+                (1, 1, 9)
+            }
+        }
+        let mut asked = 0;
+        let result = play_match(
+            Game::new_with_size(3),
+            &mut Random::with_seed(1),
+            &mut Stubborn,
+            |_| asked += 1,
+        );
+        assert_eq!(
+            result,
+            Err(
+                "o played 2B9, which isn't legal: You don't have a tile with that size".to_string()
+            )
+        );
+        assert_eq!(asked, 2);
+
+        let mut mcts: Box<dyn Agent> = Box::new(Mcts::new().with_budget(Budget::Playouts(100)));
+        let game = Game::new_with_size(7);
+        let (i, j, k) = mcts.choose_move(&game);
+        assert!(game.make_move(i, j, k).is_ok());
+    }
+
+    #[test]
+    fn position_notation() {
+        let game = Game::new_with_size(3)
+            .make_move(1, 1, 2)
+            .unwrap()
+            .make_move(0, 0, 1)
+            .unwrap();
+        let notation = format_position(&game);
+        assert_eq!(notation, "O1,-,-/-,X2,-/-,-,- x 0,1 0,2");
+        assert_eq!(parse_position(&notation).unwrap(), game);
+
+        let won = parse_position("X2,-,-/X1,O1,-/X0,-,O2 o - 0").unwrap();
+        assert_eq!(won.winner(), Some(Winner::X));
+        assert_eq!(format_position(&won), "X2,-,-/X1,O1,-/X0,-,O2 o - 0");
+
+        assert!(parse_position("X2,-,-/-,O1,- x 0 0").is_err());
+        assert!(parse_position("X2,-,-/-,O1,-/-,-,Z1 x 0 0").is_err());
+        assert!(parse_position("X2,-,-/-,O1,-/-,-,- y 0 0").is_err());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let commands = vec![
+            Command::NewGame { pieces: Some(3) },
+            Command::NewGame { pieces: None },
+            Command::Move {
+                row: 1,
+                col: 2,
+                size: 0,
+            },
+            Command::State,
+            Command::Quit,
+        ];
+        for command in commands {
+            let json = serde_json::to_string(&command).unwrap();
+            assert_eq!(serde_json::from_str::<Command>(&json).unwrap(), command);
+        }
+        assert_eq!(
+            serde_json::from_str::<Command>(r#"{"command":"move","row":1,"col":1,"size":2}"#)
+                .unwrap(),
+            Command::Move {
+                row: 1,
+                col: 1,
+                size: 2
+            }
+        );
+
+        // x wins down the left column, after an illegal move, a line that
+        // isn't JSON, a move off the board and a game too big to play
+        let input = [
+            r#"{"command":"new_game","pieces":3}"#,
+            r#"{"command":"move","row":3,"col":0,"size":1}"#,
+            r#"{"command":"new_game","pieces":100000000000}"#,
+            r#"{"command":"move","row":0,"col":0,"size":2}"#,
+            r#"{"command":"move","row":1,"col":1,"size":2}"#,
+            r#"{"command":"move","row":1,"col":1,"size":1}"#,
+            "not json",
+            r#"{"command":"move","row":1,"col":0,"size":1}"#,
+            r#"{"command":"move","row":2,"col":2,"size":1}"#,
+            r#"{"command":"move","row":2,"col":0,"size":0}"#,
+            r#"{"command":"state"}"#,
+            r#"{"command":"quit"}"#,
+            r#"{"command":"state"}"#,
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        json::run(input.as_bytes(), &mut output, 6, None).unwrap();
+
+        let events = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Event>(line).unwrap())
+            .collect::<Vec<Event>>();
+        for event in events.iter() {
+            let json = serde_json::to_string(event).unwrap();
+            assert_eq!(&serde_json::from_str::<Event>(&json).unwrap(), event);
+        }
+
+        let errors = events
+            .iter()
+            .filter(|event| matches!(event, Event::Error { .. }))
+            .count();
+        assert_eq!(errors, 4);
+        let moves = events
+            .iter()
+            .filter(|event| matches!(event, Event::Move { .. }))
+            .count();
+        assert_eq!(moves, 5);
+        assert!(events.contains(&Event::GameOver { winner: Winner::X }));
+        match events.last() {
+            Some(Event::GameOver { winner: Winner::X }) => (),
+            last => panic!("{:?}", last),
+        }
+        match &events[events.len() - 2] {
+            Event::State { game, legal_moves } => {
+                assert_eq!(game.winner(), Some(Winner::X));
+                assert!(legal_moves.is_empty());
+            }
+            event => panic!("{:?}", event),
+        }
+
+        // The solver answers every move of x
+        let mut solver = Solver::new();
+        let mut output = Vec::new();
+        let input = r#"{"command":"move","row":1,"col":1,"size":2}"#;
+        json::run(input.as_bytes(), &mut output, 3, Some(&mut solver)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let events = output.lines().collect::<Vec<&str>>();
+        assert_eq!(events.len(), 4);
+        assert!(events[2].starts_with(r#"{"event":"move","player":"O","move":["#));
+    }
+
+    // A bare HTTP/1.1 client, returning the status code and the body
+    fn http(address: &str, method: &str, path: &str, body: &str) -> (u16, serde_json::Value) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            path,
+            address,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn server_over_loopback() {
+        let listener = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let address = listener.server_addr().to_ip().unwrap().to_string();
+        let server = Arc::new(Server::new(Solver::new()).with_timeout(Duration::from_secs(60)));
+        thread::spawn(move || server.serve(listener));
+
+        let (status, body) = http(&address, "GET", "/health", "");
+        assert_eq!((status, body["status"].as_str()), (200, Some("ok")));
+
+        let (status, body) = http(&address, "GET", "/lookup/stats", "");
+        assert_eq!(status, 200);
+        assert!(body["positions"].as_u64().unwrap() > 0);
+
+        // x finishes the left column
+        let position = r#"{"position": "X2,-,-/-,O1,-/X1,-,- x 0 0,2"}"#;
+        let (status, body) = http(&address, "POST", "/move", position);
+        assert_eq!(status, 200);
+        assert_eq!(body["move"], "2A0");
+        assert_eq!(
+            (body["row"].as_u64(), body["score"].as_i64()),
+            (Some(1), Some(10))
+        );
+
+        // The 3 piece game is a tie, given as serde writes it
+        let game = serde_json::to_string(&Game::new_with_size(3)).unwrap();
+        let (status, body) = http(
+            &address,
+            "POST",
+            "/evaluate",
+            &format!(r#"{{"game": {}}}"#, game),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body["score"], 0);
+        assert!(!body["pv"].as_array().unwrap().is_empty());
+
+        let finished = r#"{"position": "X2,-,-/X1,O1,-/X0,-,O2 o - 0"}"#;
+        let (status, body) = http(&address, "POST", "/move", finished);
+        assert_eq!(status, 422);
+        assert!(body["error"].is_string());
+        let (status, body) = http(&address, "POST", "/evaluate", finished);
+        assert_eq!((status, body["score"].as_i64()), (200, Some(-10)));
+
+        assert_eq!(http(&address, "POST", "/move", "{").0, 400);
+        assert_eq!(http(&address, "POST", "/move", "{}").0, 400);
+        assert_eq!(http(&address, "GET", "/move", "").0, 405);
+        assert_eq!(http(&address, "GET", "/nowhere", "").0, 404);
+
+        // Far too big to solve in time
+        let (status, body) = http(
+            &address,
+            "POST",
+            "/move",
+            &format!(
+                r#"{{"game": {}, "timeout_ms": 200}}"#,
+                serde_json::to_string(&Game::new_with_size(8)).unwrap()
+            ),
+        );
+        assert_eq!(status, 503);
+        assert!(body["error"].as_str().unwrap().contains("200ms"));
+
+        // The solver is free again once the slow search has been stopped
+        let (status, body) = http(&address, "POST", "/move", position);
+        assert_eq!((status, body["move"].as_str()), (200, Some("2A0")));
+
+        // A position the lookup knows is answered from it, too quickly to
+        // have been searched
+        let lookup = std::fs::read_to_string("data/lookup.json").unwrap();
+        let lookup = serde_json::from_str::<serde_json::Value>(&lookup).unwrap();
+        let (known, moves) = (&lookup["x"][0][0], &lookup["x"][0][1]);
+        let (status, body) = http(
+            &address,
+            "POST",
+            "/move",
+            &format!(r#"{{"game": {}, "timeout_ms": 1000}}"#, known),
+        );
+        assert_eq!((status, &body["score"]), (200, &serde_json::Value::Null));
+        let played = serde_json::json!([body["row"], body["col"], body["size"]]);
+        assert!(*moves == played || moves.as_array().unwrap().contains(&played));
+        let (_, body) = http(&address, "GET", "/lookup/stats", "");
+        assert_eq!(body["hits"], 1);
+
+        // A request can't keep the solver for longer than the server allows
+        let listener = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let address = listener.server_addr().to_ip().unwrap().to_string();
+        let server =
+            Arc::new(Server::new(Solver::new()).with_max_timeout(Duration::from_millis(200)));
+        thread::spawn(move || server.serve(listener));
+        let (status, body) = http(
+            &address,
+            "POST",
+            "/move",
+            &format!(
+                r#"{{"game": {}, "timeout_ms": 3600000}}"#,
+                serde_json::to_string(&Game::new_with_size(8)).unwrap()
+            ),
+        );
+        assert_eq!(status, 503);
+        assert!(body["error"].as_str().unwrap().contains("200ms"));
+    }
+
+    #[test]
+    fn websocket_over_loopback() {
+        type Socket = tungstenite::WebSocket<tungstenite::stream::MaybeTlsStream<TcpStream>>;
+        fn send(socket: &mut Socket, command: websocket::Command) {
+            let text = serde_json::to_string(&command).unwrap();
+            socket.send(tungstenite::Message::Text(text)).unwrap();
+        }
+        fn receive(socket: &mut Socket) -> websocket::Event {
+            loop {
+                if let tungstenite::Message::Text(text) = socket.read().unwrap() {
+                    return serde_json::from_str(&text).unwrap();
+                }
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server =
+            websocket::Server::new(Solver::new()).with_info_interval(Duration::from_millis(10));
+        thread::spawn(move || Arc::new(server).serve(listener));
+        let (mut socket, _) = tungstenite::connect(format!("ws://{}", address)).unwrap();
+        assert!(matches!(
+            receive(&mut socket),
+            websocket::Event::State { .. }
+        ));
+
+        send(&mut socket, websocket::Command::NewGame { pieces: Some(3) });
+        let game = match receive(&mut socket) {
+            websocket::Event::State { game, legal_moves } => {
+                assert_eq!(legal_moves.len(), 27);
+                game
+            }
+            event => panic!("{:?}", event),
+        };
+        assert_eq!(*game, Game::new_with_size(3));
+
+        // Moves are pushed along with the position they lead to
+        send(
+            &mut socket,
+            websocket::Command::Move {
+                row: 1,
+                col: 1,
+                size: 2,
+            },
+        );
+        assert_eq!(
+            receive(&mut socket),
+            websocket::Event::Move {
+                player: PlayerKind::X,
+                r#move: (1, 1, 2)
+            }
+        );
+        assert!(matches!(
+            receive(&mut socket),
+            websocket::Event::State { .. }
+        ));
+        send(
+            &mut socket,
+            websocket::Command::Move {
+                row: 1,
+                col: 1,
+                size: 0,
+            },
+        );
+        assert!(matches!(
+            receive(&mut socket),
+            websocket::Event::Error { .. }
+        ));
+        send(
+            &mut socket,
+            websocket::Command::Move {
+                row: 0,
+                col: 3,
+                size: 1,
+            },
+        );
+        assert_eq!(
+            receive(&mut socket),
+            websocket::Event::Error {
+                message: "Rows and columns go from 0 to 2".to_string()
+            }
+        );
+
+        // The 3 piece game is a tie
+        send(&mut socket, websocket::Command::NewGame { pieces: None });
+        assert!(matches!(
+            receive(&mut socket),
+            websocket::Event::State { .. }
+        ));
+        send(
+            &mut socket,
+            websocket::Command::Analyse { timeout_ms: None },
+        );
+        let analysis = loop {
+            match receive(&mut socket) {
+                websocket::Event::Info { .. } => (),
+                event => break event,
+            }
+        };
+        assert!(matches!(
+            analysis,
+            websocket::Event::Analysis {
+                score: Some(0),
+                best_move: Some(_),
+                ..
+            }
+        ));
+
+        // A long search sends progress until it is stopped
+        send(&mut socket, websocket::Command::NewGame { pieces: Some(8) });
+        assert!(matches!(
+            receive(&mut socket),
+            websocket::Event::State { .. }
+        ));
+        send(
+            &mut socket,
+            websocket::Command::Analyse { timeout_ms: None },
+        );
+        assert!(matches!(
+            receive(&mut socket),
+            websocket::Event::Info { .. }
+        ));
+        send(&mut socket, websocket::Command::Stop);
+        let analysis = loop {
+            match receive(&mut socket) {
+                websocket::Event::Info { .. } => (),
+                event => break event,
+            }
+        };
+        assert!(matches!(
+            analysis,
+            websocket::Event::Analysis { score: None, .. }
+        ));
+        send(&mut socket, websocket::Command::Stop);
+        assert!(matches!(
+            receive(&mut socket),
+            websocket::Event::Error { .. }
+        ));
+
+        // Games too small or too big to play are turned away
+        for pieces in [0, MAX_PIECES + 1] {
+            send(
+                &mut socket,
+                websocket::Command::NewGame {
+                    pieces: Some(pieces),
+                },
+            );
+            assert!(matches!(
+                receive(&mut socket),
+                websocket::Event::Error { .. }
+            ));
+        }
+
+        // No search keeps the solver for longer than the server allows,
+        // whatever it asks for
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server =
+            websocket::Server::new(Solver::new()).with_max_timeout(Duration::from_millis(100));
+        thread::spawn(move || Arc::new(server).serve(listener));
+        let (mut socket, _) = tungstenite::connect(format!("ws://{}", address)).unwrap();
+        send(&mut socket, websocket::Command::NewGame { pieces: Some(8) });
+        for timeout_ms in [None, Some(3_600_000)] {
+            send(&mut socket, websocket::Command::Analyse { timeout_ms });
+            let analysis = loop {
+                if let websocket::Event::Analysis { score, .. } = receive(&mut socket) {
+                    break score;
+                }
+            };
+            assert_eq!(analysis, None);
+        }
+    }
+
+    #[test]
+    fn networked_games_over_loopback() {
+        // Two random players, one hosting and one connecting
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || {
+            Host::new(listener, 4)
+                .play(&mut Random::with_seed(1), |_| ())
+                .unwrap()
+        });
+        let mut positions = 0;
+        let client = Client::new(address)
+            .play(&mut Random::with_seed(2), |_| positions += 1)
+            .unwrap();
+        let host = host.join().unwrap();
+        assert_eq!(host, client);
+        assert!(host.is_finished() || host.legal_moves().is_empty());
+        assert!(positions > 0);
+
+        // o drops out after an illegal move and a legal one, and comes back
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || {
+            Host::new(listener, 3)
+                .play(&mut Random::with_seed(3), |_| ())
+                .unwrap()
+        });
+        let hello = Message::Hello {
+            version: PROTOCOL_VERSION,
+        };
+
+        let mut first = Connection::new(TcpStream::connect(address).unwrap()).unwrap();
+        first.send(&hello).unwrap();
+        assert!(
+            matches!(first.receive().unwrap(), Message::Welcome { moves, .. } if moves.is_empty())
+        );
+        let x_move = match first.receive().unwrap() {
+            Message::Move { r#move } => r#move,
+            message => panic!("{:?}", message),
+        };
+        first
+            .send(&Message::Move {
+                r#move: (x_move.0, x_move.1, 0),
+            })
+            .unwrap();
+        assert!(matches!(first.receive().unwrap(), Message::Rejected { .. }));
+        first.send(&Message::Move { r#move: (0, 3, 1) }).unwrap();
+        assert!(matches!(first.receive().unwrap(), Message::Rejected { .. }));
+        let game = Game::new_with_size(3)
+            .make_move(x_move.0, x_move.1, x_move.2)
+            .unwrap();
+        let (o_move, _) = game.legal_moves()[0].clone();
+        first.send(&Message::Move { r#move: o_move }).unwrap();
+        assert_eq!(
+            first.receive::<Message>().unwrap(),
+            Message::Move { r#move: o_move }
+        );
+        drop(first);
+
+        // Someone speaking another version is turned away
+        let mut stranger = Connection::new(TcpStream::connect(address).unwrap()).unwrap();
+        stranger.send(&Message::Hello { version: 0 }).unwrap();
+        assert!(matches!(stranger.receive().unwrap(), Message::Error { .. }));
+        drop(stranger);
+
+        let client = Client::new(address)
+            .with_retries(5, Duration::from_millis(10))
+            .play(&mut Random::with_seed(4), |_| ())
+            .unwrap();
+        assert_eq!(host.join().unwrap(), client);
+
+        // The host drops out and comes back, the client catching up from the
+        // moves it is welcomed with
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || {
+            let welcome = |moves: Vec<(usize, usize, usize)>| Message::Welcome {
+                version: PROTOCOL_VERSION,
+                number_of_pieces: 2,
+                moves,
+            };
+            let mut connection = Connection::new(listener.accept().unwrap().0).unwrap();
+            assert_eq!(connection.receive::<Message>().unwrap(), hello);
+            connection.send(&welcome(Vec::new())).unwrap();
+            connection
+                .send(&Message::Move { r#move: (1, 1, 1) })
+                .unwrap();
+            let proposed = connection.receive().unwrap();
+            drop(connection);
+
+            let mut connection = Connection::new(listener.accept().unwrap().0).unwrap();
+            assert_eq!(connection.receive::<Message>().unwrap(), hello);
+            match proposed {
+                Message::Move { r#move } => {
+                    connection.send(&welcome(vec![(1, 1, 1), r#move])).unwrap()
+                }
+                message => panic!("{:?}", message),
+            }
+            connection
+                .send(&Message::GameOver {
+                    winner: Winner::Tie,
+                })
+                .unwrap();
+        });
+        let client = Client::new(address)
+            .with_retries(5, Duration::from_millis(10))
+            .play(&mut Random::with_seed(5), |_| ())
+            .unwrap();
+        host.join().unwrap();
+        assert_eq!(client.get_turn_count(), 3);
+
+        // A host that welcomes with an impossible game, or plays off the
+        // board, is given up on
+        for (number_of_pieces, r#move) in [(usize::MAX, (1, 1, 1)), (2, (3, 0, 1))] {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap();
+            let host = thread::spawn(move || {
+                let mut connection = Connection::new(listener.accept().unwrap().0).unwrap();
+                connection.receive::<Message>().unwrap();
+                connection
+                    .send(&Message::Welcome {
+                        version: PROTOCOL_VERSION,
+                        number_of_pieces,
+                        moves: Vec::new(),
+                    })
+                    .unwrap();
+                let _ = connection.send(&Message::Move { r#move });
+                let _ = connection.receive::<Message>();
+            });
+            let error = Client::new(address)
+                .play(&mut Random::with_seed(5), |_| ())
+                .unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            host.join().unwrap();
+        }
+
+        // Someone who connects and says nothing doesn't hold the host up, and
+        // a connection that goes quiet is replaced by a newer one
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || {
+            Host::new(listener, 3)
+                .with_hello_timeout(Duration::from_millis(100))
+                .play(&mut Random::with_seed(6), |_| ())
+                .unwrap()
+        });
+        let silent = TcpStream::connect(address).unwrap();
+        let mut stale = Connection::new(TcpStream::connect(address).unwrap()).unwrap();
+        stale
+            .send(&Message::Hello {
+                version: PROTOCOL_VERSION,
+            })
+            .unwrap();
+        assert!(matches!(stale.receive().unwrap(), Message::Welcome { .. }));
+        let client = Client::new(address)
+            .play(&mut Random::with_seed(7), |_| ())
+            .unwrap();
+        assert_eq!(host.join().unwrap(), client);
+        drop((silent, stale));
+    }
+
+    #[test]
+    fn lobby_over_loopback() {
+        let records = "./data/test_lobby_records.txt";
+        let _ = fs::remove_file(records);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let lobby = Lobby::new().with_records(records.into());
+        thread::spawn(move || Arc::new(lobby).serve(listener));
+        let join = |name: &str| {
+            let mut connection = Connection::new(TcpStream::connect(address).unwrap()).unwrap();
+            connection
+                .send(&lobby::Command::Hello {
+                    version: LOBBY_VERSION,
+                    name: name.to_string(),
+                })
+                .unwrap();
+            assert_eq!(
+                connection.receive::<lobby::Event>().unwrap(),
+                lobby::Event::Welcome {
+                    version: LOBBY_VERSION
+                }
+            );
+            connection
+        };
+        let play = lobby::Command::Play {
+            number_of_pieces: 3,
+            opponent: Opponent::Human,
+        };
+
+        // Alice waits for an opponent, Bob is paired with her and Carol
+        // watches
+        let mut alice = join("alice");
+        alice.send(&play).unwrap();
+        let room = match alice.receive().unwrap() {
+            lobby::Event::Joined {
+                room,
+                role: Role::X,
+                ..
+            } => room,
+            event => panic!("{:?}", event),
+        };
+        let mut bob = join("bob");
+        bob.send(&lobby::Command::ListRooms).unwrap();
+        assert!(matches!(
+            bob.receive().unwrap(),
+            lobby::Event::Rooms { rooms } if rooms.len() == 1 && rooms[0].o.is_none()
+        ));
+        bob.send(&play).unwrap();
+        assert!(matches!(
+            bob.receive().unwrap(),
+            lobby::Event::Joined { role: Role::O, room: r, .. } if r == room
+        ));
+        let started = lobby::Event::Started {
+            room,
+            x: "alice".to_string(),
+            o: "bob".to_string(),
+        };
+        assert_eq!(alice.receive::<lobby::Event>().unwrap(), started);
+        assert_eq!(bob.receive::<lobby::Event>().unwrap(), started);
+        let mut carol = join("carol");
+        carol.send(&lobby::Command::Spectate { room }).unwrap();
+        assert!(matches!(
+            carol.receive().unwrap(),
+            lobby::Event::Joined {
+                role: Role::Spectator,
+                ..
+            }
+        ));
+
+        // Moves out of turn, and from the spectator, are rejected
+        bob.send(&lobby::Command::Move {
+            room,
+            r#move: (1, 1, 2),
+        })
+        .unwrap();
+        assert!(matches!(
+            bob.receive().unwrap(),
+            lobby::Event::Rejected { .. }
+        ));
+        carol
+            .send(&lobby::Command::Move {
+                room,
+                r#move: (1, 1, 2),
+            })
+            .unwrap();
+        assert!(matches!(
+            carol.receive().unwrap(),
+            lobby::Event::Rejected { .. }
+        ));
+        // So is a move off the board, without bringing the lobby down
+        alice
+            .send(&lobby::Command::Move {
+                room,
+                r#move: (0, 3, 1),
+            })
+            .unwrap();
+        assert!(matches!(
+            alice.receive().unwrap(),
+            lobby::Event::Rejected { .. }
+        ));
+
+        let moves = "2B2 1A1 3C1 1C2 3A0 2A0"
+            .split(' ')
+            .map(|m| parse_move(m).unwrap())
+            .collect::<Vec<(usize, usize, usize)>>();
+        for (i, m) in moves.iter().enumerate() {
+            let (player, connection) = match i % 2 {
+                0 => (PlayerKind::X, &mut alice),
+                _ => (PlayerKind::O, &mut bob),
+            };
+            connection
+                .send(&lobby::Command::Move { room, r#move: *m })
+                .unwrap();
+            let moved = lobby::Event::Moved {
+                room,
+                player,
+                r#move: *m,
+            };
+            for connection in [&mut alice, &mut bob, &mut carol] {
+                assert_eq!(connection.receive::<lobby::Event>().unwrap(), moved);
+            }
+        }
+        let over = lobby::Event::GameOver {
+            room,
+            winner: Winner::Tie,
+        };
+        for connection in [&mut alice, &mut bob, &mut carol] {
+            assert_eq!(connection.receive::<lobby::Event>().unwrap(), over);
+        }
+        let written = parse_records(&fs::read_to_string(records).unwrap()).unwrap();
+        assert_eq!(written.len(), 1);
+        assert_eq!(
+            (written[0].x.as_str(), written[0].o.as_str()),
+            ("alice", "bob")
+        );
+        assert_eq!(written[0].moves, moves);
+        assert_eq!(written[0].replay().unwrap().winner(), Some(Winner::Tie));
+
+        // Games that can't be played, or would take the solver too long, are
+        // turned away
+        let mut dave = join("dave");
+        for (number_of_pieces, opponent) in [
+            (0, Opponent::Human),
+            (MAX_PIECES + 1, Opponent::Human),
+            (
+                lobby::MAX_SOLVER_PIECES + 1,
+                Opponent::Solver {
+                    difficulty: Difficulty::Easy,
+                },
+            ),
+        ] {
+            dave.send(&lobby::Command::Play {
+                number_of_pieces,
+                opponent,
+            })
+            .unwrap();
+            assert!(matches!(
+                dave.receive().unwrap(),
+                lobby::Event::Error { .. }
+            ));
+        }
+
+        // Dave can't beat the perfect solver with random moves
+        dave.send(&lobby::Command::Play {
+            number_of_pieces: 3,
+            opponent: Opponent::Solver {
+                difficulty: Difficulty::Perfect,
+            },
+        })
+        .unwrap();
+        let room = match dave.receive().unwrap() {
+            lobby::Event::Joined {
+                room,
+                role: Role::X,
+                ..
+            } => room,
+            event => panic!("{:?}", event),
+        };
+        assert!(matches!(
+            dave.receive().unwrap(),
+            lobby::Event::Started { .. }
+        ));
+        let mut random = Random::with_seed(6);
+        let mut game = Game::new_with_size(3);
+        dave.send(&lobby::Command::Move {
+            room,
+            r#move: random.choose_move(&game),
+        })
+        .unwrap();
+        let winner = loop {
+            match dave.receive().unwrap() {
+                lobby::Event::Moved { r#move: m, .. } => {
+                    game = game.make_move(m.0, m.1, m.2).unwrap();
+                    let over = game.is_finished() || game.legal_moves().is_empty();
+                    if game.current_player_kind() == PlayerKind::X && !over {
+                        let r#move = random.choose_move(&game);
+                        dave.send(&lobby::Command::Move { room, r#move }).unwrap();
+                    }
+                }
+                lobby::Event::GameOver { winner, .. } => break winner,
+                event => panic!("{:?}", event),
+            }
+        };
+        assert_ne!(winner, Winner::X);
+        let _ = fs::remove_file(records);
+    }
+
+    #[test]
+    fn tui_picks_moves() {
+        use crossterm::event::KeyCode;
+
+        let mut game = Game::new_with_size(3);
+        let mut picker = Picker::new(&game);
+        assert_eq!((picker.row, picker.col, picker.size), (1, 1, Some(2)));
+        assert_eq!(
+            picker.key(&game, KeyCode::Enter),
+            Some(Action::Play((1, 1, 2)))
+        );
+
+        // The cursor stops at the edges, and only pieces in hand are picked
+        for _ in 0..3 {
+            picker.key(&game, KeyCode::Up);
+            picker.key(&game, KeyCode::Left);
+        }
+        assert_eq!((picker.row, picker.col), (0, 0));
+        picker.key(&game, KeyCode::Char('7'));
+        assert_eq!(picker.size, Some(2));
+        picker.key(&game, KeyCode::Tab);
+        assert_eq!(picker.size, Some(0));
+        picker.key(&game, KeyCode::BackTab);
+        assert_eq!(picker.size, Some(2));
+        picker.key(&game, KeyCode::Char('1'));
+        assert_eq!(
+            picker.key(&game, KeyCode::Char(' ')),
+            Some(Action::Play((0, 0, 1)))
+        );
+
+        // A smaller piece can't go on top of a bigger one
+        game = game.make_move(0, 0, 2).unwrap();
+        picker.key(&game, KeyCode::Char('1'));
+        assert_eq!(picker.key(&game, KeyCode::Enter), None);
+        picker.key(&game, KeyCode::Right);
+        assert_eq!(
+            picker.key(&game, KeyCode::Enter),
+            Some(Action::Play((0, 1, 1)))
+        );
+        assert_eq!(picker.key(&game, KeyCode::Char('q')), Some(Action::Quit));
+
+        let mut screen = Vec::new();
+        tui::draw(
+            &mut screen,
+            &game,
+            Some(&picker),
+            Some((0, 0, 2)),
+            "o to move",
+            "",
+        )
+        .unwrap();
+        let screen = String::from_utf8(screen).unwrap();
+        assert!(screen.contains("X2"));
+        assert!(screen.contains("o to move"));
+    }
+
+    #[test]
+    fn renderer_layouts() {
+        let game = parse_position("X2,-,-/-,O1,-/-,-,- x 0,1 0,2").unwrap();
+        let plain = Renderer::new().with_colour(false);
+        assert_eq!(
+            plain.render(game.tiles(), None),
+            "   A  B  C\n 1 X2 _  _\n 2 _  O1 _\n 3 _  _  _\n\n"
+        );
+        assert_eq!(
+            plain
+                .clone()
+                .with_layout(Layout::Split)
+                .render(game.tiles(), None),
+            "   A B C\n 1 x _ _     2 _ _\n 2 _ o _     _ 1 _\n 3 _ _ _     _ _ _\n\n"
+        );
+        let glyphs = plain.clone().with_glyphs(true).render(game.tiles(), None);
+        assert!(glyphs.contains(" 1 ●X2 _"));
+        assert!(glyphs.contains(" 2 _   ○O1 _"));
+
+        // Colour for both players, and bold for the last move
+        let coloured = Renderer::new()
+            .with_colour(true)
+            .render(game.tiles(), Some((1, 1, 1)));
+        assert!(coloured.contains("\x1b[31mX2\x1b[0m"));
+        assert!(coloured.contains("\x1b[1;36mO1\x1b[0m"));
+        assert_eq!("split".parse::<Layout>(), Ok(Layout::Split));
+        assert!("fancy".parse::<Layout>().is_err());
+
+        let before = Game::new_with_size(3);
+        let after = before.clone().make_move(2, 0, 1).unwrap();
+        assert_eq!(before.move_between(&after), Some((2, 0, 1)));
+        assert_eq!(after.move_between(&before), None);
+    }
+
+    #[test]
+    fn svg_diagrams() {
+        let game = parse_position("X2,-,-/-,O1,-/-,-,X0 o 0,1 0,2").unwrap();
+        let svg = Diagram::new()
+            .with_arrows(vec![(2, 0, 2), (0, 2, 1)])
+            .render(&game);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        // A ring for every size up to each piece's own, on the board, in
+        // hand and for the moves
+        assert_eq!(svg.matches("<circle").count(), 6 + 7 + 2);
+        assert_eq!(svg.matches("marker-end").count(), 1);
+        assert!(svg.contains(">B</text>") && svg.contains(">3</text>"));
+
+        let bare = Diagram::new()
+            .with_labels(false)
+            .with_hands(false)
+            .with_square(50)
+            .render(&game);
+        assert_eq!(bare.matches("<circle").count(), 6);
+        assert!(!bare.contains("<text"));
+        assert!(bare.contains("width=\"160\""));
+    }
+
+    #[test]
+    fn winning_lines() {
+        let mut game = Game::new();
+        assert!(game.winning_line().is_empty());
+        // x captures o's piece in the corner, completing the top row and a
+        // diagonal at once
+        for (row, col, size) in [
+            (0, 0, 0),
+            (2, 2, 0),
+            (0, 1, 1),
+            (1, 0, 1),
+            (1, 1, 2),
+            (2, 1, 2),
+            (2, 0, 3),
+            (0, 2, 3),
+        ] {
+            game = game.make_move(row, col, size).unwrap();
+            assert!(game.winning_line().is_empty());
+        }
+        let game = game.make_move(0, 2, 4).unwrap();
+        assert_eq!(game.winner(), Some(Winner::X));
+        assert_eq!(
+            game.winning_line(),
+            vec![[(0, 0), (0, 1), (0, 2)], [(0, 2), (1, 1), (2, 0)]]
+        );
+        assert!(game
+            .winning_line()
+            .concat()
+            .iter()
+            .all(|(row, col)| matches!(game.tiles()[*row][*col], Some((PlayerKind::X, _)))));
+
+        let column = parse_position("X0,O1,-/-,O2,-/X1,O0,- x 2 2").unwrap();
+        assert_eq!(column.winner(), Some(Winner::O));
+        assert_eq!(column.winning_line(), vec![[(0, 1), (1, 1), (2, 1)]]);
+
+        // Underlined on the console and struck through in SVG
+        let coloured = Renderer::new().with_colour(true).render_with_lines(
+            game.tiles(),
+            Some((0, 2, 4)),
+            &game.winning_line(),
+        );
+        assert!(coloured.contains("\x1b[1;4;31mX4\x1b[0m"));
+        assert!(coloured.contains("\x1b[4;31mX0\x1b[0m"));
+        assert!(coloured.contains("\x1b[36mO1\x1b[0m"));
+        let svg = Diagram::new().render(&game);
+        assert_eq!(svg.matches("class=\"winning-line\"").count(), 2);
+        assert!(!Diagram::new().render(&Game::new()).contains("winning-line"));
+    }
+
+    #[test]
+    fn hints() {
+        let mut solver = Solver::new();
+        // x threatens the top row, which o can stop by capping 1A for good
+        // or by taking 1C with a piece x can't cap
+        let game = parse_position("X2,X3,-/-,O2,-/-,-,- o 0,1 0,1,3").unwrap();
+        let hint = Hint::new(&game, &mut solver);
+        assert!(hint.wins.is_empty());
+        assert_eq!(
+            hint.threats,
+            vec![Threat {
+                player: PlayerKind::X,
+                line: [(0, 0), (0, 1), (0, 2)],
+                square: (0, 2),
+                sizes: vec![0, 1],
+                caps: vec![(0, 0, 3), (0, 2, 1), (0, 2, 3)],
+            }]
+        );
+        assert!(hint.can_defend());
+        assert_eq!(hint.analysis.score, Some(10));
+        assert_eq!(game.winner(), None);
+
+        // x can win on 1C, and can't stop o's middle row
+        let game = parse_position("X2,X3,-/O1,O2,-/-,-,- x 0,1 0,3").unwrap();
+        let hint = Hint::new(&game, &mut solver);
+        assert_eq!(hint.wins.len(), 1);
+        assert_eq!(hint.wins[0].square, (0, 2));
+        assert_eq!(hint.threats.len(), 1);
+        assert_eq!(hint.threats[0].square, (1, 2));
+        assert_eq!(hint.threats[0].sizes, vec![0, 3]);
+        assert!(!hint.can_defend());
+        assert_eq!(hint.analysis.score, Some(10));
+        assert!(matches!(hint.analysis.best_move, Some((0, 2, _))));
+        let text = hint.to_string();
+        assert!(text.starts_with("You can win on 1C with 0, 1\n"));
+        assert!(text.contains("o threatens 2C with 0, 3; it can't be stopped\n"));
+        assert!(text.ends_with(", wins\n"));
+
+        // o threatens the top and bottom rows, and x can stop either one but
+        // not both
+        let game = parse_position("O4,O2,-/X3,X2,-/O3,-,O1 x 4 0").unwrap();
+        let hint = Hint::new(&game, &mut solver);
+        assert_eq!(hint.threats.len(), 2);
+        assert_eq!(hint.threats[0].caps, vec![(0, 1, 4), (0, 2, 4)]);
+        assert_eq!(hint.threats[1].caps, vec![(2, 0, 4), (2, 1, 4), (2, 2, 4)]);
+        assert!(hint.defences().is_empty());
+        assert!(!hint.can_defend());
+        let text = hint.to_string();
+        assert!(text.contains("o threatens 1C with 0\no threatens 3B with 0\n"));
+        assert!(text.contains("No one move stops them all\n"));
+        assert!(!text.contains("stop it with"));
+
+        assert!(hint::threats(&Game::new(), PlayerKind::X).is_empty());
+        let text = Hint::new(&Game::new_with_size(3), &mut solver).to_string();
+        assert!(text.starts_with("Neither player can complete a line next move\n"));
+
+        // Without searching, only what the tablebase or the lookup know
+        let empty = Game::new_with_size(3);
+        let hint = Hint::recalled(&empty, &solver);
+        assert_eq!((hint.analysis.best_move, hint.analysis.nodes), (None, 0));
+        assert!(hint.to_string().ends_with("There is no move to suggest\n"));
+        let solver = Solver::new().with_tablebase(Tablebase::generate(3));
+        let hint = Hint::recalled(&empty, &solver);
+        assert_eq!((hint.analysis.score, hint.analysis.nodes), (Some(0), 0));
+        assert_eq!(hint.analysis.best_move, hint.analysis.pv.first().copied());
+        assert!(!hint.analysis.pv.is_empty());
+    }
+
+    #[test]
+    fn blunder_reports() {
+        let text = "[Pieces \"4\"]\n[X \"a\"]\n[O \"b\"]\n[Result \"*\"]\n\n1A2 2B2 1B3 3C3 2A0\n";
+        let record = text.parse::<GameRecord>().unwrap();
+        let report = Report::new(&record, &mut Solver::new()).unwrap();
+        assert_eq!(
+            report
+                .annotations
+                .iter()
+                .map(|annotation| annotation.verdict)
+                .collect::<Vec<Verdict>>(),
+            vec![
+                Verdict::Best,
+                Verdict::Best,
+                Verdict::Blunder,
+                Verdict::Blunder,
+                Verdict::Best
+            ]
+        );
+        // o threw away the win that x's blunder handed them
+        let thrown = &report.annotations[3];
+        assert_eq!(
+            (thrown.player, thrown.before, thrown.after),
+            (PlayerKind::O, 10, -10)
+        );
+        assert_eq!(thrown.better.map(|(row, col, _)| (row, col)), Some((0, 2)));
+        // x still wins after 2A0, and only the tablebase can tell it's slower
+        // than 1C
+        let slow = &report.annotations[4];
+        assert_eq!((slow.before, slow.after, slow.better), (10, 10, None));
+        assert_eq!(report.annotations[0].better, None);
+        assert_eq!(report.count(Verdict::Blunder), 2);
+
+        let text = report.to_string();
+        assert!(text.starts_with("[Pieces \"4\"]\n"));
+        assert!(text.contains(" 1. x 1A2  best, ties\n"));
+        assert!(text.contains(" 4. o 3C3  blunder, loses instead of winning; 1C"));
+        assert!(text.contains(" 5. x 2A0  best, wins\n"));
+        assert!(text.ends_with("\n2 blunders, 0 inaccuracies\n"));
+
+        let mut illegal = record;
+        illegal.moves.push((0, 0, 0));
+        assert!(Report::new(&illegal, &mut Solver::new()).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn blunder_reports_full() {
+        let text = "[Pieces \"4\"]\n[X \"a\"]\n[O \"b\"]\n[Result \"*\"]\n\n1A2 2B2 1B3 3C3 2A0\n";
+        let record = text.parse::<GameRecord>().unwrap();
+        let mut solver = Solver::new().with_tablebase(Tablebase::generate(4));
+        let report = Report::new(&record, &mut solver).unwrap();
+        assert_eq!(
+            report
+                .annotations
+                .iter()
+                .map(|annotation| annotation.verdict)
+                .collect::<Vec<Verdict>>(),
+            vec![
+                Verdict::Best,
+                Verdict::Best,
+                Verdict::Blunder,
+                Verdict::Blunder,
+                Verdict::Inaccuracy
+            ]
+        );
+        // x still wins after 2A0, just not straight away on 1C
+        let slow = &report.annotations[4];
+        assert_eq!((slow.before, slow.after), (10, 10));
+        assert!(matches!(slow.better, Some((0, 2, _))));
+
+        let text = report.to_string();
+        assert!(text.contains(" 5. x 2A0  inaccuracy, wins more slowly; 1C"));
+        assert!(text.ends_with("\n2 blunders, 1 inaccuracy\n"));
+    }
+
+    #[test]
+    fn gif_replays() {
+        let text =
+            "[Pieces \"3\"]\n[X \"a\"]\n[O \"b\"]\n[Result \"1-0\"]\n\n2B2 1A1 1B1 3C2 3B0\n";
+        let record = text.parse::<GameRecord>().unwrap();
+        let animation = Animation::new().with_square(30).with_delay(500);
+        let frames = animation.frames(&record).unwrap();
+        assert_eq!(frames.len(), 6);
+        assert_eq!(
+            frames[0],
+            (Raster::draw(&Game::new_with_size(3), 30, &[]), 500)
+        );
+        // Only the final position shows the winning line
+        let highlighted = |raster: &Raster| raster.pixels.contains(&4);
+        assert!(!frames[..5].iter().any(|(raster, _)| highlighted(raster)));
+        assert!(highlighted(&frames[5].0));
+        assert_eq!(frames[5].1, 3000);
+
+        let mut gif = Vec::new();
+        animation.write_gif(&record, &mut gif).unwrap();
+        let mut decoder = gif::DecodeOptions::new().read_info(gif.as_slice()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (96, 96));
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        assert_eq!(delays, vec![50, 50, 50, 50, 50, 300]);
+
+        let mut illegal = record.clone();
+        illegal.moves[3] = (2, 2, 1);
+        assert_eq!(
+            animation.frames(&illegal).unwrap_err(),
+            "Move 4: You don't have a tile with that size"
+        );
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let text = "[Pieces \"3\"]\n[X \"solver\"]\n[O \"random\"]\n[Result \"1/2-1/2\"]\n\n2B2 1A1 3C1 1C2 3A0 2A0\n";
+        let record = text.parse::<GameRecord>().unwrap();
+        assert_eq!(record.moves.len(), 6);
+        assert_eq!(record.winner, Some(Winner::Tie));
+        assert_eq!(record.to_string(), text);
+        assert_eq!(record.replay().unwrap().winner(), Some(Winner::Tie));
+        assert_eq!(record.replay().unwrap().get_number_of_pieces(), 3);
+
+        let mut unfinished = GameRecord::new(4, "mcts:playouts=10", "random");
+        unfinished.moves = vec![(0, 0, 3), (1, 1, 3)];
+        let both = format!("{}\n{}", record, unfinished);
+        assert_eq!(parse_records(&both).unwrap(), vec![record, unfinished]);
+
+        assert!("[Pieces \"3\"]\n\n9Z9".parse::<GameRecord>().is_err());
+
+        // Sizes of 10 and up take more than one digit
+        let mut large = GameRecord::new(12, "solver", "random");
+        large.moves = vec![(0, 0, 11), (1, 1, 10), (2, 2, 9)];
+        assert_eq!(large.to_string().parse::<GameRecord>().unwrap(), large);
+        assert_eq!(parse_move("3C11").unwrap(), (2, 2, 11));
+        assert!(parse_move("3C").is_err());
+    }
+
+    #[test]
+    fn tournament_scores() {
+        assert!("solver:difficulty=godlike".parse::<AgentSpec>().is_err());
+        assert!("chess".parse::<AgentSpec>().is_err());
+
+        let tournament = Tournament {
+            agents: vec![
+                "solver".parse().unwrap(),
+                "random".parse().unwrap(),
+                "mcts:playouts=50".parse().unwrap(),
+            ],
+            format: Format::Gauntlet,
+            number_of_pieces: 3,
+            games: 4,
+            opening_plies: 0,
+            seed: 0,
+        };
+        assert_eq!(tournament.pairings(), vec![(0, 1), (0, 2)]);
+
+        let mut records = Vec::new();
+        let results = tournament
+            .run(|record| records.push(record.clone()))
+            .unwrap();
+        assert_eq!(records.len(), 8);
+        for record in records.iter() {
+            let game = record.replay().unwrap();
+            if game.is_finished() {
+                assert_eq!(game.winner(), record.winner);
+            }
+        }
+
+        // The solver plays perfectly from the empty board, so never loses
+        let solver = results.total(0);
+        assert_eq!(solver.games(), 8);
+        assert_eq!(solver.losses, 0);
+        assert_eq!(results.total(1).games(), 4);
+        assert_eq!(results.table[1][2].games(), 0);
+        let (elo, low, high) = solver.elo();
+        assert!(low <= elo && elo <= high);
+        assert!(elo >= results.total(1).elo().0);
+
+        // Nothing to go on before any games are played
+        let none = Score::default();
+        assert_eq!(none.fraction(), 0.5);
+        assert_eq!(none.elo(), (0.0, -800.0, 800.0));
+    }
+
+    #[test]
+    fn mcts_wins_and_blocks() {
+        let mcts = Mcts::new().with_budget(Budget::Playouts(2000)).with_seed(0);
+
+        let mut game = Game::new();
+        game = game.make_move(0, 0, 2).unwrap();
+        game = game.make_move(2, 2, 2).unwrap();
+        game = game.make_move(2, 1, 1).unwrap();
+        game = game.make_move(1, 2, 1).unwrap();
+        game = game.make_move(0, 1, 0).unwrap();
+        let (i, j, k) = mcts.find_move(&game);
+        assert_eq!(game.make_move(i, j, k).unwrap().winner(), Some(Winner::O));
+
+        // o has to block with a piece x can't cap
+        let mut game = Game::new_with_size(4);
+        game = game.make_move(0, 0, 3).unwrap();
+        game = game.make_move(1, 1, 3).unwrap();
+        game = game.make_move(0, 1, 2).unwrap();
+        for rollout in [Rollout::Random, Rollout::Heuristic] {
+            let (i, j, k) = mcts.clone().with_rollout(rollout).find_move(&game);
+            let blocked = game.clone().make_move(i, j, k).unwrap();
+            assert!(blocked
+                .legal_moves()
+                .iter()
+                .all(|(_, child)| child.winner() != Some(Winner::X)));
+        }
+
+        let mcts = mcts.with_budget(Budget::Playouts(200));
+        let mut game = Game::new_with_size(8);
+        while !game.is_finished() && !game.legal_moves().is_empty() {
+            let (i, j, k) = mcts.find_move(&game);
+            game = game.make_move(i, j, k).unwrap();
+        }
+        assert_eq!("random".parse::<Rollout>(), Ok(Rollout::Random));
+        assert!("bogus".parse::<Rollout>().is_err());
+    }
+
+    #[test]
+    fn key_round_trip() {
+        let mut game = Game::new_with_size(4);
+        assert_eq!(Game::from_key(game.key().unwrap()), game);
+        for (i, j, k) in [(1, 1, 2), (0, 0, 3), (1, 1, 3), (0, 2, 0), (2, 0, 1)] {
+            game = game.make_move(i, j, k).unwrap();
+            assert_eq!(Game::from_key(game.key().unwrap()), game);
+        }
+
+        let mut game = Game::new();
+        game = game.make_move(0, 0, 2).unwrap();
+        game = game.make_move(0, 1, 2).unwrap();
+        game = game.make_move(2, 2, 1).unwrap();
+        game = game.make_move(2, 1, 1).unwrap();
+        game = game.make_move(1, 1, 0).unwrap();
+        assert_eq!(Game::from_key(game.key().unwrap()), game);
+        assert_eq!(
+            Game::from_key(game.key().unwrap()).winner(),
+            Some(Winner::X)
+        );
+
+        let corner = Game::new().make_move(0, 0, 1).unwrap();
+        for (i, j) in [(0, 2), (2, 0), (2, 2)] {
+            let other = Game::new().make_move(i, j, 1).unwrap();
+            assert_eq!(corner.canonical_key(), other.canonical_key());
+        }
+        let edge = Game::new().make_move(0, 1, 1).unwrap();
+        assert_ne!(corner.canonical_key(), edge.canonical_key());
+
+        // The largest pieces that fit, then one too many
+        let game = Game::new_with_size(MAX_PIECES)
+            .make_move(1, 1, MAX_PIECES - 1)
+            .unwrap();
+        assert_eq!(Game::from_key(game.key().unwrap()), game);
+        assert!(Game::new_with_size(MAX_PIECES + 1).key().is_err());
+        assert!(Game::new_with_size(MAX_PIECES + 1).canonical_key().is_err());
+    }
+
+    #[test]
+    fn tablebase() {
+        let tablebase = Tablebase::generate(3);
+        assert_eq!(tablebase.len(), 32686);
+        assert_eq!(tablebase.get(&Game::new_with_size(3)).unwrap().score, 0);
+
+        // Agrees with the search on every reply to the opening moves
+        let solver = Solver::new();
+        for i in 0..3 {
+            for j in 0..3 {
+                for k in 0..3 {
+                    let game = Game::new_with_size(3).make_move(i, j, k).unwrap();
+                    let (_, score) = solver.search_move(&game);
+                    assert_eq!(tablebase.get(&game).unwrap().score, score);
+                }
+            }
+        }
+
+        // x can win at once on the top row, so the tablebase takes that
+        // rather than a longer win
+        let mut game = Game::new_with_size(3);
+        for (i, j, k) in [(0, 0, 1), (2, 2, 0), (0, 1, 0), (1, 1, 1)] {
+            game = game.make_move(i, j, k).unwrap();
+        }
+        let entry = tablebase.get(&game).unwrap();
+        assert_eq!((entry.score, entry.depth), (-10, 1));
+        assert_eq!(entry.score_for(PlayerKind::X), 10);
+        assert_eq!(entry.score_for(PlayerKind::O), -10);
+        assert_eq!(tablebase.best_move(&game), Some((0, 2, 2)));
+        assert!(tablebase
+            .get(&Game::new_with_size(MAX_PIECES + 1))
+            .is_none());
+
+        // Following the best moves, each position is one move closer to the
+        // end than the one before
+        for (i, j, k) in [(0, 0, 0), (1, 1, 2), (0, 1, 1)] {
+            let mut game = Game::new_with_size(3).make_move(i, j, k).unwrap();
+            while let Some((i, j, k)) = tablebase.best_move(&game) {
+                let entry = tablebase.get(&game).unwrap();
+                game = game.make_move(i, j, k).unwrap();
+                let next = tablebase.get(&game).unwrap();
+                assert_eq!(next.score, entry.score);
+                assert_eq!(next.depth + 1, entry.depth);
+            }
+        }
+
+        let _ = fs::remove_file("./data/test_tablebase.bin");
+        tablebase.write("./data/test_tablebase.bin").unwrap();
+        let read = Tablebase::read("./data/test_tablebase.bin").unwrap();
+
+        // An entry count the file doesn't hold is refused, not allocated
+        let mut bytes = fs::read("./data/test_tablebase.bin").unwrap();
+        bytes[6..14].copy_from_slice(&u64::MAX.to_le_bytes());
+        fs::write("./data/test_tablebase.bin", &bytes).unwrap();
+        match Tablebase::read("./data/test_tablebase.bin") {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            Ok(_) => panic!("read a tablebase with more entries than its file"),
+        }
+        bytes[6..14].copy_from_slice(&(read.len() as u64 + 1).to_le_bytes());
+        fs::write("./data/test_tablebase.bin", &bytes).unwrap();
+        assert!(Tablebase::read("./data/test_tablebase.bin").is_err());
+        let _ = fs::remove_file("./data/test_tablebase.bin");
+        assert_eq!(read.number_of_pieces(), 3);
+        assert_eq!(read.len(), tablebase.len());
+
+        // Perfect play from the tablebase never loses to the search
+        let mut solver_x = Solver::new();
+        solver_x.kind = PlayerKind::X;
+        let solver_o = Solver::new().with_tablebase(read);
+        let mut game = Game::new_with_size(3);
+        while !game.is_finished() {
+            let (i, j, k) = match game.current_player_kind() {
+                PlayerKind::X => solver_x.search_move(&game).0,
+                PlayerKind::O => solver_o.check_tablebase(&game).unwrap(),
+            };
+            game = game.make_move(i, j, k).unwrap();
+        }
+        assert_ne!(game.winner(), Some(Winner::X));
+    }
+
+    #[test]
+    fn optimal_moves_vary_with_the_seed() {
+        let path = "./data/test_seeded_lookup.json";
+        let tablebase = Tablebase::generate(3);
+        let mut game = Game::new_with_size(3);
+        game = game.make_move(0, 0, 1).unwrap();
+        game = game.make_move(1, 1, 2).unwrap();
+        game = game.make_move(2, 2, 0).unwrap();
+        let score = tablebase.get(&game).unwrap().score;
+
+        // Lookups from before every optimal move was kept still load
+        let old = format!(
+            "{{\"x\":[[{},[0,2,1]]]}}",
+            serde_json::to_string(&game).unwrap()
+        );
+        fs::write(path, old).unwrap();
+        let solver = Solver::new_distinct_lookup(path.to_string());
+        assert_eq!(solver.check_lookup(&game), Some((0, 2, 1)));
+
+        // The board is symmetrical about the diagonal, so either corner will do
+        let solver = Solver::new_distinct_lookup(path.to_string()).with_seed(0);
+        let corner = solver.check_lookup(&game).unwrap();
+        assert!(corner == (0, 2, 1) || corner == (2, 0, 1));
+        let _ = fs::remove_file(path);
+
+        let mut moves = HashSet::new();
+        for seed in 0..16 {
+            let solver = Solver::new_distinct_lookup(path.to_string()).with_seed(seed);
+            let (i, j, k) = solver.find_move(&game);
+            let child = game.clone().make_move(i, j, k).unwrap();
+            assert_eq!(tablebase.get(&child).unwrap().score, score);
+            assert_eq!(
+                Solver::new_distinct_lookup(path.to_string())
+                    .with_seed(seed)
+                    .find_move(&game),
+                (i, j, k)
+            );
+            moves.insert((i, j, k));
+        }
+        let _ = fs::remove_file(path);
+        assert!(moves.len() > 1);
+    }
+
+    #[test]
+    fn known_values() {
+        // Nobody can force a win with 3, 4 or 5 pieces
+        for number_of_pieces in 3..6 {
+            let game = Game::new_with_size(number_of_pieces);
+            let mut solver_x = Solver::new();
+            solver_x.kind = PlayerKind::X;
+            let (_, score) = solver_x.search_move(&game);
+            assert_eq!(score, 0);
+        }
+
+        assert_eq!(
+            Tablebase::generate(3).proof(),
+            Proof {
+                number_of_pieces: 3,
+                winner: Winner::Tie,
+                length: 6,
+            }
+        );
+    }
+
+    #[test]
+    fn state_space_counts() {
+        let counts = state_space::count(3);
+        let total = state_space::total(&counts);
+        assert_eq!(counts.len(), 7);
+        assert_eq!(total.canonical, 32686);
+        assert_eq!(total.positions, 259264);
+        assert_eq!(counts[1].positions, 27);
+        assert_eq!(counts[1].canonical, 9);
+        assert_eq!(counts[0].branching_factor(), 27.0);
+
+        // The raw counts match enumerating every position without symmetry
+        let mut layer: HashSet<u128> = [Game::new_with_size(3).key().unwrap()]
+            .iter()
+            .cloned()
+            .collect();
+        for count in counts.iter() {
+            assert_eq!(layer.len() as u64, count.positions);
+            let games = layer
+                .iter()
+                .map(|key| Game::from_key(*key))
+                .collect::<Vec<Game>>();
+            assert_eq!(
+                games
+                    .iter()
+                    .filter(|game| game.winner() == Some(Winner::X))
+                    .count() as u64,
+                count.x_wins
+            );
+            assert_eq!(
+                games
+                    .iter()
+                    .map(|game| game.legal_moves().len() as u64)
+                    .sum::<u64>(),
+                count.moves
+            );
+            layer = games
+                .iter()
+                .flat_map(|game| game.legal_moves())
+                .map(|(_, child)| child.key().unwrap())
+                .collect();
+        }
+        assert!(layer.is_empty());
+    }
+
+    fn check_perft(cheap_only: bool) {
+        let reference = fs::read_to_string("./data/perft.csv").unwrap();
+        for line in reference.lines().skip(1) {
+            let row = line
+                .split(',')
+                .map(|x| x.parse::<u64>().unwrap())
+                .collect::<Vec<u64>>();
+            let (pieces, depth) = (row[0] as usize, row[1] as usize);
+            if cheap_only && pieces > 3 && depth > 5 {
+                continue;
+            }
+
+            let expected = Perft {
+                nodes: row[2],
+                x_wins: row[3],
+                o_wins: row[4],
+                ties: row[5],
+                stuck: row[6],
+            };
+            assert_eq!(
+                Game::new_with_size(pieces).perft(depth),
+                expected,
+                "{} pieces to depth {}",
+                pieces,
+                depth
+            );
+        }
+    }
+
+    #[test]
+    fn perft() {
+        check_perft(true);
+    }
+
+    // Takes minutes, run with `cargo test --release -- --ignored`
+    #[test]
+    #[ignore]
+    fn known_values_full() {
+        for (number_of_pieces, length) in [(4, 8), (5, 10)] {
+            assert_eq!(
+                Tablebase::generate(number_of_pieces).proof(),
+                Proof {
+                    number_of_pieces,
+                    winner: Winner::Tie,
+                    length,
+                }
+            );
+        }
+    }
+
+    // Takes minutes, run with `cargo test --release -- --ignored`
+    #[test]
+    #[ignore]
+    fn perft_full() {
+        check_perft(false);
     }
 
     #[test]
@@ -300,9 +2274,9 @@ mod tests {
         game = game.make_move(2, 0, 3).unwrap(); // o
         game = game.make_move(0, 2, 3).unwrap(); // o
         assert_eq!(game.winner(), None);
-        assert_eq!(game.has_mirror_symmetry(Symmetry::FlipH), false);
-        assert_eq!(game.has_mirror_symmetry(Symmetry::FlipV), false);
-        assert_eq!(game.has_rotational_symmetry(), false);
+        assert!(!game.has_mirror_symmetry(Symmetry::FlipH));
+        assert!(!game.has_mirror_symmetry(Symmetry::FlipV));
+        assert!(!game.has_rotational_symmetry());
 
         // x x o    3 1 0
         // o _ x    2 _ 0
@@ -402,9 +2376,7 @@ mod tests {
 
     #[test]
     fn lookup_symmetries() {
-        match fs::remove_file("./data/test_lookup.json".to_string()) {
-            _ => (),
-        }
+        let _ = fs::remove_file("./data/test_lookup.json");
         let solver = Solver::new_distinct_lookup("./data/test_lookup.json".to_string());
 
         // Make first move in top-left corner
@@ -452,8 +2424,83 @@ mod tests {
         game = game.make_move(2, 1, 2).unwrap();
         let s = solver.check_lookup(&game);
         assert!(s.is_some());
-        match fs::remove_file("./data/test_lookup.json".to_string()) {
-            _ => (),
+        let _ = fs::remove_file("./data/test_lookup.json");
+    }
+
+    #[test]
+    fn moves_map_back_through_the_same_symmetry() {
+        // A piece on each square in turn, found again on the flipped board
+        // and mapped back. The reverse of a quarter turn is the other
+        // quarter turn, so mapping back through it misses every square but
+        // the centre, while every other symmetry is its own reverse.
+        for (symmetry, same) in Symmetry::iter().zip(Symmetry::iter()) {
+            let reverse = same.reverse();
+            let quarter_turn = matches!(symmetry, Symmetry::Rotate90 | Symmetry::Rotate270);
+            for (row, col) in (0..3).flat_map(|row| (0..3).map(move |col| (row, col))) {
+                let game = Game::new_with_size(3).make_move(row, col, 1).unwrap();
+                let flipped = game.fliptate(&symmetry);
+                let i = flipped.tiles().data().iter().position(Option::is_some);
+                let (x, y) = index_to_coordinates(i.unwrap());
+                assert_eq!(unfliptate_move((x, y, 1), &symmetry), (row, col, 1));
+                let old = unfliptate_move((x, y, 1), &reverse);
+                assert_eq!(old == (row, col, 1), !quarter_turn || (row, col) == (1, 1));
+            }
+        }
+    }
+
+    #[test]
+    fn lookup_rotations() {
+        let path = "./data/test_lookup_rotations.json";
+        let _ = fs::remove_file(path);
+
+        // x's only quick win is 1C. Rotated a quarter turn either way the
+        // winning square moves, and a move mapped back the wrong way
+        // doesn't win.
+        let mut game = Game::new_with_size(3);
+        for (i, j, k) in [(0, 0, 1), (2, 2, 0), (0, 1, 0), (1, 1, 1)] {
+            game = game.make_move(i, j, k).unwrap();
+        }
+        let mut solver = Solver::new_distinct_lookup(path.to_string());
+        solver.kind = PlayerKind::X;
+        assert_eq!(solver.find_move(&game), (0, 2, 2));
+
+        for symmetry in [Symmetry::Rotate90, Symmetry::Rotate270] {
+            let rotated = game.clone().fliptate(&symmetry);
+            let mut solver = Solver::new_distinct_lookup(path.to_string());
+            solver.kind = PlayerKind::X;
+            let (i, j, k) = solver.check_lookup(&rotated).unwrap();
+            assert_eq!(
+                rotated.make_move(i, j, k).unwrap().winner(),
+                Some(Winner::X)
+            );
+        }
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn lookup_repair() {
+        let path = "./data/test_lookup_repair.json";
+
+        // A lookup that has x throw away a win on the board
+        let mut game = Game::new_with_size(3);
+        for (i, j, k) in [(0, 0, 1), (2, 2, 0), (0, 1, 0), (1, 1, 1)] {
+            game = game.make_move(i, j, k).unwrap();
         }
+        let json = format!(
+            "{{\"x\":[[{},[2,0,2]]]}}",
+            serde_json::to_string(&game).unwrap()
+        );
+        fs::write(path, json).unwrap();
+
+        let mut solver = Solver::new_distinct_lookup(path.to_string());
+        assert_eq!(solver.check_lookup(&game), Some((2, 0, 2)));
+        assert_eq!(solver.repair_lookup(|_, _| ()), 1);
+        assert_eq!(solver.kind, PlayerKind::O);
+
+        let mut solver = Solver::new_distinct_lookup(path.to_string());
+        let (i, j, k) = solver.check_lookup(&game).unwrap();
+        assert_eq!(game.make_move(i, j, k).unwrap().winner(), Some(Winner::X));
+        assert_eq!(solver.repair_lookup(|_, _| ()), 0);
+        let _ = fs::remove_file(path);
     }
 }
@@ -0,0 +1,543 @@
+use crate::agent::{illegal, Agent};
+use crate::game::{Game, Move, PlayerKind, Winner, MAX_PIECES};
+use crate::net::Connection;
+use crate::record::GameRecord;
+use crate::solver::{Difficulty, Solver};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Bumped whenever `Command` or `Event` change in a way older clients can't
+/// follow
+pub const LOBBY_VERSION: u32 = 1;
+
+/// The most pieces a game against the solver can have, beyond which a
+/// perfect search could take hours
+pub const MAX_SOLVER_PIECES: usize = 6;
+
+/// Who a player wants to play
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Opponent {
+    /// The next person looking for a game with the same number of pieces
+    Human,
+    Solver {
+        difficulty: Difficulty,
+    },
+}
+
+/// Client to lobby, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    /// First thing on every connection
+    Hello {
+        version: u32,
+        name: String,
+    },
+    ListRooms,
+    /// Joins a room waiting for a second player, or opens one. Against the
+    /// solver the player is always x. Games have from 1 to
+    /// `game::MAX_PIECES` pieces, or up to `MAX_SOLVER_PIECES` against the
+    /// solver.
+    Play {
+        number_of_pieces: usize,
+        opponent: Opponent,
+    },
+    Spectate {
+        room: u64,
+    },
+    Move {
+        room: u64,
+        r#move: Move,
+    },
+}
+
+/// Lobby to client, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Welcome {
+        version: u32,
+    },
+    Rooms {
+        rooms: Vec<RoomInfo>,
+    },
+    /// You have a seat in a room, or are watching it, with every move so far
+    Joined {
+        room: u64,
+        role: Role,
+        number_of_pieces: usize,
+        moves: Vec<Move>,
+    },
+    /// Both seats are taken and x is to move
+    Started {
+        room: u64,
+        x: String,
+        o: String,
+    },
+    Moved {
+        room: u64,
+        player: PlayerKind,
+        r#move: Move,
+    },
+    /// A move of yours that wasn't played
+    Rejected {
+        room: u64,
+        reason: String,
+    },
+    /// Also sent when a player leaves part way or the solver plays a move
+    /// that isn't legal, the other player winning
+    GameOver {
+        room: u64,
+        winner: Winner,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    X,
+    O,
+    Spectator,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub room: u64,
+    pub number_of_pieces: usize,
+    pub x: String,
+    /// `None` while the room waits for a second player
+    pub o: Option<String>,
+    pub spectators: usize,
+    pub moves: usize,
+}
+
+/// Hosts any number of games at once. Players are paired with each other or
+/// with a solver, anyone can watch a room, and finished games can be written
+/// out in the game record format.
+pub struct Lobby {
+    state: Mutex<State>,
+    records: Option<PathBuf>,
+}
+
+#[derive(Default)]
+struct State {
+    clients: HashMap<u64, Client>,
+    rooms: BTreeMap<u64, Room>,
+    next_id: u64,
+}
+
+struct Client {
+    name: String,
+    events: Sender<Event>,
+}
+
+enum Seat {
+    Human(u64),
+    /// Named like a tournament agent, `solver:difficulty=easy`
+    Solver(Arc<Mutex<Solver>>, String),
+}
+
+struct Room {
+    game: Game,
+    moves: Vec<Move>,
+    x: Seat,
+    o: Option<Seat>,
+    spectators: Vec<u64>,
+}
+
+impl Default for Lobby {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lobby {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            records: None,
+        }
+    }
+
+    /// Appends the record of every finished game to `path`
+    pub fn with_records(mut self, path: PathBuf) -> Self {
+        self.records = Some(path);
+        self
+    }
+
+    /// Serves clients until the listener fails, each on its own thread
+    pub fn serve(self: Arc<Self>, listener: TcpListener) -> io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            let lobby = Arc::clone(&self);
+            thread::spawn(move || {
+                if let Ok(connection) = Connection::new(stream) {
+                    lobby.client(connection);
+                }
+            });
+        }
+    }
+
+    fn client(self: &Arc<Self>, mut connection: Connection) {
+        let name = match connection.receive::<Command>() {
+            Ok(Command::Hello { version, name }) if version == LOBBY_VERSION => name,
+            Ok(Command::Hello { version, .. }) => {
+                let _ = connection.send(&Event::Error {
+                    message: format!(
+                        "The lobby speaks version {}, not {}",
+                        LOBBY_VERSION, version
+                    ),
+                });
+                return;
+            }
+            _ => return,
+        };
+
+        // Events are written on their own thread, so that a slow client
+        // doesn't hold up everybody else
+        let (events, queue) = mpsc::channel::<Event>();
+        let mut writer = match connection.try_clone() {
+            Ok(writer) => writer,
+            Err(_) => return,
+        };
+        thread::spawn(move || {
+            for event in queue {
+                if writer.send(&event).is_err() {
+                    break;
+                }
+            }
+        });
+        let _ = events.send(Event::Welcome {
+            version: LOBBY_VERSION,
+        });
+
+        let id = {
+            let mut state = self.state.lock().unwrap();
+            let id = state.new_id();
+            state.clients.insert(id, Client { name, events });
+            id
+        };
+        while let Ok(command) = connection.receive::<Command>() {
+            self.handle(id, command);
+        }
+        self.leave(id);
+    }
+
+    fn handle(self: &Arc<Self>, client: u64, command: Command) {
+        // Reading the lookup takes a while, so it isn't done while everybody
+        // else waits for the lock
+        let solver = match &command {
+            Command::Play {
+                number_of_pieces,
+                opponent: Opponent::Solver { difficulty },
+            } if supported(*number_of_pieces, MAX_SOLVER_PIECES) => {
+                Some(Solver::new().with_difficulty(*difficulty))
+            }
+            _ => None,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        match command {
+            Command::Hello { .. } => state.send(client, error("Already said hello")),
+            Command::ListRooms => {
+                let rooms = state
+                    .rooms
+                    .iter()
+                    .map(|(id, room)| RoomInfo {
+                        room: *id,
+                        number_of_pieces: room.game.get_number_of_pieces(),
+                        x: state.seat_name(&room.x),
+                        o: room.o.as_ref().map(|o| state.seat_name(o)),
+                        spectators: room.spectators.len(),
+                        moves: room.moves.len(),
+                    })
+                    .collect();
+                state.send(client, Event::Rooms { rooms });
+            }
+            Command::Play {
+                number_of_pieces,
+                opponent: Opponent::Human,
+            } if !supported(number_of_pieces, MAX_PIECES) => state.send(
+                client,
+                error(&format!("Games have from 1 to {} pieces", MAX_PIECES)),
+            ),
+            Command::Play {
+                number_of_pieces,
+                opponent: Opponent::Human,
+            } => {
+                let waiting = state.rooms.iter().find_map(|(id, room)| match room.x {
+                    Seat::Human(x)
+                        if room.o.is_none()
+                            && x != client
+                            && room.game.get_number_of_pieces() == number_of_pieces =>
+                    {
+                        Some(*id)
+                    }
+                    _ => None,
+                });
+                match waiting {
+                    Some(id) => {
+                        state.rooms.get_mut(&id).unwrap().o = Some(Seat::Human(client));
+                        state.send(client, state.joined(id, Role::O));
+                        state.start(id);
+                    }
+                    None => {
+                        let id = state.open(number_of_pieces, Seat::Human(client), None);
+                        state.send(client, state.joined(id, Role::X));
+                    }
+                }
+            }
+            Command::Play {
+                opponent: Opponent::Solver { .. },
+                ..
+            } if solver.is_none() => state.send(
+                client,
+                error(&format!(
+                    "Games against the solver have from 1 to {} pieces",
+                    MAX_SOLVER_PIECES
+                )),
+            ),
+            Command::Play {
+                number_of_pieces,
+                opponent: Opponent::Solver { difficulty },
+            } => {
+                let solver = solver.unwrap();
+                let name = format!("solver:difficulty={:?}", difficulty).to_lowercase();
+                let id = state.open(
+                    number_of_pieces,
+                    Seat::Human(client),
+                    Some(Seat::Solver(Arc::new(Mutex::new(solver)), name)),
+                );
+                state.send(client, state.joined(id, Role::X));
+                state.start(id);
+            }
+            Command::Spectate { room } => match state.rooms.get_mut(&room) {
+                Some(r) => {
+                    r.spectators.push(client);
+                    state.send(client, state.joined(room, Role::Spectator));
+                }
+                None => state.send(client, error(&format!("There is no room {}", room))),
+            },
+            Command::Move { room, r#move } => {
+                let result = match state.rooms.get(&room) {
+                    Some(r) if r.o.is_none() => Err("The game hasn't started".to_string()),
+                    Some(r) => match r.seat(r.game.current_player_kind()) {
+                        Some(Seat::Human(seated)) if *seated == client => Ok(()),
+                        _ => Err("It isn't your turn".to_string()),
+                    },
+                    None => Err(format!("There is no room {}", room)),
+                };
+                match result.and_then(|_| state.play(room, r#move)) {
+                    Ok(()) => self.after_move(&mut state, room),
+                    Err(reason) => state.send(client, Event::Rejected { room, reason }),
+                }
+            }
+        }
+    }
+
+    // Finishes the game or lets the solver reply
+    fn after_move(self: &Arc<Self>, state: &mut State, room: u64) {
+        let r = &state.rooms[&room];
+        if r.game.is_over() {
+            let winner = r.game.winner().unwrap_or(Winner::Tie);
+            self.finish(state, room, winner);
+            return;
+        }
+        if let Some(Seat::Solver(solver, _)) = r.seat(r.game.current_player_kind()) {
+            let (lobby, solver, game) = (Arc::clone(self), Arc::clone(solver), r.game.clone());
+            thread::spawn(move || {
+                let b_move = solver.lock().unwrap().choose_move(&game);
+                let mut state = lobby.state.lock().unwrap();
+                // Only if nothing happened to the room in the meantime
+                if state.rooms.get(&room).map(|r| &r.game) != Some(&game) {
+                    return;
+                }
+                match state.play(room, b_move) {
+                    Ok(()) => lobby.after_move(&mut state, room),
+                    // The solver can't be asked again, so it forfeits
+                    // rather than leave the player waiting
+                    Err(e) => {
+                        let kind = game.current_player_kind();
+                        state.broadcast(room, error(&illegal(kind, b_move, &e)));
+                        let winner = match kind {
+                            PlayerKind::X => Winner::O,
+                            PlayerKind::O => Winner::X,
+                        };
+                        lobby.finish(&mut state, room, winner);
+                    }
+                }
+            });
+        }
+    }
+
+    fn finish(&self, state: &mut State, room: u64, winner: Winner) {
+        let r = &state.rooms[&room];
+        if let (Some(path), Some(o)) = (&self.records, &r.o) {
+            let mut record = GameRecord::new(
+                r.game.get_number_of_pieces(),
+                &state.seat_name(&r.x),
+                &state.seat_name(o),
+            );
+            record.moves = r.moves.clone();
+            record.winner = Some(winner);
+            let written = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", record));
+            if let Err(e) = written {
+                eprintln!("Couldn't write the record of room {}: {}", room, e);
+            }
+        }
+        state.broadcast(room, Event::GameOver { room, winner });
+        state.rooms.remove(&room);
+    }
+
+    // A player leaving part way through loses the game
+    fn leave(self: &Arc<Self>, client: u64) {
+        let mut state = self.state.lock().unwrap();
+        let rooms = state.rooms.keys().copied().collect::<Vec<u64>>();
+        for room in rooms {
+            let r = state.rooms.get_mut(&room).unwrap();
+            r.spectators.retain(|spectator| *spectator != client);
+            let left = match (&r.x, &r.o) {
+                (Seat::Human(x), _) if *x == client => Some(Winner::O),
+                (_, Some(Seat::Human(o))) if *o == client => Some(Winner::X),
+                _ => None,
+            };
+            match (left, &r.o) {
+                (Some(_), None) => {
+                    state.rooms.remove(&room);
+                }
+                (Some(winner), Some(_)) => self.finish(&mut state, room, winner),
+                _ => (),
+            }
+        }
+        state.clients.remove(&client);
+    }
+}
+
+impl State {
+    fn new_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn open(&mut self, number_of_pieces: usize, x: Seat, o: Option<Seat>) -> u64 {
+        let id = self.new_id();
+        self.rooms.insert(
+            id,
+            Room {
+                game: Game::new_with_size(number_of_pieces),
+                moves: Vec::new(),
+                x,
+                o,
+                spectators: Vec::new(),
+            },
+        );
+        id
+    }
+
+    fn start(&self, room: u64) {
+        let r = &self.rooms[&room];
+        if let Some(o) = &r.o {
+            let started = Event::Started {
+                room,
+                x: self.seat_name(&r.x),
+                o: self.seat_name(o),
+            };
+            self.broadcast(room, started);
+        }
+    }
+
+    fn play(&mut self, room: u64, (row, col, size): Move) -> Result<(), String> {
+        let r = self.rooms.get_mut(&room).unwrap();
+        let player = r.game.current_player_kind();
+        r.game = r
+            .game
+            .clone()
+            .make_move(row, col, size)
+            .map_err(|e| e.to_string())?;
+        r.moves.push((row, col, size));
+        self.broadcast(
+            room,
+            Event::Moved {
+                room,
+                player,
+                r#move: (row, col, size),
+            },
+        );
+        Ok(())
+    }
+
+    fn joined(&self, room: u64, role: Role) -> Event {
+        let r = &self.rooms[&room];
+        Event::Joined {
+            room,
+            role,
+            number_of_pieces: r.game.get_number_of_pieces(),
+            moves: r.moves.clone(),
+        }
+    }
+
+    fn seat_name(&self, seat: &Seat) -> String {
+        match seat {
+            Seat::Human(client) => self
+                .clients
+                .get(client)
+                .map(|client| client.name.clone())
+                .unwrap_or_default(),
+            Seat::Solver(_, name) => name.clone(),
+        }
+    }
+
+    // Both players and every spectator
+    fn broadcast(&self, room: u64, event: Event) {
+        let r = &self.rooms[&room];
+        let seats = [Some(&r.x), r.o.as_ref()];
+        let players = seats.iter().flatten().filter_map(|seat| match seat {
+            Seat::Human(client) => Some(*client),
+            Seat::Solver(..) => None,
+        });
+        for client in players.chain(r.spectators.iter().copied()) {
+            self.send(client, event.clone());
+        }
+    }
+
+    fn send(&self, client: u64, event: Event) {
+        if let Some(client) = self.clients.get(&client) {
+            // A client that has gone is cleaned up by its own thread
+            let _ = client.events.send(event);
+        }
+    }
+}
+
+impl Room {
+    fn seat(&self, kind: PlayerKind) -> Option<&Seat> {
+        match kind {
+            PlayerKind::X => Some(&self.x),
+            PlayerKind::O => self.o.as_ref(),
+        }
+    }
+}
+
+fn supported(number_of_pieces: usize, max: usize) -> bool {
+    (1..=max).contains(&number_of_pieces)
+}
+
+fn error(message: &str) -> Event {
+    Event::Error {
+        message: message.to_string(),
+    }
+}
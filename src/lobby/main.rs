@@ -0,0 +1,59 @@
+extern crate getopts;
+use getopts::Options;
+use matryoshka_tic_tac_toe::lobby::Lobby;
+use std::env;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+fn print_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} [options]", program);
+    println!("{}", opts.usage(&brief));
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+    let mut address = "127.0.0.1:7878".to_string();
+    let mut lobby = Lobby::new();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "b",
+        "bind",
+        "Address to listen on, defaults to 127.0.0.1:7878",
+        "ADDRESS",
+    );
+    opts.optopt(
+        "r",
+        "records",
+        "Append the record of every finished game",
+        "FILE",
+    );
+
+    opts.optflag("h", "help", "print this help menu");
+    if let Ok(m) = opts.parse(&args[1..]) {
+        if m.opt_present("h") {
+            print_usage(&program, opts);
+            return;
+        }
+        if let Some(b) = m.opt_str("b") {
+            address = b;
+        }
+        if let Some(path) = m.opt_str("r") {
+            lobby = lobby.with_records(PathBuf::from(path));
+        }
+    };
+
+    let listener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Couldn't listen on {}: {}", address, e);
+            return;
+        }
+    };
+    println!("Lobby open on {}", address);
+    if let Err(e) = Arc::new(lobby).serve(listener) {
+        eprintln!("{}", e);
+    }
+}
@@ -1,7 +1,7 @@
 extern crate getopts;
 use getopts::Options;
-use matryoshka_tic_tac_toe::game::Game;
-use matryoshka_tic_tac_toe::solver::Solver;
+use matryoshka_tic_tac_toe::game::{Game, MAX_PIECES};
+use matryoshka_tic_tac_toe::solver::{Algorithm, MoveOrdering, Solver, Tablebase};
 use std::env;
 use std::time::SystemTime;
 
@@ -19,7 +19,6 @@ fn print_usage(program: &str, opts: Options) {
 }
 
 fn main() {
-    let solver = Solver::new_overwrite_lookup();
     let mut progress = Progress {
         number_of_pieces: 0,
         row: 0,
@@ -28,6 +27,10 @@ fn main() {
     };
     let mut until: usize = 7;
     let mut deep = false;
+    let mut retrograde = false;
+    let mut repair = false;
+    let mut algorithm = Algorithm::YoungBrothersWait;
+    let mut ordering = MoveOrdering::default();
 
     let args: Vec<String> = env::args().collect();
     let program = args[0].clone();
@@ -41,27 +44,88 @@ fn main() {
     );
 
     opts.optflag("d", "deep", "Run the generator past the top-level moves");
+    opts.optflag(
+        "r",
+        "retrograde",
+        "Solve every reachable position into data/tablebase-PIECES.bin instead",
+    );
+    opts.optflag(
+        "",
+        "repair",
+        "Search every position in data/lookup.json again and replace entries that aren't optimal",
+    );
+    opts.optopt(
+        "a",
+        "algorithm",
+        "Search algorithm: ybwc (default), parallel or pns for proof-number search",
+        "ALGORITHM",
+    );
+    opts.optopt(
+        "o",
+        "ordering",
+        "Move ordering heuristics: all, none or a list of wins,blocks,captures,killers,history,largest",
+        "HEURISTICS",
+    );
 
     opts.optflag("h", "help", "print this help menu");
-    match opts.parse(&args[1..]) {
-        Ok(m) => {
-            if m.opt_present("h") {
-                print_usage(&program, opts);
+    if let Ok(m) = opts.parse(&args[1..]) {
+        if m.opt_present("h") {
+            print_usage(&program, opts);
+            return;
+        }
+        if let Ok(Some(u)) = m.opt_get::<usize>("u") {
+            until = u;
+        }
+        if m.opt_present("d") {
+            deep = true;
+        }
+        if m.opt_present("r") {
+            retrograde = true;
+        }
+        if m.opt_present("repair") {
+            repair = true;
+        }
+        match m.opt_get::<Algorithm>("a") {
+            Ok(Some(a)) => algorithm = a,
+            Err(e) => {
+                eprintln!("{}", e);
                 return;
             }
-            if m.opt_present("u") {
-                match m.opt_get::<usize>("u") {
-                    Ok(Some(u)) => until = u,
-                    _ => (),
-                }
-            }
-            if m.opt_present("d") {
-                deep = true;
+            _ => (),
+        }
+        match m.opt_get::<MoveOrdering>("o") {
+            Ok(Some(o)) => ordering = o,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
             }
+            _ => (),
         }
-        _ => (),
     };
 
+    if retrograde {
+        if until > MAX_PIECES {
+            eprintln!("At most {} pieces fit in a position key", MAX_PIECES);
+            return;
+        }
+        for number_of_pieces in 3..until + 1 {
+            generate_tablebase(number_of_pieces);
+        }
+        return;
+    }
+
+    let mut solver = Solver::new_overwrite_lookup()
+        .with_algorithm(algorithm)
+        .with_ordering(ordering);
+
+    if repair {
+        let replaced = solver.repair_lookup(|checked, positions| {
+            println! {"{}/{} positions", checked, positions};
+        });
+        println! {"Replaced {} entries", replaced};
+        return;
+    }
+
     for number_of_pieces in 3..until + 1 {
         for row in 0..3 {
             for col in 0..3 {
@@ -73,6 +137,7 @@ fn main() {
                     println! {"{:?}", progress};
 
                     let now = SystemTime::now();
+                    solver.reset_nodes();
                     let mut game = Game::new_with_size(number_of_pieces);
                     game = game.make_move(row, col, piece).unwrap();
                     let (i, j, k) = solver.find_move(&game);
@@ -81,13 +146,24 @@ fn main() {
                         do_next_lookup(&mut game, &solver, &number_of_pieces);
                     }
 
-                    println! {"{}", now.elapsed().unwrap().as_secs()};
+                    println! {"{} {} nodes", now.elapsed().unwrap().as_secs(), solver.nodes()};
                 }
             }
         }
     }
 }
 
+fn generate_tablebase(number_of_pieces: usize) {
+    let now = SystemTime::now();
+    let tablebase = Tablebase::generate_with_progress(number_of_pieces, |ply, positions| {
+        println! {"{} pieces, ply {}: {} positions", number_of_pieces, ply, positions};
+    });
+
+    let path = format!("data/tablebase-{}.bin", number_of_pieces);
+    tablebase.write(&path).unwrap();
+    println! {"{}: {} positions in {}s", path, tablebase.len(), now.elapsed().unwrap().as_secs()};
+}
+
 fn do_next_lookup(game: &mut Game, solver: &Solver, number_of_pieces: &usize) {
     for row in 0..3 {
         for col in 0..3 {
@@ -95,26 +171,17 @@ fn do_next_lookup(game: &mut Game, solver: &Solver, number_of_pieces: &usize) {
                 if game.get_turn_count() > 4 {
                     return;
                 }
-                let new_game = game.clone().make_move(row, col, piece);
-                match new_game {
-                    Ok(_) => {
-                        let mut game = new_game.unwrap();
+                if let Ok(mut game) = game.clone().make_move(row, col, piece) {
+                    if game.is_finished() {
+                        return;
+                    }
+                    let (i, j, k) = solver.find_move(&game);
+                    if let Ok(new_game) = game.clone().make_move(i, j, k) {
+                        game = new_game;
                         if game.is_finished() {
                             return;
                         }
-                        let (i, j, k) = solver.find_move(&game);
-                        let new_game = game.clone().make_move(i, j, k);
-                        match new_game {
-                            Ok(_) => {
-                                game = game.make_move(i, j, k).unwrap();
-                                if game.is_finished() {
-                                    return;
-                                }
-                            }
-                            _ => (),
-                        }
                     }
-                    _ => (),
                 }
             }
         }
@@ -0,0 +1,318 @@
+use crate::game::{Game, Move, PlayerKind, Winner};
+use crate::solver::threats;
+use crate::symmetry::coordinates_to_index;
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// How long a search runs for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    /// Playouts in total, shared out between the threads
+    Playouts(usize),
+    /// Every thread keeps playing out games until the time is up
+    Time(Duration),
+}
+
+/// How a game is finished off from a new leaf of the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rollout {
+    /// Uniformly random legal moves
+    Random,
+    /// Take a win if there is one, otherwise block the opponent's, otherwise
+    /// play a random legal move
+    Heuristic,
+}
+
+impl FromStr for Rollout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Rollout::Random),
+            "heuristic" => Ok(Rollout::Heuristic),
+            invalid => Err(format!(
+                "Unknown rollout '{}', expected random or heuristic",
+                invalid
+            )),
+        }
+    }
+}
+
+/// What the search found out about one move from the root
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveStats {
+    pub b_move: Move,
+    pub visits: u32,
+    /// Average result of the playouts through this move for the player
+    /// making it, counting a win as 1 and a tie as a half
+    pub win_rate: f64,
+}
+
+/// Monte Carlo Tree Search with UCT. Unlike `Solver` it never searches the
+/// whole tree, so it plays games of any number of pieces at the same speed,
+/// at the cost of only ever playing well rather than perfectly.
+///
+/// Only the number of pieces grows, the board is always 3x3: `Tiles` has
+/// nine squares and the win lines, symmetries and threats are all written
+/// for them.
+///
+/// Each rayon thread grows its own tree from the root and the visits to the
+/// root's moves are summed at the end.
+#[derive(Debug, Clone)]
+pub struct Mcts {
+    budget: Budget,
+    rollout: Rollout,
+    exploration: f64,
+    seed: Option<u64>,
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mcts {
+    pub fn new() -> Self {
+        Self {
+            budget: Budget::Time(Duration::from_secs(1)),
+            rollout: Rollout::Heuristic,
+            exploration: std::f64::consts::SQRT_2,
+            seed: None,
+        }
+    }
+
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    pub fn with_rollout(mut self, rollout: Rollout) -> Self {
+        self.rollout = rollout;
+        self
+    }
+
+    /// The UCT exploration constant, larger values try unpromising moves more
+    pub fn with_exploration(mut self, exploration: f64) -> Self {
+        self.exploration = exploration;
+        self
+    }
+
+    /// Makes a search with a playout budget repeatable on the same number of
+    /// threads
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// The most visited move for whoever's turn it is
+    pub fn find_move(&self, game: &Game) -> (usize, usize, usize) {
+        self.search(game)
+            .first()
+            .map(|stats| stats.b_move)
+            .unwrap_or((0, 0, 0))
+    }
+
+    /// Every legal move with its visits and win rate, most visited first
+    pub fn search(&self, game: &Game) -> Vec<MoveStats> {
+        let threads = rayon::current_num_threads();
+        let deadline = match self.budget {
+            Budget::Time(time) => Some(Instant::now() + time),
+            Budget::Playouts(_) => None,
+        };
+
+        let trees = (0..threads)
+            .into_par_iter()
+            .map(|thread| {
+                let mut rng = match self.seed {
+                    Some(seed) => SmallRng::seed_from_u64(seed.wrapping_add(thread as u64)),
+                    None => SmallRng::from_entropy(),
+                };
+                let playouts = match self.budget {
+                    Budget::Playouts(playouts) => {
+                        Some(playouts / threads + usize::from(thread < playouts % threads))
+                    }
+                    Budget::Time(_) => None,
+                };
+                self.grow(game, playouts, deadline, &mut rng)
+            })
+            .collect::<Vec<Tree>>();
+
+        let mut stats: Vec<MoveStats> = Vec::new();
+        for tree in trees.iter() {
+            for child in tree.nodes[0].children.iter() {
+                let node = &tree.nodes[*child];
+                match stats.iter_mut().find(|stats| stats.b_move == node.b_move) {
+                    Some(stats) => {
+                        stats.win_rate += node.reward;
+                        stats.visits += node.visits;
+                    }
+                    None => stats.push(MoveStats {
+                        b_move: node.b_move,
+                        visits: node.visits,
+                        win_rate: node.reward,
+                    }),
+                }
+            }
+        }
+        for stats in stats.iter_mut() {
+            stats.win_rate /= stats.visits.max(1) as f64;
+        }
+        stats.sort_by_key(|stats| std::cmp::Reverse((stats.visits, stats.b_move)));
+        stats
+    }
+
+    fn grow(
+        &self,
+        game: &Game,
+        playouts: Option<usize>,
+        deadline: Option<Instant>,
+        rng: &mut SmallRng,
+    ) -> Tree {
+        let mut tree = Tree {
+            nodes: vec![Node::new((0, 0, 0), game.clone(), None)],
+        };
+
+        let mut played = 0;
+        loop {
+            match (playouts, deadline) {
+                (Some(playouts), _) if played >= playouts => break,
+                (_, Some(deadline)) if played > 0 && Instant::now() >= deadline => break,
+                _ => (),
+            }
+            played += 1;
+
+            let mut index = 0;
+            while tree.nodes[index].untried.is_empty() && !tree.nodes[index].children.is_empty() {
+                index = self.select(&tree, index);
+            }
+
+            if !tree.nodes[index].untried.is_empty() {
+                let untried = &mut tree.nodes[index].untried;
+                let (b_move, child) = untried.swap_remove(rng.gen_range(0..untried.len()));
+                tree.nodes.push(Node::new(b_move, child, Some(index)));
+                let child = tree.nodes.len() - 1;
+                tree.nodes[index].children.push(child);
+                index = child;
+            }
+
+            let winner = self.playout(&tree.nodes[index].game, rng);
+
+            let mut next = Some(index);
+            while let Some(index) = next {
+                let node = &mut tree.nodes[index];
+                node.visits += 1;
+                node.reward += reward(winner, node.mover);
+                next = node.parent;
+            }
+        }
+        tree
+    }
+
+    // The child with the best upper confidence bound
+    fn select(&self, tree: &Tree, index: usize) -> usize {
+        let log_visits = (tree.nodes[index].visits as f64).ln();
+        let uct = |child: &usize| {
+            let node = &tree.nodes[*child];
+            let visits = node.visits as f64;
+            node.reward / visits + self.exploration * (log_visits / visits).sqrt()
+        };
+        *tree.nodes[index]
+            .children
+            .iter()
+            .max_by(|a, b| uct(a).partial_cmp(&uct(b)).unwrap())
+            .unwrap()
+    }
+
+    // Plays the game out to the end. A player that can't move is stuck, which
+    // is scored as a tie.
+    fn playout(&self, game: &Game, rng: &mut SmallRng) -> Option<Winner> {
+        let mut game = game.clone();
+        while !game.is_finished() {
+            let moves = game.legal_moves();
+            let next = match self.rollout {
+                Rollout::Random => moves.choose(rng),
+                Rollout::Heuristic => rollout_move(&game, &moves, rng),
+            };
+            match next {
+                Some((_, child)) => game = child.clone(),
+                None => return None,
+            }
+        }
+        game.winner()
+    }
+}
+
+fn rollout_move<'a>(
+    game: &Game,
+    moves: &'a [(Move, Game)],
+    rng: &mut SmallRng,
+) -> Option<&'a (Move, Game)> {
+    let kind = game.current_player_kind();
+    if let Some(win) = moves
+        .iter()
+        .find(|(_, child)| reward(child.winner(), kind) == 1.0)
+    {
+        return Some(win);
+    }
+
+    let their_threats = threats(game, opponent(kind));
+    let blocks = moves
+        .iter()
+        .filter(|((i, j, _), _)| their_threats[coordinates_to_index((*i, *j))])
+        .collect::<Vec<&(Move, Game)>>();
+    match blocks.choose(rng) {
+        Some(block) => Some(*block),
+        None => moves.choose(rng),
+    }
+}
+
+fn opponent(kind: PlayerKind) -> PlayerKind {
+    match kind {
+        PlayerKind::X => PlayerKind::O,
+        PlayerKind::O => PlayerKind::X,
+    }
+}
+
+fn reward(winner: Option<Winner>, kind: PlayerKind) -> f64 {
+    match (winner, kind) {
+        (Some(Winner::X), PlayerKind::X) | (Some(Winner::O), PlayerKind::O) => 1.0,
+        (Some(Winner::X), PlayerKind::O) | (Some(Winner::O), PlayerKind::X) => 0.0,
+        _ => 0.5,
+    }
+}
+
+struct Tree {
+    nodes: Vec<Node>,
+}
+
+struct Node {
+    b_move: Move,
+    game: Game,
+    /// Whoever made `b_move`, the results are counted for them
+    mover: PlayerKind,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<(Move, Game)>,
+    visits: u32,
+    reward: f64,
+}
+
+impl Node {
+    fn new(b_move: Move, game: Game, parent: Option<usize>) -> Self {
+        Self {
+            b_move,
+            mover: opponent(game.current_player_kind()),
+            untried: game.legal_moves(),
+            game,
+            parent,
+            children: Vec::new(),
+            visits: 0,
+            reward: 0.0,
+        }
+    }
+}
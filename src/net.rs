@@ -0,0 +1,403 @@
+use crate::agent::{illegal, Agent};
+use crate::game::{Game, Move, PlayerKind, Winner, MAX_PIECES};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use socket2::{SockRef, TcpKeepalive};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+/// Bumped whenever `Message` changes in a way older players can't follow
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How often a host waiting on its client looks for a newer connection
+const POLL: Duration = Duration::from_millis(200);
+
+/// How long a connection may be quiet before keepalive probes are sent, and
+/// how long between them, so that one that dropped without a word errors
+/// instead of being waited on forever
+const KEEPALIVE_TIME: Duration = Duration::from_secs(10);
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Everything sent between the host and the client, one JSON object per
+/// line. The host plays x and has the only copy of the game that counts: the
+/// client proposes its moves and only plays them once the host sends them
+/// back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// Client to host, first thing on every connection
+    Hello {
+        version: u32,
+    },
+    /// Host to client, answering `Hello` with every move so far, which is
+    /// how a client that lost its connection catches up
+    Welcome {
+        version: u32,
+        number_of_pieces: usize,
+        moves: Vec<Move>,
+    },
+    /// From the client, a move it would like to play. From the host, a move
+    /// that has been played by either side.
+    Move {
+        r#move: Move,
+    },
+    /// Host to client, a proposed move that wasn't legal
+    Rejected {
+        reason: String,
+    },
+    GameOver {
+        winner: Winner,
+    },
+    /// Sent before closing a connection that can't go on, such as for a
+    /// different protocol version
+    Error {
+        message: String,
+    },
+}
+
+/// A connection carrying one JSON message per line, with TCP keepalive on
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    /// Whatever was read of a line before a read timed out
+    partial: Vec<u8>,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        let keepalive = TcpKeepalive::new()
+            .with_time(KEEPALIVE_TIME)
+            .with_interval(KEEPALIVE_INTERVAL);
+        SockRef::from(&stream).set_tcp_keepalive(&keepalive)?;
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            partial: Vec::new(),
+        })
+    }
+
+    /// How long `receive` waits before failing with `WouldBlock` or
+    /// `TimedOut`, see `is_timeout`. A message cut off by the timeout is
+    /// finished by the next `receive`. `None` waits for ever.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.writer.set_read_timeout(timeout)
+    }
+
+    /// Another handle on the same connection, such as for writing from a
+    /// different thread than the one reading
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Self::new(self.writer.try_clone()?)
+    }
+
+    pub fn send<T: Serialize>(&mut self, message: &T) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, message)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+
+    pub fn receive<T: DeserializeOwned>(&mut self) -> io::Result<T> {
+        if self.reader.read_until(b'\n', &mut self.partial)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The connection was closed",
+            ));
+        }
+        let line = std::mem::take(&mut self.partial);
+        serde_json::from_slice(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Whether `receive` gave up waiting, rather than the connection failing
+pub fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// Plays x against whoever connects, waiting for them to come back if their
+/// connection drops. A newer connection takes over from the current one
+/// while the host waits for o's move, so a client that reconnects is taken
+/// up even if its old connection went quiet without closing.
+pub struct Host {
+    listener: TcpListener,
+    game: Game,
+    moves: Vec<Move>,
+    hello_timeout: Duration,
+}
+
+impl Host {
+    pub fn new(listener: TcpListener, number_of_pieces: usize) -> Self {
+        Self {
+            listener,
+            game: Game::new_with_size(number_of_pieces),
+            moves: Vec::new(),
+            hello_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// How long a new connection has to say hello before it is dropped
+    pub fn with_hello_timeout(mut self, hello_timeout: Duration) -> Self {
+        self.hello_timeout = hello_timeout;
+        self
+    }
+
+    /// Plays the game out, `local` choosing the host's moves. `on_position`
+    /// is called before every move, like `agent::play_match`.
+    pub fn play<F>(mut self, local: &mut dyn Agent, mut on_position: F) -> io::Result<Game>
+    where
+        F: FnMut(&Game),
+    {
+        let mut connection = self.accept()?;
+        while !self.game.is_over() {
+            on_position(&self.game);
+            let (row, col, size) = match self.game.current_player_kind() {
+                PlayerKind::X => local.choose_move(&self.game),
+                PlayerKind::O => loop {
+                    match connection.receive() {
+                        Ok(Message::Move { r#move }) => break r#move,
+                        Ok(Message::Error { message }) => return Err(io::Error::other(message)),
+                        Ok(_) => (),
+                        Err(e) if is_timeout(&e) => {
+                            if let Some(newer) = self.newer()? {
+                                connection = newer;
+                            }
+                        }
+                        Err(_) => connection = self.accept()?,
+                    }
+                },
+            };
+
+            match self.game.clone().make_move(row, col, size) {
+                Ok(next) => {
+                    self.game = next;
+                    self.moves.push((row, col, size));
+                    let played = Message::Move {
+                        r#move: (row, col, size),
+                    };
+                    // Whoever reconnects is told about it in the welcome
+                    if connection.send(&played).is_err() {
+                        connection = self.accept()?;
+                    }
+                }
+                Err(e) if self.game.current_player_kind() == PlayerKind::O => {
+                    let rejected = Message::Rejected {
+                        reason: e.to_string(),
+                    };
+                    if connection.send(&rejected).is_err() {
+                        connection = self.accept()?;
+                    }
+                }
+                // A local move that isn't legal is asked for again, unless
+                // an engine played it
+                Err(_) if local.asks_again() => (),
+                Err(e) => {
+                    let message = illegal(PlayerKind::X, (row, col, size), e);
+                    let _ = connection.send(&Message::Error {
+                        message: message.clone(),
+                    });
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+                }
+            }
+        }
+
+        let winner = self.game.winner().unwrap_or(Winner::Tie);
+        // The game is over either way, so a client that has gone misses out
+        let _ = connection.send(&Message::GameOver { winner });
+        Ok(self.game)
+    }
+
+    // Waits for a client with the same protocol version and brings it up to
+    // date
+    fn accept(&self) -> io::Result<Connection> {
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            if let Some(connection) = self.welcome(stream) {
+                return Ok(connection);
+            }
+        }
+    }
+
+    // A client that has connected since, if any, without waiting for one
+    fn newer(&self) -> io::Result<Option<Connection>> {
+        self.listener.set_nonblocking(true)?;
+        let accepted = self.listener.accept();
+        self.listener.set_nonblocking(false)?;
+        match accepted {
+            Ok((stream, _)) => Ok(self.welcome(stream)),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Answers a new connection's hello, or turns it away if it speaks
+    // another protocol version or says nothing in time
+    fn welcome(&self, stream: TcpStream) -> Option<Connection> {
+        stream.set_nonblocking(false).ok()?;
+        let mut connection = Connection::new(stream).ok()?;
+        connection.set_read_timeout(Some(self.hello_timeout)).ok()?;
+        match connection.receive() {
+            Ok(Message::Hello { version }) if version == PROTOCOL_VERSION => {
+                let welcome = Message::Welcome {
+                    version: PROTOCOL_VERSION,
+                    number_of_pieces: self.game.get_number_of_pieces(),
+                    moves: self.moves.clone(),
+                };
+                connection.send(&welcome).ok()?;
+                connection.set_read_timeout(Some(POLL)).ok()?;
+                Some(connection)
+            }
+            Ok(Message::Hello { version }) => {
+                let _ = connection.send(&Message::Error {
+                    message: format!(
+                        "The host speaks protocol version {}, not {}",
+                        PROTOCOL_VERSION, version
+                    ),
+                });
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Plays o against a host, reconnecting if the connection drops
+pub struct Client<A: ToSocketAddrs> {
+    address: A,
+    retries: usize,
+    retry_delay: Duration,
+    welcome_timeout: Duration,
+}
+
+impl<A: ToSocketAddrs> Client<A> {
+    pub fn new(address: A) -> Self {
+        Self {
+            address,
+            retries: 10,
+            retry_delay: Duration::from_secs(1),
+            welcome_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// How many times to try connecting again after losing the host, and how
+    /// long to wait between tries
+    pub fn with_retries(mut self, retries: usize, retry_delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// How long to wait for the host to answer hello before trying again.
+    /// Once the game is under way the host may take as long as it likes over
+    /// its moves, and only keepalive notices a host that has gone.
+    pub fn with_welcome_timeout(mut self, welcome_timeout: Duration) -> Self {
+        self.welcome_timeout = welcome_timeout;
+        self
+    }
+
+    /// Plays the game out, `local` choosing the client's moves. `on_position`
+    /// is called before every move, like `agent::play_match`.
+    pub fn play<F>(&self, local: &mut dyn Agent, mut on_position: F) -> io::Result<Game>
+    where
+        F: FnMut(&Game),
+    {
+        let (mut connection, mut game) = self.connect()?;
+        loop {
+            if !game.is_over() {
+                on_position(&game);
+                if game.current_player_kind() == PlayerKind::O {
+                    let proposed = Message::Move {
+                        r#move: local.choose_move(&game),
+                    };
+                    if connection.send(&proposed).is_err() {
+                        (connection, game) = self.connect()?;
+                        continue;
+                    }
+                }
+            }
+
+            // Wait for the host to play a move, either side's
+            match connection.receive() {
+                Ok(Message::Move {
+                    r#move: (row, col, size),
+                }) => {
+                    game = game
+                        .make_move(row, col, size)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                }
+                Ok(Message::GameOver { .. }) => return Ok(game),
+                Ok(Message::Error { message }) => return Err(io::Error::other(message)),
+                // A rejected move is asked for again, unless an engine
+                // played it
+                Ok(Message::Rejected { reason }) if !local.asks_again() => {
+                    let _ = connection.send(&Message::Error {
+                        message: reason.clone(),
+                    });
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, reason));
+                }
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => return Err(e),
+                // The host may have finished before telling us
+                Err(_) if game.is_over() => return Ok(game),
+                Err(_) => (connection, game) = self.connect()?,
+            }
+        }
+    }
+
+    // Connects, retrying if the host can't be reached, and rebuilds the game
+    // from the moves the host has seen
+    fn connect(&self) -> io::Result<(Connection, Game)> {
+        let mut attempts = 0;
+        loop {
+            match self.handshake() {
+                Ok(connected) => return Ok(connected),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData || attempts >= self.retries => {
+                    return Err(e)
+                }
+                Err(_) => {
+                    attempts += 1;
+                    thread::sleep(self.retry_delay);
+                }
+            }
+        }
+    }
+
+    fn handshake(&self) -> io::Result<(Connection, Game)> {
+        let mut connection = Connection::new(TcpStream::connect(&self.address)?)?;
+        connection.set_read_timeout(Some(self.welcome_timeout))?;
+        connection.send(&Message::Hello {
+            version: PROTOCOL_VERSION,
+        })?;
+        let welcome = connection.receive()?;
+        connection.set_read_timeout(None)?;
+        match welcome {
+            Message::Welcome {
+                number_of_pieces, ..
+            } if !(1..=MAX_PIECES).contains(&number_of_pieces) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Games have from 1 to {} pieces", MAX_PIECES),
+            )),
+            Message::Welcome {
+                number_of_pieces,
+                moves,
+                ..
+            } => {
+                let game = moves.iter().try_fold(
+                    Game::new_with_size(number_of_pieces),
+                    |game, (row, col, size)| {
+                        game.make_move(*row, *col, *size)
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+                    },
+                )?;
+                Ok((connection, game))
+            }
+            Message::Error { message } => Err(io::Error::new(io::ErrorKind::InvalidData, message)),
+            message => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Expected a welcome, not {:?}", message),
+            )),
+        }
+    }
+}
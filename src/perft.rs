@@ -0,0 +1,80 @@
+use crate::game::{Game, Winner};
+use rayon::prelude::*;
+use std::ops::Add;
+
+/// Move sequences counted by `Game::perft`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Perft {
+    /// Sequences of exactly `depth` moves that leave the game unfinished
+    pub nodes: u64,
+    /// Sequences of at most `depth` moves that end with each result
+    pub x_wins: u64,
+    pub o_wins: u64,
+    pub ties: u64,
+    /// Sequences of fewer than `depth` moves after which the player to move
+    /// has no legal move
+    pub stuck: u64,
+}
+
+impl Add for Perft {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            nodes: self.nodes + other.nodes,
+            x_wins: self.x_wins + other.x_wins,
+            o_wins: self.o_wins + other.o_wins,
+            ties: self.ties + other.ties,
+            stuck: self.stuck + other.stuck,
+        }
+    }
+}
+
+impl Game {
+    /// Walks every sequence of legal moves up to `depth` moves long, counting
+    /// how they end. Symmetrical moves are not merged, so the counts check
+    /// `make_move` and the winner detection rather than the solver.
+    pub fn perft(&self, depth: usize) -> Perft {
+        match self.winner() {
+            Some(Winner::X) => {
+                return Perft {
+                    x_wins: 1,
+                    ..Perft::default()
+                }
+            }
+            Some(Winner::O) => {
+                return Perft {
+                    o_wins: 1,
+                    ..Perft::default()
+                }
+            }
+            Some(Winner::Tie) => {
+                return Perft {
+                    ties: 1,
+                    ..Perft::default()
+                }
+            }
+            None => (),
+        }
+
+        if depth == 0 {
+            return Perft {
+                nodes: 1,
+                ..Perft::default()
+            };
+        }
+
+        let moves = self.legal_moves();
+        if moves.is_empty() {
+            return Perft {
+                stuck: 1,
+                ..Perft::default()
+            };
+        }
+
+        moves
+            .par_iter()
+            .map(|(_, child)| child.perft(depth - 1))
+            .reduce(Perft::default, Perft::add)
+    }
+}
@@ -0,0 +1,128 @@
+use crate::game::{Game, PlayerKind};
+
+/// The colours every `Raster` is drawn in, as RGB triples in palette order
+pub const PALETTE: [u8; 15] = [
+    251, 248, 241, // paper
+    48, 52, 63, // ink
+    209, 73, 91, // x
+    0, 121, 140, // o
+    255, 214, 102, // highlight
+];
+
+const PAPER: u8 = 0;
+const INK: u8 = 1;
+const X: u8 = 2;
+const O: u8 = 3;
+const HIGHLIGHT: u8 = 4;
+
+/// A board drawn into pixels, each an index into `PALETTE`, row by row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Raster {
+    pub width: u16,
+    pub height: u16,
+    pub pixels: Vec<u8>,
+}
+
+impl Raster {
+    /// The board in `square` pixel squares, pieces drawn as nested discs like
+    /// `svg::Diagram` does. The squares of `lines`, such as
+    /// `Game::winning_line`, are shaded and struck through.
+    pub fn draw(game: &Game, square: u16, lines: &[[(usize, usize); 3]]) -> Self {
+        let square = square.max(20);
+        let margin = square / 10;
+        let size = 3 * square + 2 * margin;
+        let mut raster = Self {
+            width: size,
+            height: size,
+            pixels: vec![PAPER; size as usize * size as usize],
+        };
+        let s = square as f64;
+        let centre = |row: usize, col: usize| {
+            (
+                margin as f64 + (col as f64 + 0.5) * s,
+                margin as f64 + (row as f64 + 0.5) * s,
+            )
+        };
+
+        for (row, col) in lines.iter().flatten() {
+            let (x, y) = centre(*row, *col);
+            raster.fill(
+                |px, py| (px - x).abs() <= s / 2.0 && (py - y).abs() <= s / 2.0,
+                HIGHLIGHT,
+            );
+        }
+
+        // The grid
+        let thickness = s / 25.0;
+        for i in 1..3 {
+            let offset = margin as f64 + i as f64 * s;
+            let (start, end) = (margin as f64, margin as f64 + 3.0 * s);
+            raster.fill(
+                |px, py| (px - offset).abs() <= thickness / 2.0 && py >= start && py <= end,
+                INK,
+            );
+            raster.fill(
+                |px, py| (py - offset).abs() <= thickness / 2.0 && px >= start && px <= end,
+                INK,
+            );
+        }
+
+        let biggest = game.get_biggest_piece().max(1) as f64;
+        let largest = s * 0.42;
+        for row in 0..3 {
+            for col in 0..3 {
+                if let Some((kind, size)) = game.tiles()[row][col] {
+                    let (x, y) = centre(row, col);
+                    let colour = match kind {
+                        PlayerKind::X => X,
+                        PlayerKind::O => O,
+                    };
+                    // Rings from the outside in, alternating with the paper
+                    for ring in (0..=size).rev() {
+                        let r = largest * (0.2 + 0.8 * ring as f64 / biggest);
+                        let fill = match (size - ring) % 2 {
+                            0 => colour,
+                            _ => PAPER,
+                        };
+                        raster.fill(|px, py| (px - x).powi(2) + (py - y).powi(2) <= r * r, fill);
+                    }
+                }
+            }
+        }
+
+        // A stroke through each line, from end to end
+        for [first, _, last] in lines {
+            let (x1, y1) = centre(first.0, first.1);
+            let (x2, y2) = centre(last.0, last.1);
+            let width = s / 12.0;
+            raster.fill(
+                |px, py| distance_to_segment((px, py), (x1, y1), (x2, y2)) <= width / 2.0,
+                INK,
+            );
+        }
+
+        raster
+    }
+
+    // Colours every pixel whose centre is inside the shape
+    fn fill<F: Fn(f64, f64) -> bool>(&mut self, inside: F, colour: u8) {
+        for y in 0..self.height as usize {
+            for x in 0..self.width as usize {
+                if inside(x as f64 + 0.5, y as f64 + 0.5) {
+                    self.pixels[y * self.width as usize + x] = colour;
+                }
+            }
+        }
+    }
+}
+
+fn distance_to_segment((px, py): (f64, f64), (x1, y1): (f64, f64), (x2, y2): (f64, f64)) -> f64 {
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (((px - x1) * dx + (py - y1) * dy) / length).clamp(0.0, 1.0)
+    };
+    ((px - x1 - t * dx).powi(2) + (py - y1 - t * dy).powi(2)).sqrt()
+}
@@ -0,0 +1,140 @@
+use crate::game::{Game, Move, Winner};
+use crate::io::{format_move, parse_move};
+use std::fmt;
+use std::str::FromStr;
+
+/// A finished game: who played it, every move in order and how it ended.
+/// Written as a few `[Key "value"]` headers followed by the moves in the same
+/// notation as the console prompt:
+///
+/// ```text
+/// [Pieces "3"]
+/// [X "solver"]
+/// [O "random"]
+/// [Result "1/2-1/2"]
+///
+/// 2B2 1A1 3C1 1C2 3A0 2A0
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRecord {
+    pub number_of_pieces: usize,
+    pub x: String,
+    pub o: String,
+    pub moves: Vec<Move>,
+    /// `None` if the game was cut short, such as by a player being stuck
+    /// without a legal move
+    pub winner: Option<Winner>,
+}
+
+impl GameRecord {
+    pub fn new(number_of_pieces: usize, x: &str, o: &str) -> Self {
+        Self {
+            number_of_pieces,
+            x: x.to_string(),
+            o: o.to_string(),
+            moves: Vec::new(),
+            winner: None,
+        }
+    }
+
+    /// Plays the moves back from the empty board
+    pub fn replay(&self) -> Result<Game, String> {
+        self.moves.iter().try_fold(
+            Game::new_with_size(self.number_of_pieces),
+            |game, (row, col, size)| {
+                game.make_move(*row, *col, *size)
+                    .map_err(|e| format!("{}: {}", format_move((*row, *col, *size)), e))
+            },
+        )
+    }
+
+    /// `1-0` if x won, `0-1` if o won, `1/2-1/2` for a tie and `*` for a game
+    /// that didn't finish
+    pub fn result(&self) -> &'static str {
+        match self.winner {
+            Some(Winner::X) => "1-0",
+            Some(Winner::O) => "0-1",
+            Some(Winner::Tie) => "1/2-1/2",
+            None => "*",
+        }
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "[Pieces \"{}\"]", self.number_of_pieces)?;
+        writeln!(f, "[X \"{}\"]", self.x)?;
+        writeln!(f, "[O \"{}\"]", self.o)?;
+        writeln!(f, "[Result \"{}\"]", self.result())?;
+        writeln!(f)?;
+        let moves = self
+            .moves
+            .iter()
+            .map(|m| format_move(*m))
+            .collect::<Vec<String>>();
+        writeln!(f, "{}", moves.join(" "))
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut record = GameRecord::new(0, "", "");
+        for line in s.lines().map(|line| line.trim()) {
+            if line.starts_with('[') {
+                let (key, value) = line
+                    .trim_matches(|c| c == '[' || c == ']')
+                    .split_once(' ')
+                    .ok_or_else(|| format!("Bad header {}", line))?;
+                let value = value.trim_matches('"');
+                match key {
+                    "Pieces" => {
+                        record.number_of_pieces = value
+                            .parse()
+                            .map_err(|_| format!("Bad number of pieces {}", value))?
+                    }
+                    "X" => record.x = value.to_string(),
+                    "O" => record.o = value.to_string(),
+                    "Result" => {
+                        record.winner = match value {
+                            "1-0" => Some(Winner::X),
+                            "0-1" => Some(Winner::O),
+                            "1/2-1/2" => Some(Winner::Tie),
+                            "*" => None,
+                            _ => return Err(format!("Bad result {}", value)),
+                        }
+                    }
+                    _ => (),
+                }
+            } else {
+                for token in line.split_whitespace() {
+                    let m = parse_move(token).map_err(|e| format!("Bad move {}", e.0))?;
+                    record.moves.push(m);
+                }
+            }
+        }
+        if record.number_of_pieces == 0 {
+            return Err("Missing the Pieces header".to_string());
+        }
+        Ok(record)
+    }
+}
+
+/// Splits a file of records separated by blank lines
+pub fn parse_records(s: &str) -> Result<Vec<GameRecord>, String> {
+    let mut records = Vec::new();
+    let mut current = String::new();
+    for line in s.lines() {
+        if line.trim_start().starts_with("[Pieces") && !current.trim().is_empty() {
+            records.push(current.parse()?);
+            current.clear();
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    if !current.trim().is_empty() {
+        records.push(current.parse()?);
+    }
+    Ok(records)
+}
@@ -0,0 +1,167 @@
+extern crate getopts;
+use getopts::Options;
+use matryoshka_tic_tac_toe::animation::Animation;
+use matryoshka_tic_tac_toe::game::Game;
+use matryoshka_tic_tac_toe::io::{parse_move, parse_position};
+use matryoshka_tic_tac_toe::record::parse_records;
+use matryoshka_tic_tac_toe::svg::Diagram;
+use std::env;
+use std::fs::{self, File};
+
+fn print_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} [options]", program);
+    println!("{}", opts.usage(&brief));
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+    let mut game = Game::new();
+    let mut diagram = Diagram::new();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "p",
+        "position",
+        "The position to draw, e.g. 'X2,-,-/-,O1,-/-,-,- x 0,1 0,2', defaults to the empty board",
+        "NOTATION",
+    );
+    opts.optopt(
+        "m",
+        "moves",
+        "Moves to draw as numbered outlines joined by arrows, e.g. 2B2,1A1",
+        "MOVES",
+    );
+    opts.optopt(
+        "r",
+        "record",
+        "Replay a game from a file of game records as an animated GIF",
+        "FILE",
+    );
+    opts.optopt(
+        "g",
+        "game",
+        "Which game of the records file to replay, from 1 (default)",
+        "NUMBER",
+    );
+    opts.optopt(
+        "d",
+        "delay",
+        "How long each move of a replay stays up, defaults to 800",
+        "MILLISECONDS",
+    );
+    opts.optopt(
+        "o",
+        "output",
+        "Write the SVG here instead of stdout, or the GIF of a replay",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "square",
+        "Width of a square in pixels, defaults to 100",
+        "PIXELS",
+    );
+    opts.optflag("", "no-labels", "Leave out the row and column labels");
+    opts.optflag("", "no-hands", "Leave out the pieces left in each hand");
+    opts.optflag("h", "help", "print this help menu");
+    let m = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    if m.opt_present("h") {
+        print_usage(&program, opts);
+        return;
+    }
+    if let Some(path) = m.opt_str("r") {
+        replay(&m, &path);
+        return;
+    }
+    if let Some(position) = m.opt_str("p") {
+        match parse_position(&position) {
+            Ok(position) => game = position,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        }
+    }
+    if let Some(moves) = m.opt_str("m") {
+        match moves.split(',').map(|m| parse_move(m.trim())).collect() {
+            Ok(moves) => diagram = diagram.with_arrows(moves),
+            Err(e) => {
+                eprintln!("Invalid move: '{}'", e.0);
+                return;
+            }
+        }
+    }
+    match m.opt_get::<u32>("square") {
+        Ok(Some(square)) => diagram = diagram.with_square(square),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+        _ => (),
+    }
+    diagram = diagram
+        .with_labels(!m.opt_present("no-labels"))
+        .with_hands(!m.opt_present("no-hands"));
+    let svg = diagram.render(&game);
+    match m.opt_str("o") {
+        Some(path) => {
+            if let Err(e) = fs::write(&path, svg) {
+                eprintln!("Couldn't write {}: {}", path, e);
+            }
+        }
+        None => print!("{}", svg),
+    }
+}
+
+fn replay(m: &getopts::Matches, path: &str) {
+    let records = match fs::read_to_string(path).map_err(|e| e.to_string()) {
+        Ok(text) => parse_records(&text),
+        Err(e) => Err(e),
+    };
+    let records = match records {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("Couldn't read records from {}: {}", path, e);
+            return;
+        }
+    };
+    let number = m.opt_get_default::<usize>("g", 1).unwrap_or(0);
+    let record = match number.checked_sub(1).and_then(|i| records.get(i)) {
+        Some(record) => record,
+        None => {
+            eprintln!("There are {} games in {}", records.len(), path);
+            return;
+        }
+    };
+
+    let mut animation = Animation::new();
+    match m.opt_get::<u16>("delay") {
+        Ok(Some(delay)) => animation = animation.with_delay(delay),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+        _ => (),
+    }
+    if let Ok(Some(square)) = m.opt_get::<u16>("square") {
+        animation = animation.with_square(square);
+    }
+    let output = match m.opt_str("o") {
+        Some(output) => output,
+        None => {
+            eprintln!("A replay needs a file to write to, -o replay.gif");
+            return;
+        }
+    };
+    let written = File::create(&output).and_then(|file| animation.write_gif(record, file));
+    if let Err(e) = written {
+        eprintln!("Couldn't write {}: {}", output, e);
+    }
+}
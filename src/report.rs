@@ -0,0 +1,216 @@
+use crate::game::{Game, Move, PlayerKind};
+use crate::io::format_move;
+use crate::record::GameRecord;
+use crate::solver::{Analysis, Solver};
+use std::fmt;
+
+/// How a move compares with perfect play
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Keeps the outcome, and as far as the solver can tell wins as fast or
+    /// loses as slowly as possible
+    Best,
+    /// Keeps the outcome, but wins more slowly or loses sooner than perfect
+    /// play. Only the tablebase knows how far each position is from the
+    /// end, so without one covering the game no move is an inaccuracy.
+    Inaccuracy,
+    /// Turns a win into a tie or a loss, or a tie into a loss
+    Blunder,
+}
+
+/// One move of a game, judged by the solver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub player: PlayerKind,
+    pub r#move: Move,
+    pub verdict: Verdict,
+    /// 10 if the player wins with perfect play from before the move, 0 for a
+    /// tie and -10 for a loss
+    pub before: i8,
+    /// The same, from after the move
+    pub after: i8,
+    /// The solver's move, unless the move was best
+    pub better: Option<Move>,
+}
+
+/// A recorded game with every move annotated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub record: GameRecord,
+    pub annotations: Vec<Annotation>,
+}
+
+impl Report {
+    /// Analyses every position of the game with `solver`, so a solver with a
+    /// lookup or a tablebase is much quicker. A move along the solver's
+    /// principal variation leaves the rest of it to follow, without another
+    /// search.
+    pub fn new(record: &GameRecord, solver: &mut Solver) -> Result<Self, String> {
+        let mut game = Game::new_with_size(record.number_of_pieces);
+        let mut analysis = analyse(&game, solver)?;
+        let mut annotations = Vec::new();
+        for (i, (row, col, size)) in record.moves.iter().enumerate() {
+            let next = game
+                .clone()
+                .make_move(*row, *col, *size)
+                .map_err(|e| format!("Move {}: {}", i + 1, e))?;
+            let next_analysis = match analysis.pv.split_first() {
+                Some((best, rest)) if *best == (*row, *col, *size) && !rest.is_empty() => {
+                    Analysis {
+                        best_move: rest.first().copied(),
+                        score: analysis.score.map(|score| -score),
+                        pv: rest.to_vec(),
+                        nodes: 0,
+                    }
+                }
+                _ => analyse(&next, solver)?,
+            };
+
+            // Scores are for whoever is to move, so the position after the
+            // move is scored for the opponent
+            let (before, after) = (score(&analysis), -score(&next_analysis));
+            let verdict = if after < before {
+                Verdict::Blunder
+            } else if slower(solver, &game, &next) {
+                Verdict::Inaccuracy
+            } else {
+                Verdict::Best
+            };
+            annotations.push(Annotation {
+                player: game.current_player_kind(),
+                r#move: (*row, *col, *size),
+                verdict,
+                before,
+                after,
+                better: match verdict {
+                    Verdict::Best => None,
+                    _ => analysis.best_move,
+                },
+            });
+
+            game = next;
+            analysis = next_analysis;
+        }
+        Ok(Self {
+            record: record.clone(),
+            annotations,
+        })
+    }
+
+    /// How many moves got `verdict`
+    pub fn count(&self, verdict: Verdict) -> usize {
+        self.annotations
+            .iter()
+            .filter(|annotation| annotation.verdict == verdict)
+            .count()
+    }
+}
+
+fn analyse(game: &Game, solver: &mut Solver) -> Result<Analysis, String> {
+    let analysis = solver.analyse(game);
+    match analysis.score {
+        Some(_) => Ok(analysis),
+        None => Err("The search was stopped".to_string()),
+    }
+}
+
+// Whether the move from `game` to `next` keeps a win but takes longer over
+// it, or keeps a loss but gives in sooner, by the tablebase's distance to
+// the end. The winner's distance is to their quickest win and the loser's
+// to the longest they can hold out.
+fn slower(solver: &Solver, game: &Game, next: &Game) -> bool {
+    let (before, after) = match (solver.tablebase_entry(game), solver.tablebase_entry(next)) {
+        (Some(before), Some(after)) => (before, after),
+        _ => return false,
+    };
+    let kind = game.current_player_kind();
+    let (depth, best_depth) = (1 + after.depth as usize, before.depth as usize);
+    match (before.score_for(kind), after.score_for(kind)) {
+        (b, a) if b != a => false,
+        (b, _) if b > 0 => depth > best_depth,
+        (b, _) if b < 0 => depth < best_depth,
+        _ => false,
+    }
+}
+
+fn score(analysis: &Analysis) -> i8 {
+    analysis.score.unwrap_or(0)
+}
+
+fn outcome(score: i8) -> &'static str {
+    match score {
+        s if s > 0 => "wins",
+        0 => "ties",
+        _ => "loses",
+    }
+}
+
+/// The record's headers, then a move a line with its verdict, e.g.
+///
+/// ```text
+///  1. x 2B2  best, ties
+///  2. o 1A1  blunder, loses instead of tying; 2B3 ties
+/// ```
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "[Pieces \"{}\"]", self.record.number_of_pieces)?;
+        writeln!(f, "[X \"{}\"]", self.record.x)?;
+        writeln!(f, "[O \"{}\"]", self.record.o)?;
+        writeln!(f, "[Result \"{}\"]", self.record.result())?;
+        writeln!(f)?;
+        for (i, annotation) in self.annotations.iter().enumerate() {
+            write!(
+                f,
+                "{:>2}. {} {}  ",
+                i + 1,
+                match annotation.player {
+                    PlayerKind::X => "x",
+                    PlayerKind::O => "o",
+                },
+                format_move(annotation.r#move)
+            )?;
+            match annotation.verdict {
+                Verdict::Best => write!(f, "best, {}", outcome(annotation.after))?,
+                Verdict::Inaccuracy => write!(
+                    f,
+                    "inaccuracy, {} {}",
+                    outcome(annotation.after),
+                    match annotation.after > 0 {
+                        true => "more slowly",
+                        false => "sooner",
+                    }
+                )?,
+                Verdict::Blunder => write!(
+                    f,
+                    "blunder, {} instead of {}",
+                    outcome(annotation.after),
+                    match annotation.before > 0 {
+                        true => "winning",
+                        false => "tying",
+                    }
+                )?,
+            }
+            match annotation.better {
+                Some(better) => writeln!(
+                    f,
+                    "; {} {}",
+                    format_move(better),
+                    outcome(annotation.before)
+                )?,
+                None => writeln!(f)?,
+            }
+        }
+        let (blunders, inaccuracies) = (
+            self.count(Verdict::Blunder),
+            self.count(Verdict::Inaccuracy),
+        );
+        writeln!(
+            f,
+            "\n{} blunder{}, {} inaccurac{}",
+            blunders,
+            if blunders == 1 { "" } else { "s" },
+            inaccuracies,
+            if inaccuracies == 1 { "y" } else { "ies" }
+        )
+    }
+}
@@ -0,0 +1,238 @@
+use crate::game::Game;
+use crate::io::{format_move, parse_position};
+use crate::solver::{Analysis, LookupStats, Solver};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Bodies any bigger than this are turned away
+const MAX_BODY: u64 = 64 * 1024;
+
+/// The body of `POST /move` and `POST /evaluate`: the position either as
+/// serde writes a `Game` or in the notation of `io::format_position`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PositionRequest {
+    pub game: Option<Game>,
+    pub position: Option<String>,
+    /// Overrides the server's timeout for this request, up to the server's
+    /// maximum
+    pub timeout_ms: Option<u64>,
+}
+
+/// The solver over HTTP, answering:
+///
+/// - `GET /health`, `{"status": "ok"}`
+/// - `POST /move`, the best move for the player to move and the score it
+///   leads to: `{"move": "2B2", "row": 1, "col": 1, "size": 2, "score": 0}`.
+///   A position the lookup knows is answered from it without searching, and
+///   as the lookup only keeps moves the score is then `null`.
+/// - `POST /evaluate`, the score of the position, the principal variation and
+///   the positions searched: `{"score": 0, "pv": ["2B2", ...], "nodes": 1234}`
+/// - `GET /lookup/stats`, the size of the lookup and the tablebase, and how
+///   many moves have been answered from the lookup as `hits`
+///
+/// Scores are for the player to move: 10 for a win, 0 for a tie and -10 for
+/// a loss. Every request shares one solver, and so its lookup, and searches
+/// wait their turn for it. A search that runs out of time is stopped and
+/// answered with 503. Errors are `{"error": "..."}`.
+pub struct Server {
+    solver: Mutex<Solver>,
+    stop: Arc<AtomicBool>,
+    searches: AtomicU64,
+    /// The search holding the solver, only changed along with `stop`
+    active: Mutex<Option<u64>>,
+    /// As of the last search, so that asking doesn't wait for the solver
+    stats: Mutex<LookupStats>,
+    hits: AtomicU64,
+    timeout: Duration,
+    max_timeout: Duration,
+}
+
+impl Server {
+    pub fn new(solver: Solver) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        Self {
+            stats: Mutex::new(solver.lookup_stats()),
+            solver: Mutex::new(solver.with_stop(Arc::clone(&stop))),
+            stop,
+            searches: AtomicU64::new(0),
+            active: Mutex::new(None),
+            hits: AtomicU64::new(0),
+            timeout: Duration::from_secs(10),
+            max_timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// How long a search may take, including waiting for other searches to
+    /// finish, unless a request asks for something else
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The longest a request may ask a search to take, so that no one
+    /// request holds the solver for longer
+    pub fn with_max_timeout(mut self, max_timeout: Duration) -> Self {
+        self.max_timeout = max_timeout;
+        self
+    }
+
+    /// Answers requests until the listener is closed, each on its own thread
+    pub fn serve(self: Arc<Self>, http: tiny_http::Server) {
+        for mut request in http.incoming_requests() {
+            let server = Arc::clone(&self);
+            thread::spawn(move || {
+                let mut body = String::new();
+                let (status, json) =
+                    match request.as_reader().take(MAX_BODY).read_to_string(&mut body) {
+                        Ok(_) => server.handle(request.method().as_str(), request.url(), &body),
+                        Err(e) => error(400, &e.to_string()),
+                    };
+                let response = tiny_http::Response::from_string(json)
+                    .with_status_code(status)
+                    .with_header(
+                        tiny_http::Header::from_bytes(
+                            &b"Content-Type"[..],
+                            &b"application/json"[..],
+                        )
+                        .unwrap(),
+                    );
+                // The client may have gone, in which case nobody is waiting
+                let _ = request.respond(response);
+            });
+        }
+    }
+
+    /// The status code and JSON body of the answer to a request
+    pub fn handle(self: &Arc<Self>, method: &str, path: &str, body: &str) -> (u16, String) {
+        match (method, path) {
+            ("GET", "/health") => (200, json!({ "status": "ok" }).to_string()),
+            ("GET", "/lookup/stats") => {
+                let mut stats = serde_json::to_value(&*self.stats.lock().unwrap()).unwrap();
+                stats["hits"] = self.hits.load(Ordering::Relaxed).into();
+                (200, stats.to_string())
+            }
+            ("POST", "/move") => match self.analyse_request(body, true) {
+                Ok(Analysis {
+                    best_move: Some(best),
+                    score,
+                    ..
+                }) => (
+                    200,
+                    json!({
+                        "move": format_move(best),
+                        "row": best.0,
+                        "col": best.1,
+                        "size": best.2,
+                        "score": score,
+                    })
+                    .to_string(),
+                ),
+                Ok(_) => error(422, "There is no legal move"),
+                Err(e) => e,
+            },
+            ("POST", "/evaluate") => match self.analyse_request(body, false) {
+                Ok(analysis) => (
+                    200,
+                    json!({
+                        "score": analysis.score,
+                        "pv": analysis.pv.iter().map(|m| format_move(*m)).collect::<Vec<String>>(),
+                        "nodes": analysis.nodes,
+                    })
+                    .to_string(),
+                ),
+                Err(e) => e,
+            },
+            (_, "/health") | (_, "/lookup/stats") | (_, "/move") | (_, "/evaluate") => {
+                error(405, &format!("{} isn't allowed on {}", method, path))
+            }
+            _ => error(404, &format!("Nothing at {}", path)),
+        }
+    }
+
+    // `recall` lets the tablebase or the lookup answer without a search
+    fn analyse_request(
+        self: &Arc<Self>,
+        body: &str,
+        recall: bool,
+    ) -> Result<Analysis, (u16, String)> {
+        let request = serde_json::from_str::<PositionRequest>(body)
+            .map_err(|e| error(400, &e.to_string()))?;
+        let game = match (request.game, request.position) {
+            // Whatever winner the client sent, it is worked out again
+            (Some(game), None) => Game::from_parts(
+                *game.tiles(),
+                game.players.clone(),
+                game.current_player_kind(),
+            ),
+            (None, Some(position)) => parse_position(&position).map_err(|e| error(400, &e))?,
+            _ => return Err(error(400, "Expected either a game or a position")),
+        };
+
+        let timeout = request
+            .timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(self.timeout)
+            .min(self.max_timeout);
+        self.analyse(game, timeout, recall).ok_or_else(|| {
+            error(
+                503,
+                &format!("The search took longer than {}ms", timeout.as_millis()),
+            )
+        })
+    }
+
+    // Searches on another thread, so that the search can be stopped once the
+    // request runs out of time
+    fn analyse(self: &Arc<Self>, game: Game, timeout: Duration, recall: bool) -> Option<Analysis> {
+        let id = self.searches.fetch_add(1, Ordering::Relaxed) + 1;
+        let deadline = Instant::now() + timeout;
+        let (sender, receiver) = mpsc::channel();
+        let server = Arc::clone(self);
+        thread::spawn(move || {
+            let mut solver = server.solver.lock().unwrap();
+            {
+                let mut active = server.active.lock().unwrap();
+                if Instant::now() >= deadline {
+                    return;
+                }
+                *active = Some(id);
+                server.stop.store(false, Ordering::Relaxed);
+            }
+            let analysis = match solver.recall(&game) {
+                recalled if recall && recalled.best_move.is_some() => {
+                    if recalled.score.is_none() {
+                        server.hits.fetch_add(1, Ordering::Relaxed);
+                    }
+                    recalled
+                }
+                _ => solver.analyse(&game),
+            };
+            *server.active.lock().unwrap() = None;
+            *server.stats.lock().unwrap() = solver.lookup_stats();
+            let _ = sender.send(analysis);
+        });
+
+        match receiver.recv_timeout(timeout) {
+            // Only a stopped search has neither
+            Ok(analysis) if analysis.score.is_some() || analysis.best_move.is_some() => {
+                Some(analysis)
+            }
+            _ => {
+                let active = self.active.lock().unwrap();
+                if *active == Some(id) {
+                    self.stop.store(true, Ordering::Relaxed);
+                }
+                None
+            }
+        }
+    }
+}
+
+fn error(status: u16, message: &str) -> (u16, String) {
+    (status, json!({ "error": message }).to_string())
+}
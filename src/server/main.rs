@@ -0,0 +1,149 @@
+extern crate getopts;
+use getopts::Options;
+use matryoshka_tic_tac_toe::server::Server;
+use matryoshka_tic_tac_toe::solver::{Algorithm, Solver, Tablebase};
+use matryoshka_tic_tac_toe::websocket;
+use std::env;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn print_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} [options]", program);
+    println!("{}", opts.usage(&brief));
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+    let mut address = "127.0.0.1:8080".to_string();
+    let mut timeout = Duration::from_secs(10);
+    let mut max_timeout = Duration::from_secs(60);
+    let mut websocket = None;
+    let mut algorithm = None;
+    let mut tablebase = None;
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "b",
+        "bind",
+        "Address to listen on, defaults to 127.0.0.1:8080",
+        "ADDRESS",
+    );
+    opts.optopt(
+        "",
+        "timeout",
+        "How long a search may take, defaults to 10000",
+        "MILLISECONDS",
+    );
+    opts.optopt(
+        "",
+        "max-timeout",
+        "The longest a request may ask a search to take, defaults to 60000",
+        "MILLISECONDS",
+    );
+    opts.optopt(
+        "w",
+        "websocket",
+        "Also serve browsers over WebSocket on this address",
+        "ADDRESS",
+    );
+    opts.optopt(
+        "a",
+        "algorithm",
+        "Search algorithm: ybwc (default), parallel or pns",
+        "ALGORITHM",
+    );
+    opts.optopt(
+        "t",
+        "tablebase",
+        "Answer from a tablebase made by lookup-generator --retrograde",
+        "FILE",
+    );
+
+    opts.optflag("h", "help", "print this help menu");
+    if let Ok(m) = opts.parse(&args[1..]) {
+        if m.opt_present("h") {
+            print_usage(&program, opts);
+            return;
+        }
+        if let Some(b) = m.opt_str("b") {
+            address = b;
+        }
+        if let Ok(Some(t)) = m.opt_get::<u64>("timeout") {
+            timeout = Duration::from_millis(t);
+        }
+        if let Ok(Some(t)) = m.opt_get::<u64>("max-timeout") {
+            max_timeout = Duration::from_millis(t);
+        }
+        websocket = m.opt_str("w");
+        match m.opt_get::<Algorithm>("a") {
+            Ok(a) => algorithm = a,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        }
+        tablebase = m.opt_str("t");
+    };
+
+    // The HTTP and WebSocket servers each have a solver of their own
+    let solver = || -> Result<Solver, String> {
+        let mut solver = Solver::new();
+        if let Some(a) = algorithm {
+            solver = solver.with_algorithm(a);
+        }
+        if let Some(path) = &tablebase {
+            let t = Tablebase::read(path)
+                .map_err(|e| format!("Couldn't read tablebase {}: {}", path, e))?;
+            solver = solver.with_tablebase(t);
+        }
+        Ok(solver)
+    };
+
+    if let Some(address) = websocket {
+        let listener = match TcpListener::bind(&address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Couldn't listen on {}: {}", address, e);
+                return;
+            }
+        };
+        let server = match solver() {
+            Ok(solver) => Arc::new(
+                websocket::Server::new(solver)
+                    .with_timeout(timeout)
+                    .with_max_timeout(max_timeout),
+            ),
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        println!("Listening on ws://{}", address);
+        thread::spawn(move || server.serve(listener));
+    }
+    let solver = match solver() {
+        Ok(solver) => solver,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let http = match tiny_http::Server::http(&address) {
+        Ok(http) => http,
+        Err(e) => {
+            eprintln!("Couldn't listen on {}: {}", address, e);
+            return;
+        }
+    };
+    println!("Listening on http://{}", address);
+    Arc::new(
+        Server::new(solver)
+            .with_timeout(timeout)
+            .with_max_timeout(max_timeout),
+    )
+    .serve(http);
+}
@@ -0,0 +1,138 @@
+use super::Solver;
+use crate::game::{Game, Move, PlayerKind};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic;
+
+/// What the solver makes of a position
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Analysis {
+    /// `None` if there is no legal move, or the search was stopped before it
+    /// found one
+    pub best_move: Option<Move>,
+    /// 10 if the player to move wins, 0 for a tie and -10 for a loss. `None`
+    /// if the search was stopped before the end of the game was reached, or
+    /// there was no search, see `Solver::recall`.
+    pub score: Option<i8>,
+    /// The principal variation: perfect play by both sides from the position
+    /// to the end of the game, empty if the search was stopped
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+}
+
+impl Solver {
+    /// Scores the position for whoever's turn it is with one search, which
+    /// also gives the line both sides play to reach that score. The solver's
+    /// own side is put back afterwards. The tablebase answers instead if it
+    /// covers the position, and the lookup and the difficulty play no part.
+    pub fn analyse(&mut self, game: &Game) -> Analysis {
+        self.reset_nodes();
+        let kind = self.kind;
+        self.kind = game.current_player_kind();
+
+        let (score, pv) = match self.tablebase_line(game, self.kind) {
+            Some(line) => line,
+            None => {
+                self.heuristics.age();
+                self.pv_search(game, true, true, i8::MIN, i8::MAX)
+            }
+        };
+        self.kind = kind;
+
+        let stopped = self.stopped();
+        Analysis {
+            best_move: if stopped { None } else { pv.first().copied() },
+            score: if stopped { None } else { Some(score) },
+            pv: if stopped { Vec::new() } else { pv },
+            nodes: self.nodes(),
+        }
+    }
+
+    /// What the tablebase or the lookup already know about the position,
+    /// without searching. The lookup only knows a move, so then the score is
+    /// `None` and the principal variation is just that move. Nothing at all
+    /// if neither knows the position.
+    pub fn recall(&self, game: &Game) -> Analysis {
+        let (best_move, score, pv) = match self.tablebase_line(game, game.current_player_kind()) {
+            Some((score, pv)) => (pv.first().copied(), Some(score), pv),
+            None => match self.check_lookup(game) {
+                Some(b_move) => (Some(b_move), None, vec![b_move]),
+                None => (None, None, Vec::new()),
+            },
+        };
+        Analysis {
+            best_move,
+            score,
+            pv,
+            nodes: 0,
+        }
+    }
+
+    // Follows the tablebase's best moves, which take the quickest win and
+    // put off a loss for as long as possible, scoring the position for
+    // `kind`
+    fn tablebase_line(&self, game: &Game, kind: PlayerKind) -> Option<(i8, Vec<Move>)> {
+        let tablebase = self.tablebase.as_ref().filter(|t| t.covers(game))?;
+        let score = tablebase.get(game)?.score_for(kind);
+
+        let mut pv = Vec::new();
+        let mut position = game.clone();
+        while let Some((row, col, size)) = tablebase.best_move(&position) {
+            position = match position.make_move(row, col, size) {
+                Ok(next) => next,
+                Err(_) => break,
+            };
+            pv.push((row, col, size));
+        }
+        Some((score, pv))
+    }
+
+    // Alpha-beta like `max_search` and `min_search` over the same moves, but
+    // on one thread so that each node can hand back the line below its best
+    // move. Scores are fail-hard, so a line is only kept for a move that
+    // raised alpha or lowered beta, which at the root's full window is the
+    // exact principal variation.
+    fn pv_search(
+        &self,
+        game: &Game,
+        maximising: bool,
+        root: bool,
+        mut alpha: i8,
+        mut beta: i8,
+    ) -> (i8, Vec<Move>) {
+        self.nodes.fetch_add(1, atomic::Ordering::Relaxed);
+        if let Some(score) = self.terminal_score(game) {
+            return (score, Vec::new());
+        }
+        if self.stopped() {
+            return (if maximising { alpha } else { beta }, Vec::new());
+        }
+
+        let children = match (maximising, root) {
+            (true, true) => self.root_children(game),
+            (true, false) => self.children(game, self.kind),
+            (false, _) => self.children(game, self.their_kind()),
+        };
+        // A player with nowhere to go leaves the game unfinished, which
+        // scores as a tie
+        if children.is_empty() {
+            return (0, Vec::new());
+        }
+
+        let mut pv = Vec::new();
+        for (b_move, child) in children.iter() {
+            let (score, line) = self.pv_search(child, !maximising, false, alpha, beta);
+            if maximising && score > alpha {
+                alpha = score;
+                pv = std::iter::once(*b_move).chain(line).collect();
+            } else if !maximising && score < beta {
+                beta = score;
+                pv = std::iter::once(*b_move).chain(line).collect();
+            }
+            if alpha >= beta {
+                self.heuristics.record_cutoff(game, *b_move);
+                break;
+            }
+        }
+        (if maximising { alpha } else { beta }, pv)
+    }
+}
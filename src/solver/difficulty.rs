@@ -0,0 +1,167 @@
+use super::Solver;
+use crate::game::Game;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// How well the solver plays. Anything below `Perfect` skips the tablebase,
+/// and only `Hard` and `Perfect` use the lookup, since it only holds the best
+/// moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    /// Takes a win on the board, otherwise looks two moves ahead and plays a
+    /// random move a third of the time
+    Easy,
+    /// Takes a win on the board, otherwise looks four moves ahead and plays a
+    /// random move one time in ten
+    Medium,
+    /// Never gives away a draw and plays perfectly once it has a forced win,
+    /// otherwise plays any move that doesn't lose
+    Hard,
+    /// Always plays the best move
+    #[default]
+    Perfect,
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            "perfect" => Ok(Difficulty::Perfect),
+            invalid => Err(format!(
+                "Unknown difficulty '{}', expected easy, medium, hard or perfect",
+                invalid
+            )),
+        }
+    }
+}
+
+impl Difficulty {
+    // Plies searched and how often a random move is played instead
+    fn horizon(&self) -> Option<(usize, f64)> {
+        match self {
+            Difficulty::Easy => Some((2, 1.0 / 3.0)),
+            Difficulty::Medium => Some((4, 0.1)),
+            Difficulty::Hard | Difficulty::Perfect => None,
+        }
+    }
+}
+
+impl Solver {
+    /// A move for any difficulty but `Perfect`, from every legal move
+    /// including caps of the solver's own pieces. Nothing if there is no
+    /// legal move.
+    pub(super) fn imperfect_move(&self, game: &Game) -> Option<(usize, usize, usize)> {
+        let moves = game.legal_moves();
+        let candidates = match self.difficulty.horizon() {
+            Some((depth, blunder)) => {
+                let wins = moves
+                    .iter()
+                    .filter(|(_, child)| self.terminal_score(child) == Some(10))
+                    .map(|(b_move, _)| *b_move)
+                    .collect::<Vec<(usize, usize, usize)>>();
+                if !wins.is_empty() {
+                    wins
+                } else if self.rng.lock().unwrap().gen_bool(blunder) {
+                    moves.iter().map(|(b_move, _)| *b_move).collect()
+                } else {
+                    self.best_within_horizon(&moves, depth)
+                }
+            }
+            None => self.non_losing_moves(game),
+        };
+        candidates.choose(&mut *self.rng.lock().unwrap()).copied()
+    }
+
+    // Every move that keeps the value of the position, unless it is a win,
+    // in which case only the moves a perfect search would play. The lookup
+    // already holds exactly these, so it is consulted first.
+    fn non_losing_moves(&self, game: &Game) -> Vec<(usize, usize, usize)> {
+        if let Some(moves) = self.lookup_moves(game) {
+            return moves;
+        }
+        let optimal = self.optimal_moves(game);
+        let moves = optimal.iter().map(|best| best.b_move).collect();
+        match optimal.first() {
+            Some(best) if best.score > 0 => self.quickest(game, moves),
+            Some(_) if !self.stopped() => moves,
+            _ => game
+                .legal_moves()
+                .into_iter()
+                .map(|(b_move, _)| b_move)
+                .collect(),
+        }
+    }
+
+    // The moves with the best score when the game is cut off after `depth`
+    // plies, anything unfinished by then counting as a tie
+    fn best_within_horizon(
+        &self,
+        moves: &[((usize, usize, usize), Game)],
+        depth: usize,
+    ) -> Vec<(usize, usize, usize)> {
+        let scored = moves
+            .iter()
+            .map(|(b_move, child)| {
+                let score = self.limited_search(child, depth - 1, i8::MIN, i8::MAX, false);
+                (*b_move, score)
+            })
+            .collect::<Vec<((usize, usize, usize), i8)>>();
+        let best = scored.iter().map(|(_, score)| *score).max();
+        scored
+            .into_iter()
+            .filter(|(_, score)| Some(*score) == best)
+            .map(|(b_move, _)| b_move)
+            .collect()
+    }
+
+    fn limited_search(
+        &self,
+        game: &Game,
+        depth: usize,
+        alpha: i8,
+        beta: i8,
+        maximising: bool,
+    ) -> i8 {
+        if let Some(score) = self.terminal_score(game) {
+            return score;
+        }
+        if depth == 0 {
+            return 0;
+        }
+
+        let kind = if maximising {
+            self.kind
+        } else {
+            self.their_kind()
+        };
+        let children = self.children(game, kind);
+        if children.is_empty() {
+            return 0;
+        }
+
+        let (mut alpha, mut beta) = (alpha, beta);
+        for (_, child) in children.iter() {
+            let score = self.limited_search(child, depth - 1, alpha, beta, !maximising);
+            if maximising {
+                alpha = alpha.max(score);
+            } else {
+                beta = beta.min(score);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        if maximising {
+            alpha
+        } else {
+            beta
+        }
+    }
+}
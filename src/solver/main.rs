@@ -3,10 +3,15 @@ const SOLVER_NUMBER_OF_PIECES: usize = 6;
 
 extern crate getopts;
 use getopts::Options;
-use matryoshka_tic_tac_toe::game::{Game, PlayerKind, Winner};
+use matryoshka_tic_tac_toe::agent::{play_match, Agent, Coached};
+use matryoshka_tic_tac_toe::game::{Game, Winner, MAX_PIECES};
 use matryoshka_tic_tac_toe::io::*;
-use matryoshka_tic_tac_toe::solver::Solver;
+use matryoshka_tic_tac_toe::json;
+use matryoshka_tic_tac_toe::mcts::{Budget, Mcts, Rollout};
+use matryoshka_tic_tac_toe::solver::{Algorithm, Difficulty, MoveOrdering, Solver, Tablebase};
+use matryoshka_tic_tac_toe::tui;
 use std::env;
+use std::time::Duration;
 
 fn print_usage(program: &str, opts: Options) {
     let brief = format!("Usage: {} FILE [options]", program);
@@ -14,67 +19,286 @@ fn print_usage(program: &str, opts: Options) {
 }
 
 fn main() {
-    let solver = Solver::new();
     let args: Vec<String> = env::args().collect();
     let program = args[0].clone();
     let mut number = SOLVER_NUMBER_OF_PIECES;
+    let mut algorithm = Algorithm::YoungBrothersWait;
+    let mut ordering = MoveOrdering::default();
+    let mut difficulty = Difficulty::default();
+    let mut tablebase = None;
+    let mut mcts = None;
+    let mut seed = None;
+    let mut json = false;
+    let mut full_screen = false;
+    let mut renderer = Renderer::new();
 
     let mut opts = Options::new();
+    opts.optopt("n", "number", "How many pieces to use", "PIECES");
     opts.optopt(
-        "n",
-        "number",
-        "How many pieces to use",
+        "a",
+        "algorithm",
+        "Search algorithm: ybwc (default), parallel or pns for proof-number search",
+        "ALGORITHM",
+    );
+    opts.optopt(
+        "o",
+        "ordering",
+        "Move ordering heuristics: all, none or a list of wins,blocks,captures,killers,history,largest",
+        "HEURISTICS",
+    );
+
+    opts.optopt(
+        "d",
+        "difficulty",
+        "How well the CPU plays: easy, medium, hard or perfect (default)",
+        "LEVEL",
+    );
+
+    opts.optopt(
+        "t",
+        "tablebase",
+        "Play from a tablebase made by lookup-generator --retrograde",
+        "FILE",
+    );
+
+    opts.optopt(
+        "p",
+        "prove",
+        "Print the result of perfect play from the empty board and exit",
         "PIECES",
     );
 
+    opts.optopt(
+        "s",
+        "seed",
+        "Seed the choice between equally good moves, to replay the same games",
+        "SEED",
+    );
+
+    opts.optflag(
+        "m",
+        "mcts",
+        "Play with Monte Carlo Tree Search instead, for games too big to solve",
+    );
+    opts.optopt(
+        "",
+        "playouts",
+        "Monte Carlo playouts per move, instead of a second per move",
+        "PLAYOUTS",
+    );
+    opts.optopt(
+        "",
+        "time",
+        "Monte Carlo search time per move, defaults to 1000",
+        "MILLISECONDS",
+    );
+    opts.optopt(
+        "",
+        "rollout",
+        "Monte Carlo rollouts: heuristic (default) or random",
+        "ROLLOUT",
+    );
+
+    opts.optflag(
+        "",
+        "json",
+        "Read JSON commands and write JSON events, one per line, see docs/json-api.md",
+    );
+    opts.optflag(
+        "",
+        "tui",
+        "Play full screen, picking squares with the arrow keys",
+    );
+
+    opts.optopt(
+        "",
+        "board",
+        "How to print the board: combined (default) or split into owners and sizes",
+        "LAYOUT",
+    );
+    opts.optopt(
+        "",
+        "board-size",
+        "Width of the board, only 3 (the default) is supported",
+        "SIZE",
+    );
+    opts.optflag(
+        "",
+        "glyphs",
+        "Draw pieces as circles growing with their size",
+    );
     opts.optflag("h", "help", "print this help menu");
-    match opts.parse(&args[1..]) {
-        Ok(m) => {
-            if m.opt_present("h") {
-                print_usage(&program, opts);
+    if let Ok(m) = opts.parse(&args[1..]) {
+        if m.opt_present("h") {
+            print_usage(&program, opts);
+            return;
+        }
+        if let Ok(Some(n)) = m.opt_get::<usize>("n") {
+            number = n;
+        }
+        match m.opt_get::<usize>("p") {
+            Ok(Some(p)) if (1..=MAX_PIECES).contains(&p) => {
+                prove(p);
+                return;
+            }
+            Ok(Some(p)) => {
+                eprintln!(
+                    "Can only prove games of 1 to {} pieces, not {}",
+                    MAX_PIECES, p
+                );
+                return;
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+            _ => (),
+        }
+        match m.opt_get::<Algorithm>("a") {
+            Ok(Some(a)) => algorithm = a,
+            Err(e) => {
+                eprintln!("{}", e);
                 return;
             }
-            if m.opt_present("n") {
-                match m.opt_get::<usize>("n") {
-                    Ok(Some(n)) => number = n,
-                    _ => (),
+            _ => (),
+        }
+        match m.opt_get::<Difficulty>("d") {
+            Ok(Some(d)) => difficulty = d,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+            _ => (),
+        }
+        match m.opt_get::<MoveOrdering>("o") {
+            Ok(Some(o)) => ordering = o,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+            _ => (),
+        }
+        match m.opt_get::<u64>("s") {
+            Ok(s) => seed = s,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        }
+        json = m.opt_present("json");
+        full_screen = m.opt_present("tui");
+        match m.opt_get::<Layout>("board") {
+            Ok(Some(layout)) => renderer = renderer.with_layout(layout),
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+            _ => (),
+        }
+        renderer = renderer.with_glyphs(m.opt_present("glyphs"));
+        match m.opt_get::<usize>("board-size") {
+            Ok(Some(size)) if size != 3 => {
+                eprintln!("Only 3x3 boards are supported, not {}x{}", size, size);
+                return;
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+            _ => (),
+        }
+        if m.opt_present("m") {
+            let mut engine = Mcts::new();
+            if let Ok(Some(time)) = m.opt_get::<u64>("time") {
+                engine = engine.with_budget(Budget::Time(Duration::from_millis(time)));
+            }
+            if let Ok(Some(playouts)) = m.opt_get::<usize>("playouts") {
+                engine = engine.with_budget(Budget::Playouts(playouts));
+            }
+            match m.opt_get::<Rollout>("rollout") {
+                Ok(Some(rollout)) => engine = engine.with_rollout(rollout),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
                 }
+                _ => (),
+            }
+            if let Some(seed) = seed {
+                engine = engine.with_seed(seed);
             }
+            mcts = Some(engine);
         }
-        _ => (),
-    };
-    let mut game = Game::new_with_size(number);
-
-    while !game.is_finished() {
-        game.tiles().print();
-        let (x, o) = game.players.clone();
-        x.print_pieces();
-        o.print_pieces();
-
-        println!(
-            "Next piece: {}",
-            match game.current_player_kind() {
-                PlayerKind::X => "x",
-                PlayerKind::O => "o",
+        if let Some(path) = m.opt_str("t") {
+            match Tablebase::read(&path) {
+                Ok(t) => tablebase = Some(t),
+                Err(e) => {
+                    eprintln!("Couldn't read tablebase {}: {}", path, e);
+                    return;
+                }
             }
-        );
-
-        let (row, col, size) = match game.current_player_kind() {
-            PlayerKind::X => prompt_move(),
-            PlayerKind::O => solver.find_move(&game),
-        };
-
-        match game.clone().make_move(row, col, size) {
-            Ok(x) => game = x,
-            _ => continue,
         }
+    };
+    let mut solver = Solver::new()
+        .with_algorithm(algorithm)
+        .with_ordering(ordering)
+        .with_difficulty(difficulty)
+        .with_seed(seed.unwrap_or_else(rand::random));
+    if let Some(tablebase) = tablebase {
+        solver = solver.with_tablebase(tablebase);
     }
+    let mut cpu: Box<dyn Agent> = match mcts {
+        Some(mcts) => Box::new(mcts),
+        None => Box::new(solver),
+    };
 
-    game.tiles().print();
+    if json {
+        let stdin = std::io::stdin();
+        json::run(stdin.lock(), std::io::stdout(), number, Some(cpu.as_mut())).unwrap();
+        return;
+    }
+    if full_screen {
+        match tui::run(number, Some(cpu.as_mut())) {
+            Ok(Some(game)) => renderer.print_result(&game),
+            Ok(None) => (),
+            Err(e) => eprintln!("{}", e),
+        }
+        return;
+    }
 
-    match game.winner().expect("A finished game should have winner") {
-        Winner::X => println!("x wins!"),
-        Winner::O => println!("o wins!"),
-        Winner::Tie => println!("Tie!"),
+    match play_match(
+        Game::new_with_size(number),
+        // Searching for a hint from the start of a big game takes a while
+        &mut Coached::default().with_search(number < SOLVER_NUMBER_OF_PIECES),
+        cpu.as_mut(),
+        |game| renderer.print_position(game),
+    ) {
+        Ok(game) => renderer.print_result(&game),
+        Err(e) => eprintln!("{}", e),
     }
 }
+
+fn prove(number_of_pieces: usize) {
+    let path = format!("data/tablebase-{}.bin", number_of_pieces);
+    let tablebase = match Tablebase::read(&path) {
+        Ok(tablebase) if tablebase.number_of_pieces() == number_of_pieces => tablebase,
+        _ => {
+            println!(
+                "No {}, solving every position of the {} piece game",
+                path, number_of_pieces
+            );
+            Tablebase::generate(number_of_pieces)
+        }
+    };
+
+    let proof = tablebase.proof();
+    println!(
+        "{} pieces: {}, the longest perfect game lasts {} moves",
+        proof.number_of_pieces,
+        match proof.winner {
+            Winner::X => "first player (x) wins",
+            Winner::O => "second player (o) wins",
+            Winner::Tie => "draw",
+        },
+        proof.length
+    );
+}
@@ -1,7 +1,16 @@
 extern crate rayon;
+mod analysis;
+mod difficulty;
+mod ordering;
+mod pns;
+pub mod tablebase;
+
 use crate::game::{Game, PlayerKind, Winner};
 use crate::symmetry::*;
-use arc_swap::{ArcSwap, ArcSwapAny, Cache};
+use arc_swap::{ArcSwap, Cache};
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
@@ -9,20 +18,85 @@ use std::cmp::{max, min, Ordering};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
-use std::sync::Arc;
+use std::str::FromStr;
+use std::sync::atomic::{self, AtomicBool, AtomicI8, AtomicU64};
+use std::sync::{Arc, Mutex};
 use strum::IntoEnumIterator;
 
+pub use analysis::Analysis;
+pub use difficulty::Difficulty;
+pub(crate) use ordering::threats;
+pub(crate) use ordering::Heuristics;
+pub use ordering::MoveOrdering;
+pub use tablebase::{Proof, Tablebase};
+
+/// How the game tree is searched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Every child is searched in parallel with the bounds of its parent, so
+    /// siblings never tighten each other's window
+    Parallel,
+    /// Young Brothers Wait: the eldest child is searched first, then its
+    /// younger brothers are searched in parallel against a shared bound
+    YoungBrothersWait,
+    /// Depth-first proof-number search, which only proves or disproves wins
+    /// and so only tells a win, a tie and a loss apart
+    ProofNumber,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parallel" => Ok(Algorithm::Parallel),
+            "ybwc" => Ok(Algorithm::YoungBrothersWait),
+            "pns" => Ok(Algorithm::ProofNumber),
+            invalid => Err(format!(
+                "Unknown algorithm '{}', expected parallel, ybwc or pns",
+                invalid
+            )),
+        }
+    }
+}
+
 pub struct Solver {
     lookup_is_writable: bool,
-    lookup: Arc<ArcSwapAny<Arc<Box<Lookup>>>>,
+    lookup: Arc<ArcSwap<Lookup>>,
+    tablebase: Option<Arc<Tablebase>>,
+    algorithm: Algorithm,
+    ordering: MoveOrdering,
+    heuristics: Heuristics,
+    proof_table: usize,
+    difficulty: Difficulty,
+    randomise: bool,
+    rng: Mutex<SmallRng>,
+    nodes: Arc<AtomicU64>,
+    stop: Arc<AtomicBool>,
     pub kind: PlayerKind,
 }
 
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Solver {
     pub fn new() -> Self {
         Self {
             lookup_is_writable: false,
-            lookup: Arc::from(ArcSwap::from_pointee(Box::new(Lookup::new()))),
+            lookup: Arc::from(ArcSwap::from_pointee(Lookup::new())),
+            tablebase: None,
+            algorithm: Algorithm::YoungBrothersWait,
+            ordering: MoveOrdering::default(),
+            heuristics: Heuristics::new(),
+            proof_table: pns::TABLE_CAPACITY,
+            difficulty: Difficulty::default(),
+            randomise: false,
+            rng: Mutex::new(SmallRng::from_entropy()),
+            nodes: Arc::new(AtomicU64::new(0)),
+            stop: Arc::new(AtomicBool::new(false)),
             kind: PlayerKind::O,
         }
     }
@@ -30,22 +104,99 @@ impl Solver {
     pub fn new_overwrite_lookup() -> Self {
         Self {
             lookup_is_writable: true,
-            lookup: Arc::from(ArcSwap::from_pointee(Box::new(Lookup::new()))),
-            kind: PlayerKind::O,
+            ..Self::new()
         }
     }
 
     pub fn new_distinct_lookup(path: String) -> Self {
         Self {
             lookup_is_writable: true,
-            lookup: Arc::from(ArcSwap::from_pointee(Box::new(Lookup::new_distinct(
-                path.clone(),
-            )))),
-            kind: PlayerKind::O,
+            lookup: Arc::from(ArcSwap::from_pointee(Lookup::new_distinct(path))),
+            ..Self::new()
         }
     }
 
-    fn heuristic_move(self: &Self, game: &Game) -> Option<(usize, usize, usize)> {
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn with_ordering(mut self, ordering: MoveOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// How many positions the proof-number search remembers before it starts
+    /// forgetting the least searched ones
+    pub fn with_proof_table(mut self, capacity: usize) -> Self {
+        self.proof_table = capacity;
+        self
+    }
+
+    /// Picks at random between equally good moves, so that games vary. By
+    /// default the last of them in row/col/piece order is played.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.randomise = true;
+        self.rng = Mutex::new(SmallRng::seed_from_u64(seed));
+        self
+    }
+
+    pub fn with_difficulty(mut self, difficulty: Difficulty) -> Self {
+        self.difficulty = difficulty;
+        self
+    }
+
+    /// Consult a complete tablebase before anything else for positions with
+    /// the same number of pieces
+    pub fn with_tablebase(mut self, tablebase: Tablebase) -> Self {
+        self.tablebase = Some(Arc::new(tablebase));
+        self
+    }
+
+    /// Lets another thread cut a search short by setting `stop`, which stays
+    /// set until whoever set it clears it
+    pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Whether the search has been told to stop. The result of a search that
+    /// was stopped part way means nothing.
+    pub fn stopped(&self) -> bool {
+        self.stop.load(atomic::Ordering::Relaxed)
+    }
+
+    /// How much the lookup and the tablebase hold
+    pub fn lookup_stats(&self) -> LookupStats {
+        let lookup = self.lookup.load();
+        LookupStats {
+            path: lookup.path.clone(),
+            positions: lookup.data.x.len(),
+            moves: lookup
+                .data
+                .x
+                .values()
+                .map(|moves| moves.moves().len())
+                .sum(),
+            tablebase_positions: self.tablebase.as_ref().map(|tablebase| tablebase.len()),
+        }
+    }
+
+    /// Number of positions visited by the search since the last reset
+    pub fn nodes(&self) -> u64 {
+        self.nodes.load(atomic::Ordering::Relaxed)
+    }
+
+    /// The count behind `nodes`, for following a search from another thread
+    pub fn node_counter(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.nodes)
+    }
+
+    pub fn reset_nodes(&self) {
+        self.nodes.store(0, atomic::Ordering::Relaxed);
+    }
+
+    fn heuristic_move(&self, game: &Game) -> Option<(usize, usize, usize)> {
         let (my, their_kind) = match self.kind {
             PlayerKind::O => {
                 let (_, o) = game.players.clone();
@@ -67,280 +218,525 @@ impl Solver {
         None
     }
 
-    pub fn find_move(self: &Self, game: &Game) -> (usize, usize, usize) {
+    pub fn find_move(&self, game: &Game) -> (usize, usize, usize) {
+        if self.difficulty != Difficulty::Perfect {
+            if let Some(ijk) = self.imperfect_move(game) {
+                return ijk;
+            }
+        }
+
+        if let Some(ijk) = self.check_tablebase(game) {
+            return ijk;
+        }
+
         let before_state = game.clone();
 
         match self.check_lookup(&before_state) {
             Some(ijk) => ijk,
             _ => {
-                let m = self.heuristic_move(&before_state);
-                let (i, j, k) = match m {
-                    Some((i, j, k)) => (i, j, k),
-                    _ => {
-                        game.symmetry_range(Symmetry::FlipH)
-                            .par_iter()
-                            .map(|x| self.min_max_outer_loop(game, *x))
-                            .max()
-                            .unwrap()
-                            .b_move
-                    }
+                let moves = match self.heuristic_move(&before_state) {
+                    Some(ijk) => vec![ijk],
+                    _ => self.quickest(
+                        game,
+                        self.optimal_moves(game)
+                            .iter()
+                            .map(|best| best.b_move)
+                            .collect(),
+                    ),
                 };
 
-                self.add_to_lookup(&game, (i, j, k));
-                (i, j, k)
+                // A stopped search is no better than any legal move and
+                // isn't worth remembering
+                if moves.is_empty() || self.stopped() {
+                    return game
+                        .legal_moves()
+                        .last()
+                        .map(|(b_move, _)| *b_move)
+                        .unwrap_or((0, 0, 0));
+                }
+
+                self.add_to_lookup(game, moves.clone());
+                self.choose(game, &moves).unwrap_or((0, 0, 0))
             }
         }
     }
 
-    fn min_max_outer_loop(self: &Self, game: &Game, i: usize) -> BestMove {
-        game.symmetry_range(Symmetry::FlipV)
-            .par_iter()
-            .map(|x| self.min_max_inner_loop(game, i, *x))
-            .max()
-            .unwrap()
+    // Any optimal move that wins straight away, rather than one that only
+    // wins eventually
+    fn quickest(
+        &self,
+        game: &Game,
+        moves: Vec<(usize, usize, usize)>,
+    ) -> Vec<(usize, usize, usize)> {
+        let wins = moves
+            .iter()
+            .filter(|(i, j, k)| {
+                let child = game.clone().make_move(*i, *j, *k);
+                child.is_ok_and(|child| self.terminal_score(&child) == Some(10))
+            })
+            .copied()
+            .collect::<Vec<(usize, usize, usize)>>();
+        if wins.is_empty() {
+            moves
+        } else {
+            wins
+        }
     }
 
-    fn min_max_inner_loop(self: &Self, game: &Game, i: usize, j: usize) -> BestMove {
-        let my = match self.kind {
-            PlayerKind::O => {
-                let (_, o) = game.players.clone();
-                o
+    /// Any one of `moves`, or of the moves they are equivalent to because
+    /// the board is symmetrical, if the solver is seeded. Otherwise the last
+    /// of `moves`.
+    fn choose(
+        &self,
+        game: &Game,
+        moves: &[(usize, usize, usize)],
+    ) -> Option<(usize, usize, usize)> {
+        if !self.randomise {
+            return moves.last().copied();
+        }
+
+        let mut equivalent = Vec::new();
+        for symmetry in Symmetry::iter() {
+            if game.clone().fliptate(&symmetry) != *game {
+                continue;
             }
-            PlayerKind::X => {
-                let (x, _) = game.players.clone();
-                x
+            for m in moves.iter() {
+                equivalent.push(unfliptate_move(*m, &symmetry));
             }
-        };
-
-        my.pieces
-            .par_iter()
-            .map(|y| self.min_max_loop(game, i, j, *y))
-            .max()
-            .unwrap()
+        }
+        equivalent.sort_unstable();
+        equivalent.dedup();
+        equivalent.choose(&mut *self.rng.lock().unwrap()).copied()
     }
 
-    fn min_max_loop(self: &Self, game: &Game, i: usize, j: usize, piece: usize) -> BestMove {
-        let mut best_score = std::i8::MIN;
-        let mut best_move = (0, 0, 0);
+    /// Searches the whole game tree below `game` without consulting the
+    /// lookup or the opening heuristic
+    pub fn search_move(&self, game: &Game) -> ((usize, usize, usize), i8) {
+        let best = self.search(game);
+        (best.b_move, best.score)
+    }
 
-        let new_game = game.clone().make_move(i, j, piece);
+    fn search(&self, game: &Game) -> BestMove {
+        self.optimal_moves(game).pop().unwrap_or(BestMove {
+            b_move: (0, 0, 0),
+            score: i8::MIN,
+        })
+    }
 
-        match new_game {
-            Ok(x) => {
-                let tmp_score = self.min_search(&x, std::i8::MIN, std::i8::MAX);
+    /// Every move with the best score, in row/col/piece order
+    fn optimal_moves(&self, game: &Game) -> Vec<BestMove> {
+        if self.algorithm == Algorithm::ProofNumber {
+            return self.proof_number_search(game);
+        }
+        self.heuristics.age();
+
+        let children = self.root_children(game);
+
+        let scored = match self.algorithm {
+            Algorithm::Parallel => children
+                .par_iter()
+                .map(|(m, x)| BestMove {
+                    b_move: *m,
+                    score: self.min_search(x, i8::MIN, i8::MAX),
+                })
+                .collect::<Vec<BestMove>>(),
+            Algorithm::YoungBrothersWait => {
+                let ((eldest_move, eldest), younger) = match children.split_first() {
+                    Some(x) => x,
+                    None => return Vec::new(),
+                };
 
-                if tmp_score > best_score {
-                    best_score = tmp_score;
-                    best_move = (i, j, piece);
-                }
+                let eldest_score = self.min_search(eldest, i8::MIN, i8::MAX);
+                let alpha = AtomicI8::new(eldest_score);
+
+                // Brothers are searched with a window one below the best score
+                // so far, so that every move matching it has an exact score.
+                // Anything at or below the window may be worse than it claims.
+                let younger = younger
+                    .par_iter()
+                    .map(|(m, x)| {
+                        let a = alpha.load(atomic::Ordering::Relaxed).saturating_sub(1);
+                        let score = self.min_search(x, a, i8::MAX);
+                        alpha.fetch_max(score, atomic::Ordering::Relaxed);
+                        if score > a {
+                            Some(BestMove { b_move: *m, score })
+                        } else {
+                            None
+                        }
+                    })
+                    .collect::<Vec<Option<BestMove>>>();
+
+                std::iter::once(BestMove {
+                    b_move: *eldest_move,
+                    score: eldest_score,
+                })
+                .chain(younger.into_iter().flatten())
+                .collect::<Vec<BestMove>>()
             }
-            _ => (),
-        }
+            // Handled by `proof_number_search` before any min-max search
+            Algorithm::ProofNumber => unreachable!(),
+        };
 
-        BestMove {
-            b_move: best_move,
-            score: best_score,
-        }
+        // Whatever order the moves were searched in
+        let best = scored.iter().map(|best| best.score).max();
+        let mut optimal = scored
+            .into_iter()
+            .filter(|candidate| Some(candidate.score) == best)
+            .collect::<Vec<BestMove>>();
+        optimal.sort_by_key(|best| best.b_move);
+        optimal
     }
 
-    fn min_loop(
-        self: &Self,
-        game: &Game,
-        alpha: i8,
-        mut beta: i8,
-        i: usize,
-        j: usize,
-        piece: usize,
-    ) -> i8 {
-        match game.winner() {
-            Some(Winner::X) => return -10,
-            Some(Winner::O) => return 10,
-            Some(Winner::Tie) => return 0,
-            _ => (),
-        }
+    /// All positions reachable in one move, skipping moves made redundant by
+    /// the board's symmetry. Below the root the solver never caps its own
+    /// pieces, which prunes the search, but at the root every move is a
+    /// candidate so that a position where capping its own piece is the only
+    /// legal move still has a move to play.
+    fn root_children(&self, game: &Game) -> Vec<((usize, usize, usize), Game)> {
+        self.children_with(game, self.kind, true)
+    }
 
-        let new_game = game.clone().make_move(i, j, piece);
+    fn children(&self, game: &Game, kind: PlayerKind) -> Vec<((usize, usize, usize), Game)> {
+        self.children_with(game, kind, false)
+    }
 
-        match new_game {
-            Ok(x) => {
-                let score = self.max_search(&x, alpha, beta);
+    fn children_with(
+        &self,
+        game: &Game,
+        kind: PlayerKind,
+        own_caps: bool,
+    ) -> Vec<((usize, usize, usize), Game)> {
+        let (x, o) = &game.players;
+        let pieces = match kind {
+            PlayerKind::X => &x.pieces,
+            PlayerKind::O => &o.pieces,
+        };
 
-                beta = min(score, beta);
-                if alpha > beta {
-                    return beta;
+        let mut children = Vec::new();
+        for i in game.symmetry_range(Symmetry::FlipH) {
+            for j in game.symmetry_range(Symmetry::FlipV) {
+                if kind == self.kind && !own_caps {
+                    if let Some((k, _)) = game.tiles[i][j] {
+                        if k == self.kind {
+                            continue;
+                        }
+                    }
+                }
+                for piece in pieces {
+                    if let Ok(x) = game.clone().make_move(i, j, *piece) {
+                        children.push(((i, j, *piece), x));
+                    }
                 }
             }
-            _ => (),
         }
-        beta
+        self.heuristics.order(&self.ordering, game, &mut children);
+        children
     }
 
-    fn min_inner_loop(self: &Self, game: &Game, alpha: i8, beta: i8, i: usize, j: usize) -> i8 {
-        let their = match self.kind {
-            PlayerKind::O => {
-                let (x, _) = game.players.clone();
-                x
-            }
-            PlayerKind::X => {
-                let (_, o) = game.players.clone();
-                o
-            }
-        };
-
-        their
-            .pieces
-            .par_iter()
-            .map(|x| self.min_loop(&game, alpha, beta, i, j, *x))
-            .min()
-            .unwrap()
+    // Scores are for the side the solver plays, the way the search maximises
+    // them: 10 when `self.kind` has won and -10 when it has lost
+    fn terminal_score(&self, game: &Game) -> Option<i8> {
+        match (game.winner(), self.kind) {
+            (Some(Winner::X), PlayerKind::X) | (Some(Winner::O), PlayerKind::O) => Some(10),
+            (Some(Winner::X), PlayerKind::O) | (Some(Winner::O), PlayerKind::X) => Some(-10),
+            (Some(Winner::Tie), _) => Some(0),
+            _ => None,
+        }
     }
 
-    fn min_outer_loop(self: &Self, game: &Game, alpha: i8, beta: i8, i: usize) -> i8 {
-        game.symmetry_range(Symmetry::FlipV)
-            .par_iter()
-            .map(|x| self.min_inner_loop(&game, alpha, beta, i, *x))
-            .min()
-            .unwrap()
+    fn their_kind(&self) -> PlayerKind {
+        match self.kind {
+            PlayerKind::X => PlayerKind::O,
+            PlayerKind::O => PlayerKind::X,
+        }
     }
 
-    fn min_search(self: &Self, game: &Game, alpha: i8, beta: i8) -> i8 {
-        match game.winner() {
-            Some(Winner::X) => return -10,
-            Some(Winner::O) => return 10,
-            Some(Winner::Tie) => return 0,
-            _ => (),
+    fn min_search(&self, game: &Game, alpha: i8, beta: i8) -> i8 {
+        self.nodes.fetch_add(1, atomic::Ordering::Relaxed);
+        if let Some(score) = self.terminal_score(game) {
+            return score;
+        }
+        if self.stopped() {
+            return beta;
         }
 
-        game.symmetry_range(Symmetry::FlipH)
-            .par_iter()
-            .map(|x| self.min_outer_loop(&game, alpha, beta, *x))
-            .min()
-            .unwrap()
-    }
+        let children = self.children(game, self.their_kind());
+
+        match self.algorithm {
+            Algorithm::Parallel => children
+                .par_iter()
+                .map(|(_, x)| min(self.max_search(x, alpha, beta), beta))
+                .min()
+                .unwrap_or(beta),
+            Algorithm::YoungBrothersWait => {
+                let ((eldest_move, eldest), younger) = match children.split_first() {
+                    Some(x) => x,
+                    None => return beta,
+                };
 
-    fn max_loop(
-        self: &Self,
-        game: &Game,
-        mut alpha: i8,
-        beta: i8,
-        i: usize,
-        j: usize,
-        piece: usize,
-    ) -> i8 {
-        let new_game = game.clone().make_move(i, j, piece);
-
-        match game.tiles[i][j] {
-            Some((k, _)) => {
-                if k == self.kind {
-                    return alpha;
+                let beta = min(self.max_search(eldest, alpha, beta), beta);
+                if alpha >= beta {
+                    self.heuristics.record_cutoff(game, *eldest_move);
+                    return beta;
                 }
+
+                let shared_beta = AtomicI8::new(beta);
+                younger.par_iter().for_each(|(m, x)| {
+                    let b = shared_beta.load(atomic::Ordering::Relaxed);
+                    if alpha >= b {
+                        return;
+                    }
+                    let score = self.max_search(x, alpha, b);
+                    if alpha >= score {
+                        self.heuristics.record_cutoff(game, *m);
+                    }
+                    shared_beta.fetch_min(score, atomic::Ordering::Relaxed);
+                });
+                shared_beta.load(atomic::Ordering::Relaxed)
             }
-            _ => (),
+            // Handled by `proof_number_search` before any min-max search
+            Algorithm::ProofNumber => unreachable!(),
         }
+    }
 
-        match new_game {
-            Ok(x) => {
-                let score = self.min_search(&x, alpha, beta);
+    fn max_search(&self, game: &Game, alpha: i8, beta: i8) -> i8 {
+        self.nodes.fetch_add(1, atomic::Ordering::Relaxed);
+        if let Some(score) = self.terminal_score(game) {
+            return score;
+        }
+        if self.stopped() {
+            return alpha;
+        }
 
-                alpha = max(score, alpha);
-                if alpha > beta {
+        let children = self.children(game, self.kind);
+
+        match self.algorithm {
+            Algorithm::Parallel => children
+                .par_iter()
+                .map(|(_, x)| max(self.min_search(x, alpha, beta), alpha))
+                .max()
+                .unwrap_or(alpha),
+            Algorithm::YoungBrothersWait => {
+                let ((eldest_move, eldest), younger) = match children.split_first() {
+                    Some(x) => x,
+                    None => return alpha,
+                };
+
+                let alpha = max(self.min_search(eldest, alpha, beta), alpha);
+                if alpha >= beta {
+                    self.heuristics.record_cutoff(game, *eldest_move);
                     return alpha;
                 }
-            }
-            _ => (),
-        }
-        alpha
-    }
 
-    fn max_inner_loop(self: &Self, game: &Game, alpha: i8, beta: i8, i: usize, j: usize) -> i8 {
-        let my = match self.kind {
-            PlayerKind::O => {
-                let (_, o) = game.players.clone();
-                o
-            }
-            PlayerKind::X => {
-                let (x, _) = game.players.clone();
-                x
+                let shared_alpha = AtomicI8::new(alpha);
+                younger.par_iter().for_each(|(m, x)| {
+                    let a = shared_alpha.load(atomic::Ordering::Relaxed);
+                    if a >= beta {
+                        return;
+                    }
+                    let score = self.min_search(x, a, beta);
+                    if score >= beta {
+                        self.heuristics.record_cutoff(game, *m);
+                    }
+                    shared_alpha.fetch_max(score, atomic::Ordering::Relaxed);
+                });
+                shared_alpha.load(atomic::Ordering::Relaxed)
             }
-        };
-
-        my.pieces
-            .par_iter()
-            .map(|x| self.max_loop(game, alpha, beta, i, j, *x))
-            .max()
-            .unwrap()
+            // Handled by `proof_number_search` before any min-max search
+            Algorithm::ProofNumber => unreachable!(),
+        }
     }
 
-    fn max_outer_loop(self: &Self, game: &Game, alpha: i8, beta: i8, i: usize) -> i8 {
-        game.symmetry_range(Symmetry::FlipV)
-            .par_iter()
-            .map(|x| self.max_inner_loop(&game, alpha, beta, i, *x))
-            .max()
-            .unwrap()
+    /// The tablebase's value of the position, if it has a tablebase that
+    /// covers it
+    pub fn tablebase_entry(&self, game: &Game) -> Option<tablebase::Entry> {
+        self.tablebase
+            .as_ref()
+            .filter(|tablebase| tablebase.covers(game))?
+            .get(game)
     }
 
-    fn max_search(self: &Self, game: &Game, alpha: i8, beta: i8) -> i8 {
-        match game.winner() {
-            Some(Winner::X) => return -10,
-            Some(Winner::O) => return 10,
-            Some(Winner::Tie) => return 0,
-            _ => (),
+    pub fn check_tablebase(&self, game: &Game) -> Option<(usize, usize, usize)> {
+        match &self.tablebase {
+            Some(tablebase) if tablebase.covers(game) && self.randomise => {
+                self.choose(game, &tablebase.best_moves(game))
+            }
+            Some(tablebase) if tablebase.covers(game) => tablebase.best_move(game),
+            _ => None,
         }
+    }
 
-        game.symmetry_range(Symmetry::FlipH)
-            .par_iter()
-            .map(|x| self.max_outer_loop(&game, alpha, beta, *x))
-            .max()
-            .unwrap()
+    pub fn check_lookup(&self, game: &Game) -> Option<(usize, usize, usize)> {
+        let moves = self.lookup_moves(game)?;
+        self.choose(game, &moves)
     }
 
-    pub fn check_lookup(self: &Self, game: &Game) -> Option<(usize, usize, usize)> {
+    fn lookup_moves(&self, game: &Game) -> Option<Vec<(usize, usize, usize)>> {
         let lookup = Arc::clone(&self.lookup);
         let mut lookup = Cache::new(lookup);
         let lookup = &lookup.load().data.x;
 
-        if lookup.contains_key(&game) {
-            return Some(lookup[&game]);
+        // Lookups written before the symmetries were mapped back correctly
+        // can hold moves that aren't legal in their own position
+        let legal = |moves: Vec<(usize, usize, usize)>| {
+            let legal = moves
+                .into_iter()
+                .filter(|(i, j, k)| game.clone().make_move(*i, *j, *k).is_ok())
+                .collect::<Vec<(usize, usize, usize)>>();
+            Some(legal).filter(|legal| !legal.is_empty())
+        };
+
+        if let Some(moves) = lookup.get(game) {
+            return legal(moves.moves());
         }
         for symmetry in Symmetry::iter() {
             let symmetry_game = &game.clone().fliptate(&symmetry);
-            match lookup.get(symmetry_game) {
-                Some(ijk) => {
-                    let (i, j, k) = ijk;
-                    let (x, y) = index_to_coordinates(fliptate_coordinates(
-                        coordinates_to_index((*i, *j)),
-                        &symmetry.reverse(),
-                    ));
-                    self.add_to_lookup(&game, (x, y, *k));
-                    return Some((x, y, *k));
-                }
-                _ => (),
+            if let Some(moves) = lookup.get(symmetry_game) {
+                let moves = moves
+                    .moves()
+                    .iter()
+                    .map(|m| unfliptate_move(*m, &symmetry))
+                    .collect::<Vec<(usize, usize, usize)>>();
+                let moves = legal(moves)?;
+                self.add_to_lookup(game, moves.clone());
+                return Some(moves);
             }
         }
 
         None
     }
 
-    fn add_to_lookup(self: &Self, game: &Game, ideal_move: (usize, usize, usize)) {
+    /// Searches every position of a writable lookup again and replaces any
+    /// entry holding a move that is illegal or provably gives away some of
+    /// the position's value.
+    /// Calls `progress` with how many positions have been checked and how
+    /// many there are, and returns how many entries were replaced. Always
+    /// uses the min-max search, which scores every move.
+    pub fn repair_lookup<F>(&mut self, progress: F) -> usize
+    where
+        F: Fn(usize, usize),
+    {
+        let (kind, algorithm) = (self.kind, self.algorithm);
+        if algorithm == Algorithm::ProofNumber {
+            self.algorithm = Algorithm::YoungBrothersWait;
+        }
+
+        let mut lookup = (**self.lookup.load()).clone();
+        let positions = lookup.data.x.keys().cloned().collect::<Vec<Game>>();
+        let mut replaced = 0;
+        for (checked, game) in positions.iter().enumerate() {
+            progress(checked, positions.len());
+            self.kind = game.current_player_kind();
+            let optimal = self.optimal_moves(game);
+            let best = match optimal.first() {
+                Some(best) => best.score,
+                None => continue,
+            };
+            let mut keeps_value = true;
+            for (i, j, k) in lookup.data.x[game].moves() {
+                let child = match game.clone().make_move(i, j, k) {
+                    Ok(child) => child,
+                    Err(_) => {
+                        keeps_value = false;
+                        break;
+                    }
+                };
+                if self.min_search(&child, best.saturating_sub(1), i8::MAX) >= best {
+                    continue;
+                }
+                // The search never lets the side it plays cap its own pieces
+                // below the root, so it can undervalue a move. Only replace
+                // the move if the opponent's search, which can only
+                // undervalue the opponent, agrees that it gives value away.
+                if child.is_over() {
+                    keeps_value = false;
+                    break;
+                }
+                self.kind = child.current_player_kind();
+                let reply = self.optimal_moves(&child).first().map(|reply| reply.score);
+                self.kind = game.current_player_kind();
+                if reply.is_some_and(|reply| -reply < best) {
+                    keeps_value = false;
+                    break;
+                }
+            }
+            if !keeps_value {
+                let moves = optimal.iter().map(|best| best.b_move).collect();
+                let moves = LookupMoves::new(self.quickest(game, moves));
+                lookup.data.x.insert(game.clone(), moves);
+                replaced += 1;
+            }
+        }
+        progress(positions.len(), positions.len());
+        (self.kind, self.algorithm) = (kind, algorithm);
+
+        if self.lookup_is_writable && replaced > 0 {
+            lookup.write();
+            self.lookup.store(Arc::new(lookup));
+        }
+        replaced
+    }
+
+    fn add_to_lookup(&self, game: &Game, optimal_moves: Vec<(usize, usize, usize)>) {
         let lookup = Arc::clone(&self.lookup);
         let mut lookup = Cache::new(lookup);
         let lookup = &lookup.load();
         let path = &lookup.path;
 
         if self.lookup_is_writable {
-            let mut lookup: Box<Lookup> = Box::new(Lookup::new_distinct(path.to_string()));
-            lookup.data.x.insert(game.clone(), ideal_move);
-            lookup.clone().write();
+            let mut lookup = Lookup::new_distinct(path.to_string());
+            lookup
+                .data
+                .x
+                .insert(game.clone(), LookupMoves::new(optimal_moves));
+            lookup.write();
             self.lookup.store(Arc::from(lookup));
         };
     }
 }
 
+/// The size of a solver's lookup, and of its tablebase if it has one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LookupStats {
+    pub path: String,
+    pub positions: usize,
+    /// Optimal moves across every position
+    pub moves: usize,
+    pub tablebase_positions: Option<usize>,
+}
+
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct LookupData {
     #[serde_as(as = "Vec<(_, _)>")]
-    x: HashMap<Game, (usize, usize, usize)>,
+    x: HashMap<Game, LookupMoves>,
+}
+
+/// The optimal moves of a position. A position with a single optimal move is
+/// stored as just that move, the same as lookups written before every
+/// optimal move was kept.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum LookupMoves {
+    One((usize, usize, usize)),
+    Many(Vec<(usize, usize, usize)>),
+}
+
+impl LookupMoves {
+    fn new(mut moves: Vec<(usize, usize, usize)>) -> Self {
+        if moves.len() == 1 {
+            LookupMoves::One(moves.remove(0))
+        } else {
+            LookupMoves::Many(moves)
+        }
+    }
+
+    fn moves(&self) -> Vec<(usize, usize, usize)> {
+        match self {
+            LookupMoves::One(ijk) => vec![*ijk],
+            LookupMoves::Many(moves) => moves.clone(),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -350,7 +746,7 @@ struct Lookup {
 }
 
 impl Lookup {
-    fn write(self: &Self) {
+    fn write(&self) {
         let mut f = File::create(self.path.clone()).unwrap();
         f.write_all(&serde_json::to_string(&self.data).unwrap().into_bytes())
             .unwrap();
@@ -358,39 +754,22 @@ impl Lookup {
     }
 
     fn new_distinct(path: String) -> Self {
-        let f = File::open(&path.clone());
+        let f = File::open(&path);
         let data = match f {
             Ok(mut file) => {
                 let mut contents = String::new();
                 match file.read_to_string(&mut contents) {
-                    Ok(_) => serde_json::from_str(&contents.to_string()).unwrap(),
+                    Ok(_) => serde_json::from_str(&contents).unwrap(),
                     _ => LookupData { x: HashMap::new() },
                 }
             }
             _ => LookupData { x: HashMap::new() },
         };
-        Lookup {
-            data: data,
-            path: path,
-        }
+        Lookup { data, path }
     }
 
     fn new() -> Self {
-        let f = File::open("data/lookup.json");
-        let data = match f {
-            Ok(mut file) => {
-                let mut contents = String::new();
-                match file.read_to_string(&mut contents) {
-                    Ok(_) => serde_json::from_str(&contents.to_string()).unwrap(),
-                    _ => LookupData { x: HashMap::new() },
-                }
-            }
-            _ => LookupData { x: HashMap::new() },
-        };
-        Lookup {
-            data: data,
-            path: "data/lookup.json".to_string(),
-        }
+        Lookup::new_distinct("data/lookup.json".to_string())
     }
 }
 
@@ -0,0 +1,239 @@
+use crate::game::{Game, PlayerKind, Winner, LINES};
+use crate::symmetry::coordinates_to_index;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+// Pieces beyond this size are still searched, they just don't get a history
+const HISTORY_PIECES: usize = 16;
+const KILLER_PLIES: usize = 2 * HISTORY_PIECES + 1;
+// History saturates here, so that once shifted into the sort key it stays
+// below the bit for captures
+const HISTORY_MAX: u64 = (1 << 32) - 1;
+
+/// The heuristics used to sort the children of a position before they are
+/// searched. Ordering never changes the result of a search, only how much of
+/// the tree alpha-beta gets to prune.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveOrdering {
+    /// Moves that complete a line for the mover
+    pub wins: bool,
+    /// Moves onto the square the opponent needs to complete a line
+    pub blocks: bool,
+    /// Moves that cap one of the opponent's pieces
+    pub captures: bool,
+    /// The last move that caused a cutoff at the same ply
+    pub killers: bool,
+    /// Moves that have caused cutoffs anywhere in the tree, weighted by depth
+    pub history: bool,
+    /// Bigger pieces before smaller ones
+    pub largest_first: bool,
+}
+
+impl MoveOrdering {
+    pub fn none() -> Self {
+        Self {
+            wins: false,
+            blocks: false,
+            captures: false,
+            killers: false,
+            history: false,
+            largest_first: false,
+        }
+    }
+
+    pub fn all() -> Self {
+        Self {
+            wins: true,
+            blocks: true,
+            captures: true,
+            killers: true,
+            history: true,
+            largest_first: true,
+        }
+    }
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// Parses `all`, `none` or a comma separated list of heuristics, e.g.
+/// `wins,blocks,largest`
+impl FromStr for MoveOrdering {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => return Ok(Self::all()),
+            "none" => return Ok(Self::none()),
+            _ => (),
+        }
+
+        let mut ordering = Self::none();
+        for heuristic in s.split(',') {
+            match heuristic.trim() {
+                "wins" => ordering.wins = true,
+                "blocks" => ordering.blocks = true,
+                "captures" => ordering.captures = true,
+                "killers" => ordering.killers = true,
+                "history" => ordering.history = true,
+                "largest" => ordering.largest_first = true,
+                invalid => return Err(format!("Unknown move ordering heuristic '{}'", invalid)),
+            }
+        }
+        Ok(ordering)
+    }
+}
+
+/// Killer moves and the history table, shared by every thread of a search
+pub(crate) struct Heuristics {
+    killers: Vec<AtomicUsize>,
+    history: Vec<AtomicU64>,
+}
+
+impl Heuristics {
+    pub(crate) fn new() -> Self {
+        Self {
+            killers: (0..KILLER_PLIES).map(|_| AtomicUsize::new(0)).collect(),
+            history: (0..2 * 9 * HISTORY_PIECES)
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+        }
+    }
+
+    /// Forgets the killers and halves the history before a new search, so
+    /// that what was learned about earlier positions fades instead of piling
+    /// up for the rest of the game
+    pub(crate) fn age(&self) {
+        for killer in &self.killers {
+            killer.store(0, Ordering::Relaxed);
+        }
+        for history in &self.history {
+            let _ = history.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |h| Some(h / 2));
+        }
+    }
+
+    /// How often `(i, j, k)` has caused a cutoff for `kind`, weighted by
+    /// depth
+    pub(crate) fn history(&self, kind: PlayerKind, (i, j, k): (usize, usize, usize)) -> u64 {
+        history_index(kind, i, j, k).map_or(0, |index| self.history[index].load(Ordering::Relaxed))
+    }
+
+    /// Remember a move that caused a cutoff in `game`
+    pub(crate) fn record_cutoff(&self, game: &Game, (i, j, k): (usize, usize, usize)) {
+        let plies = plies_remaining(game);
+        if plies < KILLER_PLIES {
+            self.killers[plies].store(pack(i, j, k), Ordering::Relaxed);
+        }
+        if let Some(index) = history_index(game.current_player_kind(), i, j, k) {
+            let bonus = (plies * plies) as u64;
+            let _ = self.history[index].fetch_update(Ordering::Relaxed, Ordering::Relaxed, |h| {
+                Some((h + bonus).min(HISTORY_MAX))
+            });
+        }
+    }
+
+    /// Sorts `children` of `game` so the most promising moves come first
+    pub(crate) fn order(
+        &self,
+        ordering: &MoveOrdering,
+        game: &Game,
+        children: &mut [((usize, usize, usize), Game)],
+    ) {
+        if *ordering == MoveOrdering::none() {
+            return;
+        }
+
+        let kind = game.current_player_kind();
+        let their_kind = match kind {
+            PlayerKind::X => PlayerKind::O,
+            PlayerKind::O => PlayerKind::X,
+        };
+        let threats = if ordering.blocks {
+            threats(game, their_kind)
+        } else {
+            [false; 9]
+        };
+        let plies = plies_remaining(game);
+        let killer = if ordering.killers && plies < KILLER_PLIES {
+            self.killers[plies].load(Ordering::Relaxed)
+        } else {
+            0
+        };
+
+        children.sort_by_cached_key(|((i, j, k), child)| {
+            let mut score: u64 = 0;
+            if ordering.wins {
+                let won = matches!(
+                    (child.winner(), kind),
+                    (Some(Winner::X), PlayerKind::X) | (Some(Winner::O), PlayerKind::O)
+                );
+                if won {
+                    score += 1 << 40;
+                }
+            }
+            if ordering.blocks && threats[coordinates_to_index((*i, *j))] {
+                score += 1 << 39;
+            }
+            if ordering.killers && killer == pack(*i, *j, *k) {
+                score += 1 << 38;
+            }
+            if ordering.captures {
+                if let Some((owner, _)) = game.tiles[*i][*j] {
+                    if owner == their_kind {
+                        score += 1 << 37;
+                    }
+                }
+            }
+            if ordering.history {
+                score += self.history(kind, (*i, *j, *k)) << 5;
+            }
+            if ordering.largest_first {
+                score += *k as u64;
+            }
+            std::cmp::Reverse(score)
+        });
+    }
+}
+
+/// Squares that would complete a line for `kind` if they owned them
+pub(crate) fn threats(game: &Game, kind: PlayerKind) -> [bool; 9] {
+    let owner = |index: usize| game.tiles.data[index].map(|(owner, _)| owner);
+    let mut threats = [false; 9];
+    for line in LINES.iter() {
+        for (n, square) in line.iter().enumerate() {
+            let others = line
+                .iter()
+                .enumerate()
+                .filter(|(m, _)| *m != n)
+                .all(|(_, other)| owner(*other) == Some(kind));
+            if others && owner(*square) != Some(kind) {
+                threats[*square] = true;
+            }
+        }
+    }
+    threats
+}
+
+fn plies_remaining(game: &Game) -> usize {
+    let (x, o) = &game.players;
+    x.pieces.len() + o.pieces.len()
+}
+
+// Zero is reserved for "no killer"
+fn pack(i: usize, j: usize, k: usize) -> usize {
+    k * 9 + coordinates_to_index((i, j)) + 1
+}
+
+fn history_index(kind: PlayerKind, i: usize, j: usize, k: usize) -> Option<usize> {
+    if k >= HISTORY_PIECES {
+        return None;
+    }
+    let kind = match kind {
+        PlayerKind::X => 0,
+        PlayerKind::O => 1,
+    };
+    Some((kind * 9 + coordinates_to_index((i, j))) * HISTORY_PIECES + k)
+}
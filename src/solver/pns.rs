@@ -0,0 +1,259 @@
+use super::{BestMove, Solver};
+use crate::game::{Game, Move, PlayerKind, Winner};
+use std::collections::HashMap;
+use std::sync::atomic;
+
+const INFINITY: u32 = u32::MAX;
+
+/// How many positions the table holds by default before the least searched
+/// half of it is forgotten
+pub(super) const TABLE_CAPACITY: usize = 1 << 21;
+
+/// Proof and disproof numbers: how many more positions would have to be
+/// solved to prove, or to disprove, that the attacker wins
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Numbers {
+    proof: u32,
+    disproof: u32,
+}
+
+const PROVEN: Numbers = Numbers {
+    proof: 0,
+    disproof: INFINITY,
+};
+
+const DISPROVEN: Numbers = Numbers {
+    proof: INFINITY,
+    disproof: 0,
+};
+
+const UNKNOWN: Numbers = Numbers {
+    proof: 1,
+    disproof: 1,
+};
+
+/// Depth-first proof-number search. It only decides whether `attacker` can
+/// force a win and keeps two numbers for at most `capacity` positions, so it
+/// needs far less memory than a full solve. When the table fills up, the
+/// positions with the smallest subtrees are dropped, as they are the
+/// quickest to search again. The game tree has no cycles, since every move
+/// uses up a piece, so none of df-pn's usual cycle handling is needed.
+struct ProofNumberSearch<'a> {
+    solver: &'a Solver,
+    attacker: PlayerKind,
+    capacity: usize,
+    /// The numbers of each position and how many nodes were searched below it
+    table: HashMap<u128, (Numbers, u64)>,
+    /// The moves from the root and their numbers, as of the last time the
+    /// root was searched. The table may have forgotten them since.
+    root_moves: Vec<(Move, Numbers)>,
+}
+
+// The search only starts from positions that fit in a key, and pieces only
+// get used up, so every position below the root fits too
+fn key(game: &Game) -> u128 {
+    game.canonical_key().unwrap()
+}
+
+impl<'a> ProofNumberSearch<'a> {
+    fn new(solver: &'a Solver, attacker: PlayerKind, capacity: usize) -> Self {
+        Self {
+            solver,
+            attacker,
+            capacity,
+            table: HashMap::new(),
+            root_moves: Vec::new(),
+        }
+    }
+
+    fn store(&mut self, key: u128, numbers: Numbers, work: u64) {
+        if self.table.len() >= self.capacity && !self.table.contains_key(&key) {
+            let mut works = self
+                .table
+                .values()
+                .map(|(_, work)| *work)
+                .collect::<Vec<u64>>();
+            let middle = works.len() / 2;
+            let (_, median, _) = works.select_nth_unstable(middle);
+            let median = *median;
+            self.table.retain(|_, (_, work)| *work > median);
+        }
+        self.table.insert(key, (numbers, work));
+    }
+
+    fn numbers(&self, game: &Game) -> Numbers {
+        match (game.winner(), self.attacker) {
+            (Some(Winner::X), PlayerKind::X) | (Some(Winner::O), PlayerKind::O) => PROVEN,
+            (Some(_), _) => DISPROVEN,
+            _ => self
+                .table
+                .get(&key(game))
+                .map_or(UNKNOWN, |(numbers, _)| *numbers),
+        }
+    }
+
+    /// Whether `attacker` can force a win from `game`
+    fn prove(&mut self, game: &Game) -> bool {
+        self.mid(game, INFINITY, INFINITY, true);
+        self.numbers(game).proof == 0
+    }
+
+    // Multiple iterative deepening: keep expanding the most-proving child of
+    // `game` until its numbers reach either threshold
+    fn mid(&mut self, game: &Game, proof_threshold: u32, disproof_threshold: u32, root: bool) {
+        let numbers = self.numbers(game);
+        if numbers.proof >= proof_threshold
+            || numbers.disproof >= disproof_threshold
+            || self.solver.stopped()
+        {
+            return;
+        }
+        let start = self.solver.nodes.fetch_add(1, atomic::Ordering::Relaxed);
+
+        // The attacker gets the solver's usual moves, but the defender gets
+        // every legal move, as a defence the search never tried could be the
+        // one that holds
+        let attacking = game.current_player_kind() == self.attacker;
+        let (moves, children): (Vec<Move>, Vec<Game>) = match (attacking, root) {
+            (true, true) => self.solver.root_children(game),
+            (true, false) => self.solver.children(game, self.attacker),
+            (false, _) => game.legal_moves(),
+        }
+        .into_iter()
+        .unzip();
+        let key = key(game);
+
+        // A player that can't move is stuck and the attacker hasn't won
+        if children.is_empty() {
+            self.store(key, DISPROVEN, 1);
+            return;
+        }
+
+        loop {
+            let child_numbers = children
+                .iter()
+                .map(|child| self.numbers(child))
+                .collect::<Vec<Numbers>>();
+            // Whichever child settled the root was searched last, so it is
+            // still in the table here
+            if root {
+                self.root_moves = moves.iter().copied().zip(child_numbers.clone()).collect();
+            }
+
+            // At the attacker's turn one proven child is enough, at the
+            // defender's turn every child has to be proven
+            let (minimum, sum) = if attacking {
+                (
+                    child_numbers.iter().map(|n| n.proof).min().unwrap(),
+                    sum(child_numbers.iter().map(|n| n.disproof)),
+                )
+            } else {
+                (
+                    child_numbers.iter().map(|n| n.disproof).min().unwrap(),
+                    sum(child_numbers.iter().map(|n| n.proof)),
+                )
+            };
+            let numbers = if attacking {
+                Numbers {
+                    proof: minimum,
+                    disproof: sum,
+                }
+            } else {
+                Numbers {
+                    proof: sum,
+                    disproof: minimum,
+                }
+            };
+            let work = self.solver.nodes.load(atomic::Ordering::Relaxed) - start;
+            self.store(key, numbers, work);
+
+            if numbers.proof >= proof_threshold
+                || numbers.disproof >= disproof_threshold
+                || self.solver.stopped()
+            {
+                return;
+            }
+
+            // The most-proving child is the one with the smallest proof number
+            // at the attacker's turn, or disproof number at the defender's
+            let select = |n: &Numbers| if attacking { n.proof } else { n.disproof };
+            let mut order = (0..children.len()).collect::<Vec<usize>>();
+            order.sort_by_key(|i| select(&child_numbers[*i]));
+            let best = order[0];
+            let second = order
+                .get(1)
+                .map(|i| select(&child_numbers[*i]))
+                .unwrap_or(INFINITY);
+            let best_numbers = child_numbers[best];
+
+            if attacking {
+                self.mid(
+                    &children[best],
+                    proof_threshold.min(second.saturating_add(1)),
+                    (disproof_threshold - numbers.disproof).saturating_add(best_numbers.disproof),
+                    false,
+                );
+            } else {
+                self.mid(
+                    &children[best],
+                    (proof_threshold - numbers.proof).saturating_add(best_numbers.proof),
+                    disproof_threshold.min(second.saturating_add(1)),
+                    false,
+                );
+            }
+        }
+    }
+}
+
+// Only an infinite number makes the sum infinite. Finite sums stop one
+// short of it, so a large sum is never mistaken for a proof.
+fn sum<I: Iterator<Item = u32>>(numbers: I) -> u32 {
+    numbers.fold(0, |sum, n| {
+        if sum == INFINITY || n == INFINITY {
+            INFINITY
+        } else {
+            sum.checked_add(n)
+                .map_or(INFINITY - 1, |sum| sum.min(INFINITY - 1))
+        }
+    })
+}
+
+impl Solver {
+    /// Every move the search proves to be best: the moves that force a win
+    /// if there are any, otherwise the moves that the opponent can't force a
+    /// win against, otherwise the game is lost and every move is as bad.
+    /// Nothing for a position that doesn't fit in a key.
+    pub(super) fn proof_number_search(&self, game: &Game) -> Vec<BestMove> {
+        let children = self.root_children(game);
+        if children.is_empty() || game.key().is_err() {
+            return Vec::new();
+        }
+
+        let mut attack = ProofNumberSearch::new(self, self.kind, self.proof_table);
+        let mut moves = if attack.prove(game) {
+            attack
+                .root_moves
+                .iter()
+                .filter(|(_, numbers)| numbers.proof == 0)
+                .map(|(b_move, _)| BestMove {
+                    b_move: *b_move,
+                    score: 10,
+                })
+                .collect::<Vec<BestMove>>()
+        } else {
+            let mut defence = ProofNumberSearch::new(self, self.their_kind(), self.proof_table);
+            let lost = defence.prove(game);
+            defence
+                .root_moves
+                .iter()
+                .filter(|(_, numbers)| lost || numbers.disproof == 0)
+                .map(|(b_move, _)| BestMove {
+                    b_move: *b_move,
+                    score: if lost { -10 } else { 0 },
+                })
+                .collect::<Vec<BestMove>>()
+        };
+        moves.sort_by_key(|best| best.b_move);
+        moves
+    }
+}
@@ -0,0 +1,280 @@
+use crate::game::{Game, PlayerKind, Winner};
+use crate::state_space::reachable_layers;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+const MAGIC: &[u8; 4] = b"MTTT";
+const VERSION: u8 = 2;
+
+// The score for whoever's turn it is, then how much they like the length of
+// the game
+type Rank = (i8, i16);
+
+/// The value of a position under perfect play. Unlike the solver, whose
+/// scores are for the player it plays, the score here is absolute: it
+/// doesn't depend on whose turn it is, so an entry is the same whichever
+/// side looks it up. `score_for` converts it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    /// 10 if o wins, -10 if x wins and 0 for a tie
+    pub score: i8,
+    /// Number of moves left when the winner wins as quickly as they can and
+    /// the loser holds out as long as they can. For a tie, the longest game
+    /// where neither player gives up the tie.
+    pub depth: u8,
+}
+
+impl Entry {
+    /// The score for `kind`, the way the solver scores: 10 if `kind` wins,
+    /// -10 if it loses and 0 for a tie
+    pub fn score_for(&self, kind: PlayerKind) -> i8 {
+        match kind {
+            PlayerKind::O => self.score,
+            PlayerKind::X => -self.score,
+        }
+    }
+}
+
+/// The result of perfect play from the empty board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Proof {
+    pub number_of_pieces: usize,
+    /// x moves first, so `Winner::X` is a first-player win
+    pub winner: Winner,
+    /// Moves in the longest game where neither player ever makes a mistake
+    pub length: usize,
+}
+
+/// Every reachable position of an N piece game, solved by retrograde
+/// analysis. Since every move uses up a piece, the positions form layers by
+/// the number of moves played and each layer is solved from the one below.
+pub struct Tablebase {
+    number_of_pieces: usize,
+    entries: HashMap<u128, Entry>,
+}
+
+impl Tablebase {
+    pub fn generate(number_of_pieces: usize) -> Self {
+        Self::generate_with_progress(number_of_pieces, |_, _| ())
+    }
+
+    /// As `generate`, calling `progress` with the ply and the number of
+    /// positions in it as each layer is enumerated and again as it is solved
+    pub fn generate_with_progress<F>(number_of_pieces: usize, progress: F) -> Self
+    where
+        F: Fn(usize, usize),
+    {
+        let layers = reachable_layers(number_of_pieces, &progress);
+
+        let mut entries: HashMap<u128, Entry> = HashMap::new();
+        for (ply, layer) in layers.iter().enumerate().rev() {
+            let solved = layer
+                .par_iter()
+                .map(|key| (*key, solve(&Game::from_key(*key), &entries)))
+                .collect::<Vec<(u128, Entry)>>();
+            progress(ply, solved.len());
+            entries.extend(solved);
+        }
+
+        Self {
+            number_of_pieces,
+            entries,
+        }
+    }
+
+    pub fn number_of_pieces(&self) -> usize {
+        self.number_of_pieces
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether `game` is a position from a game with this many pieces
+    pub fn covers(&self, game: &Game) -> bool {
+        game.get_number_of_pieces() == self.number_of_pieces
+    }
+
+    pub fn proof(&self) -> Proof {
+        let entry = self
+            .get(&Game::new_with_size(self.number_of_pieces))
+            .expect("A tablebase always contains the empty board");
+        Proof {
+            number_of_pieces: self.number_of_pieces,
+            winner: match entry.score {
+                10 => Winner::O,
+                -10 => Winner::X,
+                _ => Winner::Tie,
+            },
+            length: entry.depth as usize,
+        }
+    }
+
+    /// Nothing for positions from other games, including any that don't
+    /// fit in a key
+    pub fn get(&self, game: &Game) -> Option<Entry> {
+        let key = game.canonical_key().ok()?;
+        self.entries.get(&key).copied()
+    }
+
+    /// The best move for whoever's turn it is, preferring the quickest win
+    /// and the slowest loss
+    pub fn best_move(&self, game: &Game) -> Option<(usize, usize, usize)> {
+        self.rank_moves(game)
+            .into_iter()
+            .max_by_key(|(m, rank)| (*rank, *m))
+            .map(|(m, _)| m)
+    }
+
+    /// Every move that keeps the score of the position
+    pub fn best_moves(&self, game: &Game) -> Vec<(usize, usize, usize)> {
+        let entry = match self.get(game) {
+            Some(entry) => entry,
+            None => return Vec::new(),
+        };
+        game.legal_moves()
+            .into_iter()
+            .filter(|(_, child)| self.get(child).map(|e| e.score) == Some(entry.score))
+            .map(|(m, _)| m)
+            .collect()
+    }
+
+    fn rank_moves(&self, game: &Game) -> Vec<((usize, usize, usize), Rank)> {
+        let kind = game.current_player_kind();
+        game.legal_moves()
+            .into_iter()
+            .filter_map(|(m, child)| {
+                self.get(&child).map(|entry| {
+                    let score = entry.score_for(kind);
+                    let depth = entry.depth as i16;
+                    (m, (score, if score > 0 { -depth } else { depth }))
+                })
+            })
+            .collect()
+    }
+
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        let mut keys = self.entries.keys().cloned().collect::<Vec<u128>>();
+        keys.sort_unstable();
+
+        let mut f = BufWriter::new(File::create(path)?);
+        f.write_all(MAGIC)?;
+        f.write_all(&[VERSION, self.number_of_pieces as u8])?;
+        f.write_all(&(keys.len() as u64).to_le_bytes())?;
+        for key in keys {
+            let entry = self.entries[&key];
+            f.write_all(&key.to_le_bytes())?;
+            f.write_all(&[entry.score as u8, entry.depth])?;
+        }
+        f.flush()?;
+        f.get_ref().sync_all()
+    }
+
+    pub fn read(path: &str) -> io::Result<Self> {
+        let mut f = BufReader::new(File::open(path)?);
+
+        let mut header = [0; 14];
+        f.read_exact(&mut header)?;
+        if &header[0..4] != MAGIC || header[4] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a version {} tablebase", path, VERSION),
+            ));
+        }
+        let number_of_pieces = header[5] as usize;
+        let mut len = [0; 8];
+        len.copy_from_slice(&header[6..14]);
+        let len = u64::from_le_bytes(len);
+
+        // The count is only trusted as far as the file holds that many
+        // entries, so a corrupt one can't reserve more memory than it takes
+        let size = f.get_ref().metadata()?.len();
+        if len.checked_mul(18).and_then(|n| n.checked_add(14)) != Some(size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is {} bytes, not {} entries", path, size, len),
+            ));
+        }
+        let len = len as usize;
+
+        let mut entries = HashMap::with_capacity(len);
+        let mut record = [0; 18];
+        for _ in 0..len {
+            f.read_exact(&mut record)?;
+            let mut key = [0; 16];
+            key.copy_from_slice(&record[0..16]);
+            entries.insert(
+                u128::from_le_bytes(key),
+                Entry {
+                    score: record[16] as i8,
+                    depth: record[17],
+                },
+            );
+        }
+
+        Ok(Self {
+            number_of_pieces,
+            entries,
+        })
+    }
+}
+
+// A player that can't move is stuck, and `Game` has no way to pass, so the
+// game can't go on and is scored as a tie
+fn solve(game: &Game, solved: &HashMap<u128, Entry>) -> Entry {
+    match game.winner() {
+        Some(Winner::X) => {
+            return Entry {
+                score: -10,
+                depth: 0,
+            }
+        }
+        Some(Winner::O) => {
+            return Entry {
+                score: 10,
+                depth: 0,
+            }
+        }
+        Some(Winner::Tie) => return Entry { score: 0, depth: 0 },
+        None => (),
+    }
+
+    let children = game
+        .legal_moves()
+        .iter()
+        .map(|(_, child)| solved[&child.canonical_key().unwrap()])
+        .collect::<Vec<Entry>>();
+
+    let kind = game.current_player_kind();
+    let score = children.iter().map(|entry| entry.score_for(kind)).max();
+
+    match score {
+        Some(score) => {
+            let depths = children
+                .iter()
+                .filter(|entry| entry.score_for(kind) == score)
+                .map(|entry| entry.depth);
+            // The winner takes the quickest win, everyone else plays on as
+            // long as they can
+            let depth = if score > 0 {
+                depths.min()
+            } else {
+                depths.max()
+            };
+            Entry {
+                score: match kind {
+                    PlayerKind::O => score,
+                    PlayerKind::X => -score,
+                },
+                depth: 1 + depth.unwrap(),
+            }
+        }
+        None => Entry { score: 0, depth: 0 },
+    }
+}
@@ -0,0 +1,49 @@
+extern crate getopts;
+use getopts::Options;
+use matryoshka_tic_tac_toe::game::MAX_PIECES;
+use matryoshka_tic_tac_toe::state_space;
+use std::env;
+use std::time::SystemTime;
+
+fn print_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} [options]", program);
+    println!("{}", opts.usage(&brief));
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+    let mut number = 4;
+    let mut csv = None;
+
+    let mut opts = Options::new();
+    opts.optopt("n", "number", "How many pieces to count", "PIECES");
+    opts.optopt("c", "csv", "Also write the table as CSV", "FILE");
+
+    opts.optflag("h", "help", "print this help menu");
+    if let Ok(m) = opts.parse(&args[1..]) {
+        if m.opt_present("h") {
+            print_usage(&program, opts);
+            return;
+        }
+        if let Ok(Some(n)) = m.opt_get::<usize>("n") {
+            number = n;
+        }
+        csv = m.opt_str("c");
+    };
+
+    if number > MAX_PIECES {
+        eprintln!("At most {} pieces fit in a position key", MAX_PIECES);
+        return;
+    }
+
+    let now = SystemTime::now();
+    let counts = state_space::count(number);
+    println!("Reachable positions with {} pieces", number);
+    state_space::print(&counts);
+    println!("{}s", now.elapsed().unwrap().as_secs());
+
+    if let Some(path) = csv {
+        state_space::write_csv(&counts, &path).unwrap();
+    }
+}
@@ -0,0 +1,205 @@
+use crate::game::{Game, Winner};
+use crate::symmetry::Symmetry;
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Write};
+use strum::IntoEnumIterator;
+
+/// Every position reachable in an N piece game, up to symmetry, grouped by
+/// the number of moves played. Calls `progress` with the ply and the number
+/// of positions in it as each layer is found. Panics with more than
+/// `MAX_PIECES` pieces, which don't fit in a key.
+pub fn reachable_layers<F>(number_of_pieces: usize, progress: F) -> Vec<HashSet<u128>>
+where
+    F: Fn(usize, usize),
+{
+    let mut layers: Vec<HashSet<u128>> = vec![[Game::new_with_size(number_of_pieces)
+        .canonical_key()
+        .unwrap_or_else(|e| panic!("{}", e))]
+    .iter()
+    .cloned()
+    .collect()];
+
+    loop {
+        let next = layers
+            .last()
+            .unwrap()
+            .par_iter()
+            .fold(HashSet::new, |mut next, key| {
+                for (_, child) in Game::from_key(*key).legal_moves() {
+                    // Pieces only get used up, so a child fits if its parent does
+                    next.insert(child.canonical_key().unwrap());
+                }
+                next
+            })
+            .reduce(HashSet::new, |mut a, b| {
+                if a.len() < b.len() {
+                    return b.into_iter().chain(a).collect();
+                }
+                a.extend(b);
+                a
+            });
+        progress(layers.len(), next.len());
+        if next.is_empty() {
+            break;
+        }
+        layers.push(next);
+    }
+    layers
+}
+
+/// The positions after a number of moves
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlyCount {
+    pub ply: usize,
+    pub positions: u64,
+    /// Positions left after removing rotations and reflections
+    pub canonical: u64,
+    pub x_wins: u64,
+    pub o_wins: u64,
+    pub ties: u64,
+    /// Unfinished positions where the player to move has no legal move
+    pub stuck: u64,
+    /// Legal moves summed over every unfinished position
+    pub moves: u64,
+}
+
+impl PlyCount {
+    pub fn terminal(&self) -> u64 {
+        self.x_wins + self.o_wins + self.ties + self.stuck
+    }
+
+    /// Average number of legal moves from the unfinished positions
+    pub fn branching_factor(&self) -> f64 {
+        let open = self.positions - self.x_wins - self.o_wins - self.ties;
+        if open == 0 {
+            0.0
+        } else {
+            self.moves as f64 / open as f64
+        }
+    }
+
+    fn add(mut self, other: Self) -> Self {
+        self.positions += other.positions;
+        self.canonical += other.canonical;
+        self.x_wins += other.x_wins;
+        self.o_wins += other.o_wins;
+        self.ties += other.ties;
+        self.stuck += other.stuck;
+        self.moves += other.moves;
+        self
+    }
+}
+
+/// Counts the reachable positions of an N piece game ply by ply. Only one
+/// position of each symmetry class is enumerated, the raw counts weight it by
+/// the number of distinct positions it stands for.
+pub fn count(number_of_pieces: usize) -> Vec<PlyCount> {
+    reachable_layers(number_of_pieces, |_, _| ())
+        .iter()
+        .enumerate()
+        .map(|(ply, layer)| {
+            layer
+                .par_iter()
+                .map(|key| {
+                    let game = Game::from_key(*key);
+                    let orbit = Symmetry::iter()
+                        .map(|symmetry| game.fliptate(&symmetry).key().unwrap())
+                        .collect::<HashSet<u128>>()
+                        .len() as u64;
+
+                    let mut count = PlyCount {
+                        ply,
+                        positions: orbit,
+                        canonical: 1,
+                        ..PlyCount::default()
+                    };
+                    match game.winner() {
+                        Some(Winner::X) => count.x_wins = orbit,
+                        Some(Winner::O) => count.o_wins = orbit,
+                        Some(Winner::Tie) => count.ties = orbit,
+                        None => {
+                            let moves = game.legal_moves().len() as u64;
+                            if moves == 0 {
+                                count.stuck = orbit;
+                            }
+                            count.moves = orbit * moves;
+                        }
+                    }
+                    count
+                })
+                .reduce(
+                    || PlyCount {
+                        ply,
+                        ..PlyCount::default()
+                    },
+                    PlyCount::add,
+                )
+        })
+        .collect()
+}
+
+pub fn total(counts: &[PlyCount]) -> PlyCount {
+    counts
+        .iter()
+        .cloned()
+        .fold(PlyCount::default(), PlyCount::add)
+}
+
+const COLUMNS: [&str; 8] = [
+    "ply",
+    "positions",
+    "canonical",
+    "x_wins",
+    "o_wins",
+    "ties",
+    "stuck",
+    "branching_factor",
+];
+
+pub fn print(counts: &[PlyCount]) {
+    for column in COLUMNS.iter() {
+        print!(" {:>16}", column);
+    }
+    println!();
+
+    for count in counts.iter() {
+        print_row(&count.ply.to_string(), count);
+    }
+    print_row("total", &total(counts));
+}
+
+fn print_row(ply: &str, count: &PlyCount) {
+    println!(
+        " {:>16} {:>16} {:>16} {:>16} {:>16} {:>16} {:>16} {:>16.2}",
+        ply,
+        count.positions,
+        count.canonical,
+        count.x_wins,
+        count.o_wins,
+        count.ties,
+        count.stuck,
+        count.branching_factor()
+    );
+}
+
+pub fn write_csv(counts: &[PlyCount], path: &str) -> io::Result<()> {
+    let mut f = File::create(path)?;
+    writeln!(f, "{}", COLUMNS.join(","))?;
+    for count in counts.iter() {
+        writeln!(
+            f,
+            "{},{},{},{},{},{},{},{:.4}",
+            count.ply,
+            count.positions,
+            count.canonical,
+            count.x_wins,
+            count.o_wins,
+            count.ties,
+            count.stuck,
+            count.branching_factor()
+        )?;
+    }
+    f.sync_all()
+}
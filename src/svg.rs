@@ -0,0 +1,322 @@
+use crate::game::{Game, Move, Player, PlayerKind};
+use std::fmt::Write;
+
+const X_COLOUR: &str = "#d1495b";
+const O_COLOUR: &str = "#00798c";
+const INK: &str = "#30343f";
+const PAPER: &str = "#fbf8f1";
+
+/// Draws positions as SVG, every piece as nested circles, one for each size
+/// up to its own, coloured by its owner, and a stroke through a winning line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagram {
+    square: u32,
+    labels: bool,
+    hands: bool,
+    arrows: Vec<Move>,
+}
+
+impl Default for Diagram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Diagram {
+    /// 100 pixel squares, with coordinate labels and both hands
+    pub fn new() -> Self {
+        Self {
+            square: 100,
+            labels: true,
+            hands: true,
+            arrows: Vec::new(),
+        }
+    }
+
+    /// The width of a square in pixels
+    pub fn with_square(mut self, square: u32) -> Self {
+        self.square = square.max(20);
+        self
+    }
+
+    /// The row numbers and column letters, as in `2B1`
+    pub fn with_labels(mut self, labels: bool) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// The pieces each player has left, below the board
+    pub fn with_hands(mut self, hands: bool) -> Self {
+        self.hands = hands;
+        self
+    }
+
+    /// Moves to draw on top of the position, such as a line of play. Each
+    /// is numbered and outlined in the colour of whoever would play it,
+    /// starting with the player to move, and arrows join them in order.
+    pub fn with_arrows(mut self, moves: Vec<Move>) -> Self {
+        self.arrows = moves;
+        self
+    }
+
+    pub fn render(&self, game: &Game) -> String {
+        let s = self.square as f64;
+        let margin = if self.labels { s * 0.4 } else { s * 0.1 };
+        let board = 3.0 * s;
+        let hand_height = if self.hands { s * 0.8 } else { 0.0 };
+        let width = n(margin + board + s * 0.1);
+        let height = n(margin + board + s * 0.1 + hand_height);
+        let biggest = game.get_biggest_piece();
+        let centre = |row: usize, col: usize| {
+            (
+                margin + (col as f64 + 0.5) * s,
+                margin + (row as f64 + 0.5) * s,
+            )
+        };
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif">"#,
+            w = width,
+            h = height
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<defs><marker id="arrowhead" viewBox="0 0 10 10" refX="8" refY="5" markerWidth="6" markerHeight="6" orient="auto-start-reverse"><path d="M 0 0 L 10 5 L 0 10 z" fill="{}"/></marker></defs>"#,
+            INK
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<rect width="{}" height="{}" fill="{}"/>"#,
+            width, height, PAPER
+        )
+        .unwrap();
+
+        // The grid and its labels
+        for i in 1..3 {
+            let offset = margin + i as f64 * s;
+            writeln!(
+                svg,
+                r#"<line x1="{a}" y1="{m}" x2="{a}" y2="{e}" stroke="{ink}" stroke-width="{w}" stroke-linecap="round"/>"#,
+                a = n(offset),
+                m = n(margin),
+                e = n(margin + board),
+                ink = INK,
+                w = n(s * 0.04)
+            )
+            .unwrap();
+            writeln!(
+                svg,
+                r#"<line x1="{m}" y1="{a}" x2="{e}" y2="{a}" stroke="{ink}" stroke-width="{w}" stroke-linecap="round"/>"#,
+                a = n(offset),
+                m = n(margin),
+                e = n(margin + board),
+                ink = INK,
+                w = n(s * 0.04)
+            )
+            .unwrap();
+        }
+        if self.labels {
+            for i in 0..3 {
+                let (x, y) = centre(i, i);
+                writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="{}" text-anchor="middle" fill="{}">{}</text>"#,
+                    n(x),
+                    n(margin * 0.7),
+                    n(s * 0.22),
+                    INK,
+                    (b'A' + i as u8) as char
+                )
+                .unwrap();
+                writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="{}" text-anchor="middle" dominant-baseline="central" fill="{}">{}</text>"#,
+                    n(margin * 0.5),
+                    n(y),
+                    n(s * 0.22),
+                    INK,
+                    i + 1
+                )
+                .unwrap();
+            }
+        }
+
+        for row in 0..3 {
+            for col in 0..3 {
+                if let Some((kind, size)) = game.tiles()[row][col] {
+                    let (x, y) = centre(row, col);
+                    svg += &piece(x, y, s * 0.42, kind, size, biggest);
+                }
+            }
+        }
+
+        // A stroke through every completed line, past the pieces at each end
+        for [first, _, last] in game.winning_line() {
+            let ((x1, y1), (x2, y2)) = (centre(first.0, first.1), centre(last.0, last.1));
+            let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+            let (dx, dy) = ((x2 - x1) / length * s * 0.4, (y2 - y1) / length * s * 0.4);
+            writeln!(
+                svg,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}" stroke-linecap="round" opacity="0.8" class="winning-line"/>"#,
+                n(x1 - dx),
+                n(y1 - dy),
+                n(x2 + dx),
+                n(y2 + dy),
+                INK,
+                n(s * 0.08)
+            )
+            .unwrap();
+        }
+
+        // Outlines where the moves go, then arrows between them
+        let mut kind = game.current_player_kind();
+        let points = self
+            .arrows
+            .iter()
+            .map(|(row, col, size)| {
+                let (x, y) = centre(*row, *col);
+                let r = radius(s * 0.42, *size, biggest);
+                let point = (x, y, r, kind);
+                kind = other(kind);
+                point
+            })
+            .collect::<Vec<(f64, f64, f64, PlayerKind)>>();
+        for (i, &(x, y, r, kind)) in points.iter().enumerate() {
+            writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{}" r="{}" fill="none" stroke="{}" stroke-width="{}" stroke-dasharray="{} {}"/>"#,
+                n(x),
+                n(y),
+                n(r),
+                colour(kind),
+                n(s * 0.04),
+                n(s * 0.08),
+                n(s * 0.05)
+            )
+            .unwrap();
+            writeln!(
+                svg,
+                r#"<text x="{}" y="{}" font-size="{}" font-weight="bold" text-anchor="middle" dominant-baseline="central" fill="{}">{}</text>"#,
+                n(x + s * 0.3),
+                n(y - s * 0.3),
+                n(s * 0.18),
+                colour(kind),
+                i + 1
+            )
+            .unwrap();
+        }
+        for pair in points.windows(2) {
+            let ((x1, y1, r1, _), (x2, y2, r2, _)) = (pair[0], pair[1]);
+            let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+            // Moves on the same square have nothing to point at
+            if length < r1 + r2 {
+                continue;
+            }
+            let (dx, dy) = ((x2 - x1) / length, (y2 - y1) / length);
+            writeln!(
+                svg,
+                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{}" stroke-width="{}" marker-end="url(#arrowhead)"/>"#,
+                x1 + dx * r1,
+                y1 + dy * r1,
+                x2 - dx * r2,
+                y2 - dy * r2,
+                INK,
+                n(s * 0.03)
+            )
+            .unwrap();
+        }
+
+        if self.hands {
+            let (x, o) = &game.players;
+            for (i, player) in [x, o].iter().enumerate() {
+                svg += &hand(
+                    player,
+                    margin + i as f64 * board / 2.0,
+                    margin + board + s * 0.1 + hand_height / 2.0,
+                    s,
+                    biggest,
+                );
+            }
+        }
+
+        svg += "</svg>\n";
+        svg
+    }
+}
+
+// Circles inside each other from the biggest down, alternating between the
+// owner's colour and the paper
+fn piece(x: f64, y: f64, largest: f64, kind: PlayerKind, size: usize, biggest: usize) -> String {
+    let mut svg = String::new();
+    for ring in (0..=size).rev() {
+        let fill = match (size - ring) % 2 {
+            0 => colour(kind),
+            _ => PAPER,
+        };
+        writeln!(
+            svg,
+            r#"<circle cx="{}" cy="{}" r="{}" fill="{}" stroke="{}" stroke-width="{}"/>"#,
+            n(x),
+            n(y),
+            n(radius(largest, ring, biggest)),
+            fill,
+            colour(kind),
+            n(largest * 0.04)
+        )
+        .unwrap();
+    }
+    svg
+}
+
+// The pieces left in a hand, small, in a row starting at `left`
+fn hand(player: &Player, left: f64, y: f64, square: f64, biggest: usize) -> String {
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-size="{}" dominant-baseline="central" fill="{}">{}</text>"#,
+        n(left),
+        n(y),
+        n(square * 0.22),
+        colour(player.kind),
+        match player.kind {
+            PlayerKind::X => "x",
+            PlayerKind::O => "o",
+        }
+    )
+    .unwrap();
+    let step = square * 1.3 / (player.pieces.len().max(4) as f64);
+    for (i, size) in player.pieces.iter().enumerate() {
+        let x = left + square * 0.3 + (i as f64 + 0.5) * step;
+        svg += &piece(x, y, step * 0.45, player.kind, *size, biggest);
+    }
+    svg
+}
+
+// From a fifth of the largest for the smallest piece up to the largest
+fn radius(largest: f64, size: usize, biggest: usize) -> f64 {
+    let smallest = largest * 0.2;
+    smallest + (largest - smallest) * size as f64 / biggest.max(1) as f64
+}
+
+// Rounded, to keep the SVG short
+fn n(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+fn colour(kind: PlayerKind) -> &'static str {
+    match kind {
+        PlayerKind::X => X_COLOUR,
+        PlayerKind::O => O_COLOUR,
+    }
+}
+
+fn other(kind: PlayerKind) -> PlayerKind {
+    match kind {
+        PlayerKind::X => PlayerKind::O,
+        PlayerKind::O => PlayerKind::X,
+    }
+}
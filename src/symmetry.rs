@@ -1,5 +1,6 @@
-use crate::game::{Game, Tile};
+use crate::game::{Game, Move, Tile};
 use std::convert::TryInto;
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
 // These are ordered so they're most efficient when iterated through
@@ -10,11 +11,13 @@ pub enum Symmetry {
     Rotate90,
     Rotate180,
     Rotate270,
+    Transpose,
+    AntiTranspose,
     NoSymmetry,
 }
 
 impl Symmetry {
-    pub fn reverse(self: Self) -> Symmetry {
+    pub fn reverse(self) -> Symmetry {
         match self {
             Symmetry::Rotate90 => Symmetry::Rotate270,
             Symmetry::Rotate270 => Symmetry::Rotate90,
@@ -25,9 +28,7 @@ impl Symmetry {
 
 impl Game {
     pub fn symmetry_range(&self, symmetry: Symmetry) -> Vec<usize> {
-        if self.has_rotational_symmetry() {
-            return (0..2).collect::<Vec<usize>>();
-        } else if self.has_mirror_symmetry(symmetry) {
+        if self.has_rotational_symmetry() || self.has_mirror_symmetry(symmetry) {
             return (0..2).collect::<Vec<usize>>();
         }
 
@@ -56,7 +57,7 @@ impl Game {
             && self.tiles == self.fliptate(&Symmetry::Rotate270).tiles
     }
 
-    pub fn fliptate(self: &Self, symmetry: &Symmetry) -> Self {
+    pub fn fliptate(&self, symmetry: &Symmetry) -> Self {
         let f = match symmetry {
             Symmetry::NoSymmetry => noop,
             Symmetry::Rotate90 => rotate_coordinates_by_90,
@@ -64,6 +65,8 @@ impl Game {
             Symmetry::Rotate270 => rotate_coordinates_by_270,
             Symmetry::FlipH => flip_coordinates_horizontally,
             Symmetry::FlipV => flip_coordinates_vertically,
+            Symmetry::Transpose => transpose_coordinates,
+            Symmetry::AntiTranspose => anti_transpose_coordinates,
         };
 
         let mut after = self.clone();
@@ -76,6 +79,14 @@ impl Game {
         after.tiles.data = flipped.try_into().unwrap();
         after
     }
+
+    /// The smallest `Game::key` of the position under any of the eight
+    /// symmetries of the board, so equivalent positions share a key
+    pub fn canonical_key(&self) -> Result<u128, String> {
+        Symmetry::iter().try_fold(u128::MAX, |min, symmetry| {
+            Ok(min.min(self.fliptate(&symmetry).key()?))
+        })
+    }
 }
 
 pub fn fliptate_coordinates(c: usize, symmetry: &Symmetry) -> usize {
@@ -86,10 +97,22 @@ pub fn fliptate_coordinates(c: usize, symmetry: &Symmetry) -> usize {
         Symmetry::Rotate270 => rotate_coordinates_by_270,
         Symmetry::FlipH => flip_coordinates_horizontally,
         Symmetry::FlipV => flip_coordinates_vertically,
+        Symmetry::Transpose => transpose_coordinates,
+        Symmetry::AntiTranspose => anti_transpose_coordinates,
     };
     f(c)
 }
 
+/// Where `m`, a move on the board `fliptate(symmetry)` made, is played on the
+/// original board. `fliptate` moves the tile at `f(i)` to `i`, so a move at
+/// `i` on the flipped board is at `f(i)` on the original, through the same
+/// symmetry and not its reverse.
+pub fn unfliptate_move(m: Move, symmetry: &Symmetry) -> Move {
+    let (i, j, k) = m;
+    let (x, y) = index_to_coordinates(fliptate_coordinates(coordinates_to_index((i, j)), symmetry));
+    (x, y, k)
+}
+
 pub fn rotate_coordinates_by_90(c: usize) -> usize {
     match c {
         0 => 6,
@@ -100,7 +123,7 @@ pub fn rotate_coordinates_by_90(c: usize) -> usize {
         6 => 8,
         7 => 5,
         8 => 2,
-        _ => (c),
+        _ => c,
     }
 }
 
@@ -114,7 +137,7 @@ fn rotate_coordinates_by_180(c: usize) -> usize {
         6 => 2,
         7 => 1,
         8 => 0,
-        _ => (c),
+        _ => c,
     }
 }
 
@@ -128,7 +151,7 @@ fn rotate_coordinates_by_270(c: usize) -> usize {
         6 => 0,
         7 => 3,
         8 => 6,
-        _ => (c),
+        _ => c,
     }
 }
 
@@ -140,7 +163,7 @@ fn flip_coordinates_horizontally(c: usize) -> usize {
         6 => 0,
         7 => 1,
         8 => 2,
-        _ => (c),
+        _ => c,
     }
 }
 
@@ -152,7 +175,31 @@ fn flip_coordinates_vertically(c: usize) -> usize {
         5 => 3,
         6 => 8,
         8 => 6,
-        _ => (c),
+        _ => c,
+    }
+}
+
+fn transpose_coordinates(c: usize) -> usize {
+    match c {
+        1 => 3,
+        2 => 6,
+        3 => 1,
+        5 => 7,
+        6 => 2,
+        7 => 5,
+        _ => c,
+    }
+}
+
+fn anti_transpose_coordinates(c: usize) -> usize {
+    match c {
+        0 => 8,
+        1 => 5,
+        3 => 7,
+        5 => 1,
+        7 => 3,
+        8 => 0,
+        _ => c,
     }
 }
 
@@ -170,7 +217,7 @@ pub fn rotate_ij_by_90(c: (usize, usize)) -> (usize, usize) {
         (2, 0) => (2, 2),
         (2, 1) => (1, 2),
         (2, 2) => (0, 2),
-        _ => (c),
+        _ => c,
     }
 }
 
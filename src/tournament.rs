@@ -0,0 +1,304 @@
+use crate::agent::{play_match_moves, Agent, Random};
+use crate::game::{Game, Move, Winner};
+use crate::mcts::{Budget, Mcts};
+use crate::record::GameRecord;
+use crate::solver::Solver;
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// An agent and its settings, written as the kind of agent followed by
+/// `key=value` options: `solver`, `solver:difficulty=easy,ordering=none`,
+/// `mcts:playouts=500,rollout=random` or `random`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentSpec {
+    pub name: String,
+    kind: String,
+    options: Vec<(String, String)>,
+}
+
+impl FromStr for AgentSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, options) = match s.split_once(':') {
+            Some((kind, options)) => (kind, options),
+            None => (s, ""),
+        };
+        let options = options
+            .split(',')
+            .filter(|option| !option.is_empty())
+            .map(|option| match option.split_once('=') {
+                Some((key, value)) => Ok((key.to_string(), value.to_string())),
+                None => Err(format!("Expected key=value, not '{}'", option)),
+            })
+            .collect::<Result<Vec<(String, String)>, String>>()?;
+
+        let spec = Self {
+            name: s.to_string(),
+            kind: kind.to_string(),
+            options,
+        };
+        // Catch mistakes before any games are played
+        spec.build(0)?;
+        Ok(spec)
+    }
+}
+
+impl AgentSpec {
+    pub fn build(&self, seed: u64) -> Result<Box<dyn Agent>, String> {
+        match self.kind.as_str() {
+            "solver" => {
+                let mut solver = Solver::new().with_seed(seed);
+                for (key, value) in self.options.iter() {
+                    solver = match key.as_str() {
+                        "difficulty" => solver.with_difficulty(value.parse()?),
+                        "ordering" => solver.with_ordering(value.parse()?),
+                        "algorithm" => solver.with_algorithm(value.parse()?),
+                        _ => return Err(format!("Unknown solver option '{}'", key)),
+                    };
+                }
+                Ok(Box::new(solver))
+            }
+            "mcts" => {
+                let mut mcts = Mcts::new().with_seed(seed);
+                for (key, value) in self.options.iter() {
+                    let number = || {
+                        value
+                            .parse::<u64>()
+                            .map_err(|_| format!("Expected a number for {}, not '{}'", key, value))
+                    };
+                    mcts = match key.as_str() {
+                        "playouts" => mcts.with_budget(Budget::Playouts(number()? as usize)),
+                        "time" => mcts.with_budget(Budget::Time(Duration::from_millis(number()?))),
+                        "rollout" => mcts.with_rollout(value.parse()?),
+                        _ => return Err(format!("Unknown mcts option '{}'", key)),
+                    };
+                }
+                Ok(Box::new(mcts))
+            }
+            "random" => Ok(Box::new(Random::with_seed(seed))),
+            kind => Err(format!(
+                "Unknown agent '{}', expected solver, mcts or random",
+                kind
+            )),
+        }
+    }
+}
+
+/// Who plays who
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Every agent plays every other agent
+    RoundRobin,
+    /// The first agent plays every other agent, who don't play each other
+    Gauntlet,
+}
+
+/// Wins, draws and losses from one agent's point of view. A game that is cut
+/// short counts as a draw.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Score {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points per game, a win being worth 1 and a draw a half. An even 0.5
+    /// before any games are played.
+    pub fn fraction(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + 0.5 * self.draws as f64) / self.games() as f64
+    }
+
+    /// The Elo difference against the opposition implied by the score, with
+    /// the bounds of a 95% confidence interval. A perfect or a zero score
+    /// has no finite estimate and is clamped to ±800, and no games at all
+    /// give 0 with the widest interval.
+    pub fn elo(&self) -> (f64, f64, f64) {
+        if self.games() == 0 {
+            return (0.0, -800.0, 800.0);
+        }
+        let n = self.games() as f64;
+        let p = self.fraction();
+        let variance = (self.wins as f64 * (1.0 - p).powi(2)
+            + self.draws as f64 * (0.5 - p).powi(2)
+            + self.losses as f64 * p.powi(2))
+            / n;
+        let margin = 1.96 * (variance / n).sqrt();
+        (elo(p), elo(p - margin), elo(p + margin))
+    }
+
+    fn add(&mut self, other: &Score) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+
+    fn flipped(&self) -> Score {
+        Score {
+            wins: self.losses,
+            draws: self.draws,
+            losses: self.wins,
+        }
+    }
+}
+
+fn elo(fraction: f64) -> f64 {
+    if fraction <= 0.0 {
+        return -800.0;
+    }
+    if fraction >= 1.0 {
+        return 800.0;
+    }
+    (-400.0 * (1.0 / fraction - 1.0).log10()).clamp(-800.0, 800.0)
+}
+
+pub struct Tournament {
+    pub agents: Vec<AgentSpec>,
+    pub format: Format,
+    pub number_of_pieces: usize,
+    /// Games per pairing, each opening is played twice with the colours swapped
+    pub games: usize,
+    /// Random moves played before the agents take over, so games between
+    /// deterministic agents don't all repeat
+    pub opening_plies: usize,
+    pub seed: u64,
+}
+
+/// The score of every agent against every other, `table[a][b]` being a's
+/// score against b
+pub struct Results {
+    pub names: Vec<String>,
+    pub table: Vec<Vec<Score>>,
+}
+
+impl Results {
+    pub fn total(&self, agent: usize) -> Score {
+        let mut total = Score::default();
+        for score in self.table[agent].iter() {
+            total.add(score);
+        }
+        total
+    }
+}
+
+impl Tournament {
+    pub fn pairings(&self) -> Vec<(usize, usize)> {
+        let n = self.agents.len();
+        match self.format {
+            Format::RoundRobin => (0..n)
+                .flat_map(|a| (a + 1..n).map(move |b| (a, b)))
+                .collect(),
+            Format::Gauntlet => (1..n).map(|b| (0, b)).collect(),
+        }
+    }
+
+    /// Plays every pairing, calling `on_game` with the record of each game as
+    /// it finishes
+    pub fn run<F>(&self, mut on_game: F) -> Result<Results, String>
+    where
+        F: FnMut(&GameRecord),
+    {
+        let n = self.agents.len();
+        let mut table = vec![vec![Score::default(); n]; n];
+        let mut rng = SmallRng::seed_from_u64(self.seed);
+        let mut seed = self.seed;
+
+        for (a, b) in self.pairings() {
+            let mut agent_a = self.agents[a].build(seed)?;
+            let mut agent_b = self.agents[b].build(seed + 1)?;
+            seed += 2;
+
+            let mut opening = Vec::new();
+            for game in 0..self.games {
+                if game % 2 == 0 {
+                    opening = random_opening(self.number_of_pieces, self.opening_plies, &mut rng);
+                }
+                let a_is_x = game % 2 == 0;
+                let record = if a_is_x {
+                    play(self, &opening, (a, agent_a.as_mut()), (b, agent_b.as_mut()))?
+                } else {
+                    play(self, &opening, (b, agent_b.as_mut()), (a, agent_a.as_mut()))?
+                };
+
+                let x_score = match record.winner {
+                    Some(Winner::X) => Score {
+                        wins: 1,
+                        ..Score::default()
+                    },
+                    Some(Winner::O) => Score {
+                        losses: 1,
+                        ..Score::default()
+                    },
+                    _ => Score {
+                        draws: 1,
+                        ..Score::default()
+                    },
+                };
+                let a_score = if a_is_x { x_score } else { x_score.flipped() };
+                table[a][b].add(&a_score);
+                table[b][a].add(&a_score.flipped());
+                on_game(&record);
+            }
+        }
+
+        Ok(Results {
+            names: self.agents.iter().map(|spec| spec.name.clone()).collect(),
+            table,
+        })
+    }
+}
+
+fn random_opening(number_of_pieces: usize, plies: usize, rng: &mut SmallRng) -> Vec<Move> {
+    let mut game = Game::new_with_size(number_of_pieces);
+    let mut opening = Vec::new();
+    for _ in 0..plies {
+        match game.legal_moves().choose(rng) {
+            Some((m, child)) if !child.is_finished() => {
+                opening.push(*m);
+                game = child.clone();
+            }
+            _ => break,
+        }
+    }
+    opening
+}
+
+fn play(
+    tournament: &Tournament,
+    opening: &[Move],
+    (x, x_agent): (usize, &mut dyn Agent),
+    (o, o_agent): (usize, &mut dyn Agent),
+) -> Result<GameRecord, String> {
+    let mut record = GameRecord::new(
+        tournament.number_of_pieces,
+        &tournament.agents[x].name,
+        &tournament.agents[o].name,
+    );
+    let mut game = Game::new_with_size(tournament.number_of_pieces);
+    for (row, col, size) in opening.iter() {
+        game = game.make_move(*row, *col, *size).unwrap();
+        record.moves.push((*row, *col, *size));
+    }
+
+    let (game, moves) = play_match_moves(game, x_agent, o_agent, |_| ()).map_err(|e| {
+        format!(
+            "{} against {}: {}",
+            tournament.agents[x].name, tournament.agents[o].name, e
+        )
+    })?;
+    record.moves.extend(moves);
+
+    record.winner = game.winner();
+    Ok(record)
+}
@@ -0,0 +1,188 @@
+extern crate getopts;
+use getopts::Options;
+use matryoshka_tic_tac_toe::tournament::{AgentSpec, Format, Results, Tournament};
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+fn print_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} -a AGENT -a AGENT [options]", program);
+    println!("{}", opts.usage(&brief));
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optmulti(
+        "a",
+        "agent",
+        "An agent to enter: solver, mcts or random, with options such as \
+         solver:difficulty=easy or mcts:playouts=500,rollout=random",
+        "AGENT",
+    );
+    opts.optopt(
+        "n",
+        "number",
+        "How many pieces to use, defaults to 4",
+        "PIECES",
+    );
+    opts.optopt("g", "games", "Games per pairing, defaults to 10", "GAMES");
+    opts.optflag(
+        "",
+        "gauntlet",
+        "Only play the first agent against the others, instead of a round robin",
+    );
+    opts.optopt(
+        "",
+        "openings",
+        "Random moves to start each game with, defaults to 2",
+        "PLIES",
+    );
+    opts.optopt("s", "seed", "Seed the openings and the agents", "SEED");
+    opts.optopt("r", "records", "Write the record of every game", "FILE");
+
+    opts.optflag("h", "help", "print this help menu");
+    let m = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    if m.opt_present("h") {
+        print_usage(&program, opts);
+        return;
+    }
+
+    let agents = match m
+        .opt_strs("a")
+        .iter()
+        .map(|spec| spec.parse::<AgentSpec>())
+        .collect::<Result<Vec<AgentSpec>, String>>()
+    {
+        Ok(agents) if agents.len() >= 2 => agents,
+        Ok(_) => {
+            eprintln!("A tournament needs at least two agents");
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let tournament = Tournament {
+        agents,
+        format: if m.opt_present("gauntlet") {
+            Format::Gauntlet
+        } else {
+            Format::RoundRobin
+        },
+        number_of_pieces: m.opt_get_default("n", 4).unwrap_or(4),
+        games: m.opt_get_default("g", 10).unwrap_or(10),
+        opening_plies: m.opt_get_default("openings", 2).unwrap_or(2),
+        seed: m
+            .opt_get::<u64>("s")
+            .ok()
+            .flatten()
+            .unwrap_or_else(rand::random),
+    };
+
+    let mut records = match m.opt_str("r").map(File::create) {
+        Some(Ok(file)) => Some(BufWriter::new(file)),
+        Some(Err(e)) => {
+            eprintln!("Couldn't create the records file: {}", e);
+            return;
+        }
+        None => None,
+    };
+
+    let mut played = 0;
+    let results = tournament.run(|record| {
+        played += 1;
+        println!(
+            "Game {}: {} - {} {}",
+            played,
+            record.x,
+            record.o,
+            record.result()
+        );
+        if let Some(records) = records.as_mut() {
+            writeln!(records, "{}", record).unwrap();
+        }
+    });
+    match results {
+        Ok(results) => print_results(&results),
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+fn print_results(results: &Results) {
+    let width = results
+        .names
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0);
+
+    println!();
+    println!("Wins-draws-losses of each row against each column");
+    print!("{:width$}", "", width = width);
+    for i in 0..results.names.len() {
+        print!(" {:>8}", i + 1);
+    }
+    println!();
+    for (i, name) in results.names.iter().enumerate() {
+        print!("{:width$}", name, width = width);
+        for (j, score) in results.table[i].iter().enumerate() {
+            if i == j || score.games() == 0 {
+                print!(" {:>8}", "-");
+            } else {
+                print!(
+                    " {:>8}",
+                    format!("{}-{}-{}", score.wins, score.draws, score.losses)
+                );
+            }
+        }
+        println!();
+    }
+
+    let mut standings = (0..results.names.len())
+        .map(|i| (i, results.total(i)))
+        .filter(|(_, total)| total.games() > 0)
+        .collect::<Vec<_>>();
+    standings.sort_by(|(_, a), (_, b)| b.fraction().partial_cmp(&a.fraction()).unwrap());
+
+    println!();
+    println!(
+        "{:>2} {:width$} {:>6} {:>6} {:>6} {:>6} {:>6} {:>16}",
+        "",
+        "Agent",
+        "Games",
+        "Won",
+        "Drawn",
+        "Lost",
+        "Score",
+        "Elo (95%)",
+        width = width
+    );
+    for (i, total) in standings {
+        let (elo, low, high) = total.elo();
+        println!(
+            "{:>2} {:width$} {:>6} {:>6} {:>6} {:>6} {:>5.1}% {:>+5.0} [{:+.0}, {:+.0}]",
+            i + 1,
+            results.names[i],
+            total.games(),
+            total.wins,
+            total.draws,
+            total.losses,
+            100.0 * total.fraction(),
+            elo,
+            low,
+            high,
+            width = width
+        );
+    }
+}
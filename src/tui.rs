@@ -0,0 +1,326 @@
+use crate::agent::{illegal, Agent};
+use crate::game::{Game, Move, PlayerKind, Winner};
+use crate::io::{format_move, glyph};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{
+    Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor,
+};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::io::{self, Write};
+
+const HELP: &str = "arrows move  0-9 or tab pick a piece  enter play  q quit";
+
+/// What a key press asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Play(Move),
+    Quit,
+}
+
+/// The square under the cursor and the piece picked from the hand of the
+/// player to move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Picker {
+    pub row: usize,
+    pub col: usize,
+    /// `None` once the hand is empty
+    pub size: Option<usize>,
+}
+
+impl Picker {
+    /// The centre square and the biggest piece in hand
+    pub fn new(game: &Game) -> Self {
+        Self {
+            row: 1,
+            col: 1,
+            size: hand(game).iter().max().copied(),
+        }
+    }
+
+    /// Moves the cursor, picks a piece, or plays the picked piece on the
+    /// square under the cursor if that is legal
+    pub fn key(&mut self, game: &Game, code: KeyCode) -> Option<Action> {
+        let hand = hand(game);
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.row = self.row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.row = (self.row + 1).min(2),
+            KeyCode::Left | KeyCode::Char('h') => self.col = self.col.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.col = (self.col + 1).min(2),
+            KeyCode::Char(c @ '0'..='9') => {
+                let size = c.to_digit(10).unwrap() as usize;
+                if hand.contains(&size) {
+                    self.size = Some(size);
+                }
+            }
+            KeyCode::Tab | KeyCode::Char('+') => {
+                self.size = match self.size {
+                    Some(size) => hand.iter().find(|piece| **piece > size).copied(),
+                    None => None,
+                }
+                .or_else(|| hand.first().copied());
+            }
+            KeyCode::BackTab | KeyCode::Char('-') => {
+                self.size = match self.size {
+                    Some(size) => hand.iter().rev().find(|piece| **piece < size).copied(),
+                    None => None,
+                }
+                .or_else(|| hand.last().copied());
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                if let Some(size) = self.size {
+                    if is_legal(game, (self.row, self.col, size)) {
+                        return Some(Action::Play((self.row, self.col, size)));
+                    }
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => return Some(Action::Quit),
+            _ => (),
+        }
+        None
+    }
+
+    /// Keeps the picked piece if it is still in hand, for the next turn
+    fn update(&mut self, game: &Game) {
+        let hand = hand(game);
+        if !self.size.is_some_and(|size| hand.contains(&size)) {
+            self.size = hand.iter().max().copied();
+        }
+    }
+}
+
+/// Plays a game full screen, the arrow keys picking a square and the number
+/// keys or tab a piece. The `cpu`, if any, answers every move as o. `None` if
+/// the game was quit before the end.
+pub fn run(number_of_pieces: usize, mut cpu: Option<&mut dyn Agent>) -> io::Result<Option<Game>> {
+    let _screen = Screen::enter()?;
+    let mut out = io::stdout();
+    let mut game = Game::new_with_size(number_of_pieces);
+    let mut picker = Picker::new(&game);
+    let mut last_move = None;
+    let mut message = String::new();
+
+    while !game.is_over() {
+        let kind = game.current_player_kind();
+        if let (Some(cpu), PlayerKind::O) = (cpu.as_mut(), kind) {
+            let status = format!("{} is thinking...", name(kind));
+            draw(&mut out, &game, None, last_move, &status, &message)?;
+            let (row, col, size) = cpu.choose_move(&game);
+            match game.clone().make_move(row, col, size) {
+                Ok(next) => {
+                    game = next;
+                    last_move = Some((row, col, size));
+                    picker.update(&game);
+                }
+                Err(_) if cpu.asks_again() => (),
+                Err(e) => return Err(io::Error::other(illegal(kind, (row, col, size), e))),
+            }
+            continue;
+        }
+
+        let status = match last_move {
+            Some(m) => format!("{} to move, last move {}", name(kind), format_move(m)),
+            None => format!("{} to move", name(kind)),
+        };
+        draw(&mut out, &game, Some(&picker), last_move, &status, &message)?;
+        let code = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                // Raw mode swallows ctrl-c
+                if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+                    return Ok(None);
+                }
+                key.code
+            }
+            _ => continue,
+        };
+        message.clear();
+        match picker.key(&game, code) {
+            Some(Action::Play((row, col, size))) => {
+                game = game.make_move(row, col, size).unwrap();
+                last_move = Some((row, col, size));
+                picker.update(&game);
+            }
+            Some(Action::Quit) => return Ok(None),
+            None if matches!(code, KeyCode::Enter | KeyCode::Char(' ')) => {
+                message = "That piece can't go there".to_string();
+            }
+            None => (),
+        }
+    }
+
+    let result = match game.winner() {
+        Some(Winner::X) => "x wins!",
+        Some(Winner::O) => "o wins!",
+        _ => "Tie!",
+    };
+    draw(&mut out, &game, None, last_move, result, "Press any key")?;
+    loop {
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                break;
+            }
+        }
+    }
+    Ok(Some(game))
+}
+
+// Raw mode on the alternate screen, for as long as it lives
+struct Screen;
+
+impl Screen {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Draws the board, both hands and the status bar. Squares where the picked
+/// piece can go are highlighted, the last move is bold and a winning line is
+/// shaded.
+pub fn draw<W: Write>(
+    out: &mut W,
+    game: &Game,
+    picker: Option<&Picker>,
+    last_move: Option<Move>,
+    status: &str,
+    message: &str,
+) -> io::Result<()> {
+    queue!(out, Clear(ClearType::All), MoveTo(2, 0))?;
+    queue!(
+        out,
+        SetAttribute(Attribute::Bold),
+        Print("Matryoshka tic-tac-toe")
+    )?;
+    queue!(out, SetAttribute(Attribute::Reset), MoveTo(4, 2))?;
+    for col in 0..3u8 {
+        queue!(out, Print(format!("    {}   ", (b'A' + col) as char)))?;
+    }
+
+    let line = |left: &str, middle: &str, right: &str| {
+        format!("{}{}{}", left, ["───────"; 3].join(middle), right)
+    };
+    queue!(out, MoveTo(4, 3), Print(line("┌", "┬", "┐")))?;
+    let winning = game.winning_line().concat();
+    for row in 0..3 {
+        let y = 4 + 2 * row as u16;
+        queue!(out, MoveTo(2, y), Print(format!("{} │", row + 1)))?;
+        for col in 0..3 {
+            let tile = game.tiles()[row][col];
+            let under_cursor = picker.is_some_and(|p| (p.row, p.col) == (row, col));
+            let legal = picker
+                .and_then(|p| p.size)
+                .is_some_and(|size| is_legal(game, (row, col, size)));
+            if legal {
+                queue!(out, SetBackgroundColor(Color::DarkGreen))?;
+            }
+            if under_cursor {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            if last_move.is_some_and(|(r, c, _)| (r, c) == (row, col)) {
+                queue!(out, SetAttribute(Attribute::Bold))?;
+            }
+            if winning.contains(&(row, col)) {
+                queue!(out, SetBackgroundColor(Color::DarkYellow))?;
+            }
+            match tile {
+                Some((kind, size)) => queue!(
+                    out,
+                    SetForegroundColor(colour(kind)),
+                    Print(format!(
+                        " {} {}{:<2} ",
+                        glyph(size, game.get_number_of_pieces() - 1),
+                        name(kind).to_uppercase(),
+                        size
+                    ))
+                )?,
+                None => queue!(out, Print("       "))?,
+            }
+            queue!(out, SetAttribute(Attribute::Reset), ResetColor, Print("│"))?;
+        }
+        let separator = match row {
+            2 => line("└", "┴", "┘"),
+            _ => line("├", "┼", "┤"),
+        };
+        queue!(out, MoveTo(4, y + 1), Print(separator))?;
+    }
+
+    // The hand of the player to move shows the picked piece
+    let (x, o) = &game.players;
+    for (i, player) in [x, o].iter().enumerate() {
+        let y = 11 + i as u16;
+        queue!(
+            out,
+            MoveTo(2, y),
+            SetForegroundColor(colour(player.kind)),
+            Print(format!("{}:", name(player.kind))),
+            ResetColor
+        )?;
+        for size in &player.pieces {
+            let picked = player.kind == game.current_player_kind()
+                && picker.is_some_and(|p| p.size == Some(*size));
+            if picked {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(
+                out,
+                Print(format!(" {}", size)),
+                SetAttribute(Attribute::Reset)
+            )?;
+        }
+    }
+
+    queue!(out, MoveTo(2, 14), Print(status))?;
+    queue!(
+        out,
+        MoveTo(2, 15),
+        SetForegroundColor(Color::Yellow),
+        Print(message),
+        ResetColor
+    )?;
+    queue!(
+        out,
+        MoveTo(2, 17),
+        SetAttribute(Attribute::Dim),
+        Print(HELP),
+        SetAttribute(Attribute::Reset)
+    )?;
+    out.flush()
+}
+
+fn colour(kind: PlayerKind) -> Color {
+    match kind {
+        PlayerKind::X => Color::Red,
+        PlayerKind::O => Color::Cyan,
+    }
+}
+
+fn name(kind: PlayerKind) -> &'static str {
+    match kind {
+        PlayerKind::X => "x",
+        PlayerKind::O => "o",
+    }
+}
+
+fn hand(game: &Game) -> Vec<usize> {
+    let (x, o) = &game.players;
+    let mut hand = match game.current_player_kind() {
+        PlayerKind::X => x.pieces.clone(),
+        PlayerKind::O => o.pieces.clone(),
+    };
+    hand.sort_unstable();
+    hand.dedup();
+    hand
+}
+
+fn is_legal(game: &Game, (row, col, size): Move) -> bool {
+    game.clone().make_move(row, col, size).is_ok()
+}
@@ -0,0 +1,337 @@
+use crate::game::{Game, Move, PlayerKind, Winner, MAX_PIECES};
+use crate::json;
+use crate::solver::{Analysis, Solver};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::{Message, WebSocket};
+
+/// How long a connection waits for a message before seeing to its search
+const POLL: Duration = Duration::from_millis(20);
+
+/// A command sent by the browser as one text message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    /// Starts again from an empty board, with the same number of pieces as
+    /// before unless `pieces` is given, from 1 to `game::MAX_PIECES`
+    NewGame { pieces: Option<usize> },
+    /// Plays a move for whoever's turn it is
+    Move { row: usize, col: usize, size: usize },
+    /// Asks for the position again
+    State,
+    /// Searches the position, with `info` on the way and `analysis` at the
+    /// end. Stopped after `timeout_ms`, or the server's timeout if not
+    /// given, and never later than the server's maximum.
+    Analyse { timeout_ms: Option<u64> },
+    /// Stops the search, which is answered with whatever it found
+    Stop,
+}
+
+/// An event sent to the browser as one text message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The position after every change, with the moves the player to move
+    /// can make
+    State {
+        game: Box<Game>,
+        legal_moves: Vec<Move>,
+    },
+    Move {
+        player: PlayerKind,
+        r#move: Move,
+    },
+    GameOver {
+        winner: Winner,
+    },
+    /// How a search is getting on. `nodes` stays 0 while it waits for
+    /// another connection's search to finish.
+    Info {
+        nodes: u64,
+        time_ms: u64,
+    },
+    /// The end of a search, see `solver::Analysis`. `score` is `None` if the
+    /// search was stopped.
+    Analysis {
+        best_move: Option<Move>,
+        score: Option<i8>,
+        pv: Vec<Move>,
+        nodes: u64,
+        time_ms: u64,
+    },
+    /// A command that couldn't be read or carried out. Nothing changes.
+    Error {
+        message: String,
+    },
+}
+
+impl From<json::Event> for Event {
+    fn from(event: json::Event) -> Self {
+        match event {
+            json::Event::State { game, legal_moves } => Event::State { game, legal_moves },
+            json::Event::Move { player, r#move } => Event::Move { player, r#move },
+            json::Event::GameOver { winner } => Event::GameOver { winner },
+            json::Event::Error { message } => Event::Error { message },
+        }
+    }
+}
+
+/// Plays games with browsers over WebSocket, one game per connection, pushing
+/// every change to the position and following searches as they go. Every
+/// connection shares one solver, and so its lookup, and searches wait their
+/// turn for it.
+pub struct Server {
+    solver: Mutex<Solver>,
+    nodes: Arc<AtomicU64>,
+    stop: Arc<AtomicBool>,
+    searches: AtomicU64,
+    /// The search holding the solver, only changed along with `stop`
+    active: Mutex<Option<u64>>,
+    info_interval: Duration,
+    timeout: Duration,
+    max_timeout: Duration,
+}
+
+// A search started by a connection
+struct Search {
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+    started: Instant,
+    deadline: Instant,
+    last_info: Instant,
+    results: Receiver<Analysis>,
+}
+
+impl Server {
+    pub fn new(solver: Solver) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        Self {
+            nodes: solver.node_counter(),
+            solver: Mutex::new(solver.with_stop(Arc::clone(&stop))),
+            stop,
+            searches: AtomicU64::new(0),
+            active: Mutex::new(None),
+            info_interval: Duration::from_millis(250),
+            timeout: Duration::from_secs(10),
+            max_timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// How long a search may take, including waiting for other searches to
+    /// finish, unless the command asks for something else
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The longest a command may ask a search to take, so that no one
+    /// connection holds the solver for longer
+    pub fn with_max_timeout(mut self, max_timeout: Duration) -> Self {
+        self.max_timeout = max_timeout;
+        self
+    }
+
+    /// How often `info` is sent while a search runs
+    pub fn with_info_interval(mut self, info_interval: Duration) -> Self {
+        self.info_interval = info_interval;
+        self
+    }
+
+    /// Serves connections until the listener fails, each on its own thread
+    pub fn serve(self: Arc<Self>, listener: TcpListener) -> io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            let server = Arc::clone(&self);
+            thread::spawn(move || {
+                if let Ok(socket) = tungstenite::accept(stream) {
+                    server.connection(socket);
+                }
+            });
+        }
+    }
+
+    fn connection(self: &Arc<Self>, mut socket: WebSocket<TcpStream>) {
+        // Reads time out so that the search can be followed in between
+        if socket.get_ref().set_read_timeout(Some(POLL)).is_err() {
+            return;
+        }
+        let mut game = Game::new();
+        let mut search: Option<Search> = None;
+        let mut events = vec![json::Event::state(&game).into()];
+
+        loop {
+            let sent = events.drain(..).all(|event| {
+                let text = serde_json::to_string(&event).unwrap();
+                socket.send(Message::Text(text)).is_ok()
+            });
+            if !sent {
+                break;
+            }
+
+            match socket.read() {
+                Ok(Message::Text(text)) => match serde_json::from_str::<Command>(&text) {
+                    Ok(command) => {
+                        // Anything but asking for the position again makes a
+                        // running search pointless
+                        if !matches!(command, Command::State | Command::Stop) {
+                            if let Some(stale) = search.take() {
+                                self.stop_search(&stale);
+                            }
+                        }
+                        match command {
+                            Command::NewGame { pieces } => {
+                                match pieces.unwrap_or(game.get_number_of_pieces()) {
+                                    pieces @ 1..=MAX_PIECES => {
+                                        game = Game::new_with_size(pieces);
+                                        events.push(json::Event::state(&game).into());
+                                    }
+                                    _ => events.push(
+                                        json::Event::error(&format!(
+                                            "Games have from 1 to {} pieces",
+                                            MAX_PIECES
+                                        ))
+                                        .into(),
+                                    ),
+                                }
+                            }
+                            Command::Move { .. } if game.is_over() => {
+                                events.push(json::Event::error("The game is over").into())
+                            }
+                            Command::Move { row, col, .. } if row > 2 || col > 2 => events
+                                .push(json::Event::error("Rows and columns go from 0 to 2").into()),
+                            Command::Move { row, col, size } => {
+                                let player = game.current_player_kind();
+                                match game.clone().make_move(row, col, size) {
+                                    Ok(next) => {
+                                        game = next;
+                                        events.push(Event::Move {
+                                            player,
+                                            r#move: (row, col, size),
+                                        });
+                                        events.push(json::Event::state(&game).into());
+                                        if game.is_over() {
+                                            events.push(Event::GameOver {
+                                                winner: game.winner().unwrap_or(Winner::Tie),
+                                            });
+                                        }
+                                    }
+                                    Err(e) => events.push(json::Event::error(e).into()),
+                                }
+                            }
+                            Command::State => events.push(json::Event::state(&game).into()),
+                            Command::Analyse { timeout_ms } => {
+                                let timeout = timeout_ms
+                                    .map(Duration::from_millis)
+                                    .unwrap_or(self.timeout)
+                                    .min(self.max_timeout);
+                                search = Some(self.search(game.clone(), timeout));
+                            }
+                            Command::Stop => match &search {
+                                Some(running) => self.stop_search(running),
+                                None => events
+                                    .push(json::Event::error("There is no search to stop").into()),
+                            },
+                        }
+                    }
+                    Err(e) => events.push(json::Event::error(&e.to_string()).into()),
+                },
+                // Pings are answered by tungstenite itself
+                Ok(_) => (),
+                Err(tungstenite::Error::Io(e))
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut => {}
+                Err(_) => break,
+            }
+
+            if let Some(running) = search.as_mut() {
+                match running.results.try_recv() {
+                    Ok(analysis) => {
+                        events.push(Event::Analysis {
+                            best_move: analysis.best_move,
+                            score: analysis.score,
+                            pv: analysis.pv,
+                            nodes: analysis.nodes,
+                            time_ms: running.started.elapsed().as_millis() as u64,
+                        });
+                        search = None;
+                    }
+                    Err(TryRecvError::Empty) => {
+                        if Instant::now() >= running.deadline {
+                            self.stop_search(running);
+                        }
+                        if running.last_info.elapsed() >= self.info_interval {
+                            running.last_info = Instant::now();
+                            let nodes = match *self.active.lock().unwrap() == Some(running.id) {
+                                true => self.nodes.load(Ordering::Relaxed),
+                                false => 0,
+                            };
+                            events.push(Event::Info {
+                                nodes,
+                                time_ms: running.started.elapsed().as_millis() as u64,
+                            });
+                        }
+                    }
+                    Err(TryRecvError::Disconnected) => search = None,
+                }
+            }
+        }
+
+        if let Some(running) = search {
+            self.stop_search(&running);
+        }
+    }
+
+    // Searches on another thread, once the solver is free
+    fn search(self: &Arc<Self>, game: Game, timeout: Duration) -> Search {
+        let id = self.searches.fetch_add(1, Ordering::Relaxed) + 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, results) = mpsc::channel();
+        let server = Arc::clone(self);
+        let waiting = Arc::clone(&cancelled);
+        thread::spawn(move || {
+            let mut solver = server.solver.lock().unwrap();
+            {
+                let mut active = server.active.lock().unwrap();
+                if waiting.load(Ordering::Relaxed) {
+                    let _ = sender.send(Analysis {
+                        best_move: None,
+                        score: None,
+                        pv: Vec::new(),
+                        nodes: 0,
+                    });
+                    return;
+                }
+                *active = Some(id);
+                server.stop.store(false, Ordering::Relaxed);
+            }
+            let analysis = solver.analyse(&game);
+            *server.active.lock().unwrap() = None;
+            let _ = sender.send(analysis);
+        });
+
+        let started = Instant::now();
+        Search {
+            id,
+            cancelled,
+            started,
+            deadline: started + timeout,
+            last_info: started,
+            results,
+        }
+    }
+
+    // Stops a search whether it is running or still waiting for the solver
+    fn stop_search(&self, search: &Search) {
+        search.cancelled.store(true, Ordering::Relaxed);
+        let active = self.active.lock().unwrap();
+        if *active == Some(search.id) {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use matryoshka_tic_tac_toe::io::parse_move;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::time::{Duration, Instant};
+
+struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Engine {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_engine"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Self {
+            child,
+            stdin,
+            stdout,
+        }
+    }
+
+    fn send(&mut self, command: &str) {
+        writeln!(self.stdin, "{}", command).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    // Every line up to and including the first one starting with `last`
+    fn read_until(&mut self, last: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            assert_ne!(self.stdout.read_line(&mut line).unwrap(), 0, "{:?}", lines);
+            let line = line.trim_end().to_string();
+            let done = line.starts_with(last);
+            lines.push(line);
+            if done {
+                return lines;
+            }
+        }
+    }
+}
+
+#[test]
+fn engine_over_pipes() {
+    let mut engine = Engine::start();
+
+    engine.send("uci");
+    let lines = engine.read_until("uciok");
+    assert!(lines[0].starts_with("id name"));
+    engine.send("isready");
+    assert_eq!(engine.read_until("readyok"), vec!["readyok"]);
+
+    // The 3 piece game is a tie
+    engine.send("newgame 3");
+    engine.send("position startpos");
+    engine.send("go");
+    let lines = engine.read_until("bestmove");
+    let info = lines[lines.len() - 2]
+        .split_whitespace()
+        .collect::<Vec<&str>>();
+    assert_eq!(info[..3], ["info", "score", "0"]);
+    let pv = info.iter().position(|word| *word == "pv").unwrap();
+    assert_eq!(lines.last().unwrap(), &format!("bestmove {}", info[pv + 1]));
+
+    // x finishes the left column
+    engine.send("position X2,-,-/-,O1,-/X1,-,- x 0 0,2");
+    engine.send("go");
+    let lines = engine.read_until("bestmove");
+    assert!(lines[0].starts_with("info score 10 "));
+    assert_eq!(lines[1], "bestmove 2A0");
+
+    engine.send("position X2,-,-/-,O1,-/X1,-,- x 0 0,2 moves 2A0");
+    engine.send("go");
+    assert_eq!(engine.read_until("bestmove"), vec!["bestmove none"]);
+
+    engine.send("position startpos moves 2B2 2B1");
+    assert!(engine.read_until("info string")[0].contains("illegal move 2B1"));
+    engine.send("bogus");
+    assert!(engine.read_until("info string")[0].contains("unknown command"));
+
+    // Far too big to solve, so only stopping it ends the search
+    engine.send("newgame 8");
+    engine.send("go");
+    let started = Instant::now();
+    engine.send("stop");
+    let lines = engine.read_until("bestmove");
+    // which the lookup doesn't know, so the move is only a guess and says so
+    assert!(lines[0].starts_with("info string stopped"));
+    assert!(lines[0].contains("guessing from"));
+    let best = lines.last().unwrap().strip_prefix("bestmove ").unwrap();
+    assert!(parse_move(best).is_ok());
+    assert!(started.elapsed() < Duration::from_secs(60));
+
+    engine.send("go 100");
+    engine.read_until("bestmove");
+
+    engine.send("quit");
+    assert!(engine.child.wait().unwrap().success());
+}